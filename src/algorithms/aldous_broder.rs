@@ -0,0 +1,64 @@
+use rand::seq::SliceRandom;
+
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
+
+use super::{connectable_neighbors, find_regions};
+
+/// The Aldous-Broder algorithm: wander randomly, connecting each cell the
+/// first time it is visited. Like Wilson's algorithm, this picks uniformly
+/// from all spanning trees, but it can take a long time to finish.
+pub struct AldousBroder {
+    visited: Vec<bool>,
+}
+
+impl AldousBroder {
+    pub fn new() -> Self {
+        Self {
+            visited: Vec::new()
+        }
+    }
+}
+
+impl Default for AldousBroder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MazeGenerator for AldousBroder {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let size = grid.get_size();
+        self.visited.clear();
+        self.visited.resize(size * size, false);
+
+        // The walk can't leave its region, so walk each region separately
+        let (regions, region_count) = find_regions(grid);
+        let mut region_cells = vec![Vec::new(); region_count];
+        for (index, region) in regions.iter().enumerate() {
+            region_cells[*region].push(index);
+        }
+
+        for cells in &region_cells {
+            let start = *cells.choose(rng).expect("regions are never empty");
+            let mut current = GridCoords::from_index(start, size);
+            self.visited[start] = true;
+            let mut remaining = cells.len() - 1;
+
+            while remaining > 0 {
+                let neighbors = connectable_neighbors(grid, current);
+                let next = *neighbors.choose(rng)
+                    .expect("regions with multiple cells are connected");
+                let next_index = next.to_index(size);
+                if !self.visited[next_index] {
+                    grid.connect(current, next);
+                    self.visited[next_index] = true;
+                    remaining -= 1;
+                }
+                current = next;
+            }
+        }
+    }
+}
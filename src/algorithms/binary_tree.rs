@@ -0,0 +1,50 @@
+use rand::seq::SliceRandom;
+
+use crate::direction::Direction;
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
+
+use super::{connect_components, connectable_neighbor};
+
+/// The binary tree algorithm: every cell connects either up or to the
+/// right. This produces long corridors along the top and right sides.
+pub struct BinaryTree;
+
+impl BinaryTree {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for BinaryTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MazeGenerator for BinaryTree {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let size = grid.get_size();
+        for y in 0..size {
+            for x in 0..size {
+                let cell = GridCoords { x, y };
+                let options: Vec<GridCoords> = [Direction::Up, Direction::Right]
+                    .into_iter()
+                    .filter_map(|direction| {
+                        connectable_neighbor(grid, cell, direction)
+                    })
+                    .collect();
+
+                if let Some(neighbor) = options.choose(rng) {
+                    grid.connect(cell, *neighbor);
+                }
+            }
+        }
+
+        // Walls from the parent tile can cut off a cell from both of its
+        // options, so patch up any separate trees
+        connect_components(grid, rng);
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::direction::Direction;
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
+
+use super::{connect_components, connectable_neighbor, UnionFind};
+
+/// Eller's algorithm: build the maze one row at a time, only keeping track
+/// of which cells in the current row are already connected.
+pub struct Eller;
+
+impl Eller {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for Eller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MazeGenerator for Eller {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let size = grid.get_size();
+        let mut sets = UnionFind::new(size * size);
+
+        for y in 0..size {
+            let is_last_row = y == size - 1;
+
+            // Randomly join neighbors in the row that are not already
+            // connected. The last row joins everything it can.
+            for x in 0..(size - 1) {
+                let cell = GridCoords { x, y };
+                let right = match connectable_neighbor(grid, cell, Direction::Right) {
+                    Some(right) => right,
+                    None => continue
+                };
+
+                let should_join = is_last_row || rng.gen();
+                if should_join && sets.union(cell.to_index(size), right.to_index(size)) {
+                    grid.connect(cell, right);
+                }
+            }
+
+            if is_last_row {
+                break;
+            }
+
+            // Each set in this row connects upwards at least once (where
+            // the walls allow it) so it isn't cut off from the rest of the
+            // maze.
+            // (A BTreeMap so the sets are visited in a consistent order)
+            let mut up_connections: BTreeMap<usize, Vec<GridCoords>> =
+                BTreeMap::new();
+            for x in 0..size {
+                let cell = GridCoords { x, y };
+                if connectable_neighbor(grid, cell, Direction::Up).is_some() {
+                    let set = sets.find(cell.to_index(size));
+                    up_connections.entry(set).or_default().push(cell);
+                }
+            }
+
+            for cells in up_connections.values_mut() {
+                cells.shuffle(rng);
+                let count = rng.gen_range(1..=cells.len());
+                for cell in &cells[0..count] {
+                    let above = cell.get_adjacent(Direction::Up);
+                    sets.union(cell.to_index(size), above.to_index(size));
+                    grid.connect(*cell, above);
+                }
+            }
+        }
+
+        // Walls from the parent tile can leave sets with no way up, so
+        // patch up any separate trees
+        connect_components(grid, rng);
+    }
+}
@@ -0,0 +1,78 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
+
+use super::connectable_neighbors;
+
+/// The growing tree algorithm: keep a list of active cells and repeatedly
+/// extend the maze from one of them. Always picking the newest cell behaves
+/// like the recursive backtracker, always picking a random one behaves like
+/// Prim's algorithm.
+pub struct GrowingTree {
+    /// Probability of picking the newest active cell rather than a random
+    /// one
+    newest_probability: f64,
+    visited: Vec<bool>,
+    active: Vec<GridCoords>,
+}
+
+impl GrowingTree {
+    pub fn new(newest_probability: f64) -> Self {
+        Self {
+            newest_probability,
+            visited: Vec::new(),
+            active: Vec::new(),
+        }
+    }
+
+    fn grow_tree(&mut self, grid: &mut Grid, start: GridCoords, rng: &mut TileRng) {
+        let size = grid.get_size();
+        self.active.clear();
+        self.active.push(start);
+        self.visited[start.to_index(size)] = true;
+
+        while !self.active.is_empty() {
+            let index = if rng.gen_bool(self.newest_probability) {
+                self.active.len() - 1
+            } else {
+                rng.gen_range(0..self.active.len())
+            };
+            let cell = self.active[index];
+
+            let unvisited: Vec<GridCoords> = connectable_neighbors(grid, cell)
+                .into_iter()
+                .filter(|neighbor| !self.visited[neighbor.to_index(size)])
+                .collect();
+
+            match unvisited.choose(rng) {
+                Some(neighbor) => {
+                    grid.connect(cell, *neighbor);
+                    self.visited[neighbor.to_index(size)] = true;
+                    self.active.push(*neighbor);
+                },
+                None => {
+                    self.active.remove(index);
+                }
+            }
+        }
+    }
+}
+
+impl MazeGenerator for GrowingTree {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let size = grid.get_size();
+        self.visited.clear();
+        self.visited.resize(size * size, false);
+
+        // One tree per region of the grid
+        for index in 0..size * size {
+            if !self.visited[index] {
+                self.grow_tree(grid, GridCoords::from_index(index, size), rng);
+            }
+        }
+    }
+}
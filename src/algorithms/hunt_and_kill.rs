@@ -0,0 +1,96 @@
+use rand::seq::SliceRandom;
+
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
+
+use super::connectable_neighbors;
+
+/// The hunt-and-kill algorithm: random walk through unvisited cells. When
+/// stuck, scan the grid for an unvisited cell next to a visited one and
+/// continue from there.
+pub struct HuntAndKill {
+    visited: Vec<bool>,
+    /// Every cell before this index has been visited, so hunting can
+    /// start here instead of scanning the whole grid every time.
+    hunt_start: usize,
+}
+
+impl HuntAndKill {
+    pub fn new() -> Self {
+        Self {
+            visited: Vec::new(),
+            hunt_start: 0,
+        }
+    }
+
+    fn unvisited_neighbors(&self, grid: &Grid, cell: GridCoords) -> Vec<GridCoords> {
+        connectable_neighbors(grid, cell)
+            .into_iter()
+            .filter(|neighbor| !self.visited[neighbor.to_index(grid.get_size())])
+            .collect()
+    }
+
+    /// Find an unvisited cell to continue from. Cells next to the visited
+    /// part of the maze are connected to it. If there are none, the
+    /// current region is finished, so start fresh from any unvisited cell.
+    fn hunt(&mut self, grid: &mut Grid, rng: &mut TileRng) -> Option<GridCoords> {
+        let size = grid.get_size();
+        while self.hunt_start < size * size && self.visited[self.hunt_start] {
+            self.hunt_start += 1;
+        }
+
+        let mut first_unvisited = None;
+        for index in self.hunt_start..size * size {
+            if self.visited[index] {
+                continue;
+            }
+
+            let cell = GridCoords::from_index(index, size);
+            let visited_neighbors: Vec<GridCoords> = connectable_neighbors(grid, cell)
+                .into_iter()
+                .filter(|neighbor| self.visited[neighbor.to_index(size)])
+                .collect();
+
+            if let Some(neighbor) = visited_neighbors.choose(rng) {
+                grid.connect(cell, *neighbor);
+                return Some(cell);
+            }
+
+            first_unvisited.get_or_insert(cell);
+        }
+
+        first_unvisited
+    }
+}
+
+impl Default for HuntAndKill {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MazeGenerator for HuntAndKill {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let size = grid.get_size();
+        self.visited.clear();
+        self.visited.resize(size * size, false);
+        self.hunt_start = 0;
+
+        let mut current = Some(GridCoords { x: 0, y: 0 });
+        while let Some(cell) = current {
+            self.visited[cell.to_index(size)] = true;
+
+            // Kill: walk to a random unvisited neighbor
+            let neighbors = self.unvisited_neighbors(grid, cell);
+            current = match neighbors.choose(rng) {
+                Some(neighbor) => {
+                    grid.connect(cell, *neighbor);
+                    Some(*neighbor)
+                },
+                None => self.hunt(grid, rng)
+            };
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use rand::seq::SliceRandom;
+
+use crate::grid::Grid;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
+
+use super::{connectable_edges, UnionFind};
+
+/// Randomized Kruskal's algorithm: visit every possible connection in a
+/// random order and keep it if it joins two separate trees.
+pub struct Kruskal;
+
+impl Kruskal {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for Kruskal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MazeGenerator for Kruskal {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let size = grid.get_size();
+        let mut sets = UnionFind::new(size * size);
+        let mut edges = connectable_edges(grid);
+        edges.shuffle(rng);
+
+        for (a, b) in edges {
+            if sets.union(a.to_index(size), b.to_index(size)) {
+                grid.connect(a, b);
+            }
+        }
+    }
+}
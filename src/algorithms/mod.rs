@@ -0,0 +1,175 @@
+//! Maze generation algorithms other than the original recursive
+//! backtracker, which lives in `dfs.rs`. See `maze_generator.rs` for the
+//! common interface.
+
+mod aldous_broder;
+mod binary_tree;
+mod eller;
+mod growing_tree;
+mod hunt_and_kill;
+mod kruskal;
+mod prim;
+mod sidewinder;
+mod wilson;
+
+pub use aldous_broder::AldousBroder;
+pub use binary_tree::BinaryTree;
+pub use eller::Eller;
+pub use growing_tree::GrowingTree;
+pub use hunt_and_kill::HuntAndKill;
+pub use kruskal::Kruskal;
+pub use prim::Prim;
+pub use sidewinder::Sidewinder;
+pub use wilson::Wilson;
+
+use rand::seq::SliceRandom;
+
+use crate::direction::Direction;
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::seed::TileRng;
+
+/// Neighbors of a cell that the maze is allowed to connect to
+fn connectable_neighbors(grid: &Grid, cell: GridCoords) -> Vec<GridCoords> {
+    cell.get_neighbors(grid.get_size())
+        .into_iter()
+        .filter(|neighbor| grid.can_connect(cell, *neighbor))
+        .collect()
+}
+
+/// The neighbor in the given direction, if it is inside the grid and the
+/// maze is allowed to connect to it.
+fn connectable_neighbor(
+    grid: &Grid,
+    cell: GridCoords,
+    direction: Direction
+) -> Option<GridCoords> {
+    let size = grid.get_size();
+    let in_bounds = match direction {
+        Direction::Right => cell.x + 1 < size,
+        Direction::Up => cell.y + 1 < size,
+        Direction::Left => cell.x > 0,
+        Direction::Down => cell.y > 0,
+    };
+    if !in_bounds {
+        return None;
+    }
+
+    let neighbor = cell.get_adjacent(direction);
+    if grid.can_connect(cell, neighbor) {
+        Some(neighbor)
+    } else {
+        None
+    }
+}
+
+/// Every pair of adjacent cells that can be connected, listing each pair
+/// once.
+fn connectable_edges(grid: &Grid) -> Vec<(GridCoords, GridCoords)> {
+    let size = grid.get_size();
+    let mut edges = Vec::new();
+    for y in 0..size {
+        for x in 0..size {
+            let cell = GridCoords { x, y };
+            for direction in [Direction::Right, Direction::Up] {
+                if let Some(neighbor) = connectable_neighbor(grid, cell, direction) {
+                    edges.push((cell, neighbor));
+                }
+            }
+        }
+    }
+
+    edges
+}
+
+/// Disjoint sets of cells, indexed by cell index
+pub(crate) struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    pub(crate) fn new(count: usize) -> Self {
+        Self {
+            parents: (0..count).collect()
+        }
+    }
+
+    pub(crate) fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+
+        // Path compression
+        let mut current = index;
+        while self.parents[current] != root {
+            let next = self.parents[current];
+            self.parents[current] = root;
+            current = next;
+        }
+
+        root
+    }
+
+    /// Merge the sets containing a and b. Returns false if they were already
+    /// in the same set.
+    pub(crate) fn union(&mut self, a: usize, b: usize) -> bool {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a == root_b {
+            return false;
+        }
+
+        self.parents[root_b] = root_a;
+        true
+    }
+}
+
+/// Label each cell with the region it belongs to, i.e. the cells it could
+/// reach if there were no walls besides the blocked connections. Returns
+/// the label of each cell (by index) and the number of regions.
+fn find_regions(grid: &Grid) -> (Vec<usize>, usize) {
+    let size = grid.get_size();
+    let mut sets = UnionFind::new(size * size);
+    for (a, b) in connectable_edges(grid) {
+        sets.union(a.to_index(size), b.to_index(size));
+    }
+
+    let mut labels = vec![usize::MAX; size * size];
+    let mut region_count = 0;
+    for index in 0..size * size {
+        let root = sets.find(index);
+        if labels[root] == usize::MAX {
+            labels[root] = region_count;
+            region_count += 1;
+        }
+        labels[index] = labels[root];
+    }
+
+    (labels, region_count)
+}
+
+/// Some algorithms (binary tree, sidewinder, Eller's) only connect cells in
+/// certain directions. Blocked connections inherited from the parent tile
+/// can leave them with several disconnected trees in the same region. This
+/// joins them with random connections, like the tail end of Kruskal's
+/// algorithm. The grid must not already contain loops.
+fn connect_components(grid: &mut Grid, rng: &mut TileRng) {
+    let size = grid.get_size();
+    let mut sets = UnionFind::new(size * size);
+    let mut candidates = Vec::new();
+    for (a, b) in connectable_edges(grid) {
+        if grid.is_connected(a, b) {
+            sets.union(a.to_index(size), b.to_index(size));
+        } else {
+            candidates.push((a, b));
+        }
+    }
+
+    candidates.shuffle(rng);
+    for (a, b) in candidates {
+        if sets.union(a.to_index(size), b.to_index(size)) {
+            grid.connect(a, b);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use rand::Rng;
+
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
+
+use super::connectable_neighbors;
+
+/// Randomized Prim's algorithm: grow a tree by repeatedly picking a random
+/// connection from the tree to a cell outside it.
+pub struct Prim {
+    visited: Vec<bool>,
+    /// Connections from a cell in the tree to a neighbor that may or may not
+    /// be in the tree yet.
+    frontier: Vec<(GridCoords, GridCoords)>,
+}
+
+impl Prim {
+    pub fn new() -> Self {
+        Self {
+            visited: Vec::new(),
+            frontier: Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, grid: &Grid, cell: GridCoords) {
+        self.visited[cell.to_index(grid.get_size())] = true;
+        for neighbor in connectable_neighbors(grid, cell) {
+            if !self.visited[neighbor.to_index(grid.get_size())] {
+                self.frontier.push((cell, neighbor));
+            }
+        }
+    }
+
+    fn grow_tree(&mut self, grid: &mut Grid, start: GridCoords, rng: &mut TileRng) {
+        self.frontier.clear();
+        self.visit(grid, start);
+
+        while !self.frontier.is_empty() {
+            let index = rng.gen_range(0..self.frontier.len());
+            let (cell, neighbor) = self.frontier.swap_remove(index);
+            if self.visited[neighbor.to_index(grid.get_size())] {
+                continue;
+            }
+
+            grid.connect(cell, neighbor);
+            self.visit(grid, neighbor);
+        }
+    }
+}
+
+impl Default for Prim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MazeGenerator for Prim {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let size = grid.get_size();
+        self.visited.clear();
+        self.visited.resize(size * size, false);
+
+        // One tree per region of the grid
+        for index in 0..size * size {
+            if !self.visited[index] {
+                self.grow_tree(grid, GridCoords::from_index(index, size), rng);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::direction::Direction;
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
+
+use super::{connect_components, connectable_neighbor};
+
+/// The sidewinder algorithm: carve horizontal runs of cells, and connect
+/// each run upwards from a random cell in the run.
+pub struct Sidewinder {
+    run: Vec<GridCoords>,
+}
+
+impl Sidewinder {
+    pub fn new() -> Self {
+        Self {
+            run: Vec::new()
+        }
+    }
+
+    /// Connect the current run to the row above from a random cell
+    /// (if possible) and start a new run.
+    fn close_run(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let exits: Vec<(GridCoords, GridCoords)> = self.run
+            .iter()
+            .filter_map(|cell| {
+                connectable_neighbor(grid, *cell, Direction::Up)
+                    .map(|neighbor| (*cell, neighbor))
+            })
+            .collect();
+
+        if let Some((cell, neighbor)) = exits.choose(rng) {
+            grid.connect(*cell, *neighbor);
+        }
+        self.run.clear();
+    }
+}
+
+impl Default for Sidewinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MazeGenerator for Sidewinder {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let size = grid.get_size();
+        for y in 0..size {
+            self.run.clear();
+            for x in 0..size {
+                let cell = GridCoords { x, y };
+                self.run.push(cell);
+
+                let right = connectable_neighbor(grid, cell, Direction::Right);
+                let up = connectable_neighbor(grid, cell, Direction::Up);
+
+                // The top row has no choice but to keep going right. 
+                // Elsewhere, flip a coin whether to close the run.
+                let keep_going = match right {
+                    Some(_) => y == size - 1 || up.is_none() || rng.gen(),
+                    None => false
+                };
+
+                if let (true, Some(neighbor)) = (keep_going, right) {
+                    grid.connect(cell, neighbor);
+                } else {
+                    self.close_run(grid, rng);
+                }
+            }
+        }
+
+        // Walls from the parent tile can prevent a run from connecting
+        // upwards, so patch up any separate trees
+        connect_components(grid, rng);
+    }
+}
@@ -0,0 +1,85 @@
+use rand::seq::SliceRandom;
+
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
+
+use super::{connectable_neighbors, find_regions};
+
+/// Wilson's algorithm: add loop-erased random walks to the tree until every
+/// cell is included. This picks uniformly at random from all possible
+/// spanning trees.
+pub struct Wilson {
+    in_tree: Vec<bool>,
+    /// The direction the random walk last left each cell in. Overwriting
+    /// this when the walk revisits a cell is what erases the loops.
+    next_cell: Vec<Option<GridCoords>>,
+}
+
+impl Wilson {
+    pub fn new() -> Self {
+        Self {
+            in_tree: Vec::new(),
+            next_cell: Vec::new(),
+        }
+    }
+
+    fn add_walk(&mut self, grid: &mut Grid, start: GridCoords, rng: &mut TileRng) {
+        let size = grid.get_size();
+
+        // Random walk until we hit the tree
+        let mut current = start;
+        while !self.in_tree[current.to_index(size)] {
+            let neighbors = connectable_neighbors(grid, current);
+            let next = *neighbors.choose(rng)
+                .expect("cells outside the tree must have a neighbor");
+            self.next_cell[current.to_index(size)] = Some(next);
+            current = next;
+        }
+
+        // Retrace the walk without the loops and add it to the tree
+        let mut current = start;
+        while !self.in_tree[current.to_index(size)] {
+            let next = self.next_cell[current.to_index(size)]
+                .expect("walk must continue until it reaches the tree");
+            grid.connect(current, next);
+            self.in_tree[current.to_index(size)] = true;
+            current = next;
+        }
+    }
+}
+
+impl Default for Wilson {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MazeGenerator for Wilson {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
+        let size = grid.get_size();
+        self.in_tree.clear();
+        self.in_tree.resize(size * size, false);
+        self.next_cell.clear();
+        self.next_cell.resize(size * size, None);
+
+        // A random walk can never leave its region, so each region needs
+        // its own tree. Start each one from a random cell.
+        let (regions, region_count) = find_regions(grid);
+        let mut region_cells = vec![Vec::new(); region_count];
+        for (index, region) in regions.iter().enumerate() {
+            region_cells[*region].push(index);
+        }
+        for cells in &region_cells {
+            let root = *cells.choose(rng).expect("regions are never empty");
+            self.in_tree[root] = true;
+        }
+
+        for index in 0..size * size {
+            if !self.in_tree[index] {
+                self.add_walk(grid, GridCoords::from_index(index, size), rng);
+            }
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! Texture atlases for the custom shader. An atlas is a single row of 16
+//! square tiles, one for each combination of connection bits. The walls
+//! atlas is indexed by a cell's connection bits, the connections atlas by
+//! its solution bits. North is at the top of each tile.
+//!
+//! The atlases can either be hand-made PNGs, a theme (see the `theme`
+//! module) or drawn here from an `AtlasStyle`.
+
+use std::fs::read;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use image::{Rgba, RgbaImage};
+
+use crate::direction::Direction;
+use crate::error::{MazeError, Result};
+use crate::theme::Theme;
+
+/// Number of tiles in an atlas, one for every combination of the 4
+/// connection bits
+pub const ATLAS_TILES: u32 = 16;
+
+const UP: u8 = Direction::Up.bit();
+const DOWN: u8 = Direction::Down.bit();
+const LEFT: u8 = Direction::Left.bit();
+const RIGHT: u8 = Direction::Right.bit();
+
+/// Where the tiles are within an atlas image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasLayout {
+    /// Width and height of each tile in pixels, not counting padding
+    pub tile_size: u32,
+    /// Pixels around each tile that repeat the tile's edge, so sampling
+    /// near the edge of a tile never picks up its neighbor
+    pub padding: u32,
+}
+
+impl AtlasLayout {
+    /// Work out the layout of an atlas image, given how much padding it has
+    pub fn from_dimensions(width: u32, height: u32, padding: u32) -> Result<Self> {
+        let layout = height.checked_sub(2 * padding)
+            .filter(|&tile_size| tile_size > 0)
+            .map(|tile_size| Self { tile_size, padding });
+
+        match layout {
+            Some(layout) if layout.dimensions() == (width, height) => Ok(layout),
+            _ => Err(MazeError::InvalidInput(format!(
+                "a {}x{} atlas can't be split into {} square tiles with {} pixels of padding",
+                width,
+                height,
+                ATLAS_TILES,
+                padding
+            )))
+        }
+    }
+
+    /// Distance between the starts of neighboring tiles
+    pub fn tile_stride(&self) -> u32 {
+        self.tile_size + 2 * self.padding
+    }
+
+    /// Width and height of the whole atlas image
+    pub fn dimensions(&self) -> (u32, u32) {
+        (ATLAS_TILES * self.tile_stride(), self.tile_stride())
+    }
+}
+
+/// How to draw the corners where walls meet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CornerStyle {
+    Square,
+    Round,
+}
+
+/// Parameters for drawing atlases. The defaults look like the hand-made
+/// test atlases: white on black, so the shader can use the red channel as
+/// a mask.
+#[derive(Clone, Debug)]
+pub struct AtlasStyle {
+    /// Width and height of each tile in pixels, not counting padding
+    pub tile_size: u32,
+    /// Thickness of the wall along each side of a cell. Neighboring cells
+    /// each draw half of the wall between them.
+    pub wall_thickness: u32,
+    /// Width of the solution path
+    pub path_thickness: u32,
+    pub wall_color: Rgba<u8>,
+    pub floor_color: Rgba<u8>,
+    /// Color of the solution path. The rest of the connections atlas is
+    /// transparent
+    pub path_color: Rgba<u8>,
+    pub corners: CornerStyle,
+    pub padding: u32,
+}
+
+impl Default for AtlasStyle {
+    fn default() -> Self {
+        Self {
+            tile_size: 16,
+            wall_thickness: 2,
+            path_thickness: 2,
+            wall_color: Rgba([255, 255, 255, 255]),
+            floor_color: Rgba([0, 0, 0, 255]),
+            path_color: Rgba([255, 255, 255, 255]),
+            corners: CornerStyle::Square,
+            padding: 1,
+        }
+    }
+}
+
+impl AtlasStyle {
+    pub fn get_layout(&self) -> AtlasLayout {
+        AtlasLayout {
+            tile_size: self.tile_size,
+            padding: self.padding,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let size = self.tile_size;
+        if size == 0 || size > 1024 {
+            return Err(MazeError::InvalidInput(format!(
+                "atlas tiles must be from 1 to 1024 pixels wide, not {}",
+                size
+            )));
+        }
+
+        if 2 * self.wall_thickness > size || self.path_thickness > size {
+            return Err(MazeError::InvalidInput(format!(
+                "walls {} pixels thick and paths {} pixels thick don't fit in a {} pixel tile",
+                self.wall_thickness,
+                self.path_thickness,
+                size
+            )));
+        }
+
+        if self.padding > size {
+            return Err(MazeError::InvalidInput(format!(
+                "{} pixels of padding is more than the tile size",
+                self.padding
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Draw the walls atlas. A cell with no connections at all is solid
+    /// wall.
+    pub fn make_walls_atlas(&self) -> Result<RgbaImage> {
+        self.validate()?;
+        Ok(self.make_atlas(|bits, x, y| {
+            if self.is_wall(bits, x, y) {
+                self.wall_color
+            } else {
+                self.floor_color
+            }
+        }))
+    }
+
+    /// Draw the connections atlas, with a path from the center of the cell
+    /// out to each side in the solution bits.
+    pub fn make_connections_atlas(&self) -> Result<RgbaImage> {
+        self.validate()?;
+        Ok(self.make_atlas(|bits, x, y| {
+            if self.is_path(bits, x, y) {
+                self.path_color
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        }))
+    }
+
+    /// Draw every tile, with the padding copied from the nearest pixel of
+    /// the tile. draw gets the bits and the center of the pixel within the
+    /// tile, with y pointing down.
+    fn make_atlas(&self, draw: impl Fn(u8, f64, f64) -> Rgba<u8>) -> RgbaImage {
+        let layout = self.get_layout();
+        let (width, height) = layout.dimensions();
+        let stride = layout.tile_stride();
+        let to_tile = |pixel: u32| {
+            let inside = pixel.saturating_sub(self.padding).min(self.tile_size - 1);
+            inside as f64 + 0.5
+        };
+
+        RgbaImage::from_fn(width, height, |x, y| {
+            let bits = (x / stride) as u8;
+            draw(bits, to_tile(x % stride), to_tile(y))
+        })
+    }
+
+    fn is_wall(&self, bits: u8, x: f64, y: f64) -> bool {
+        if bits == 0 {
+            return true;
+        }
+
+        let size = self.tile_size as f64;
+        let thickness = self.wall_thickness as f64;
+        let walls = [
+            (UP, y < thickness),
+            (DOWN, y > size - thickness),
+            (LEFT, x < thickness),
+            (RIGHT, x > size - thickness),
+        ];
+        if walls.iter().any(|&(bit, inside)| bits & bit == 0 && inside) {
+            return true;
+        }
+
+        // Distance from the nearest corner of the tile, and which walls
+        // meet there
+        let (dx, horizontal) = if x < size / 2.0 { (x, LEFT) } else { (size - x, RIGHT) };
+        let (dy, vertical) = if y < size / 2.0 { (y, UP) } else { (size - y, DOWN) };
+
+        match self.corners {
+            // Every corner gets a post so walls from the neighboring cells
+            // always join up
+            CornerStyle::Square => dx < thickness && dy < thickness,
+            CornerStyle::Round => {
+                let post = dx * dx + dy * dy < thickness * thickness;
+
+                // Where two walls of this cell meet, fill in the inside of
+                // the corner with a curve
+                let both_walls = bits & horizontal == 0 && bits & vertical == 0;
+                let (fx, fy) = (2.0 * thickness - dx, 2.0 * thickness - dy);
+                let fillet = both_walls &&
+                    dx < 2.0 * thickness &&
+                    dy < 2.0 * thickness &&
+                    fx * fx + fy * fy > thickness * thickness;
+
+                post || fillet
+            }
+        }
+    }
+
+    fn is_path(&self, bits: u8, x: f64, y: f64) -> bool {
+        if bits == 0 {
+            return false;
+        }
+
+        let center = self.tile_size as f64 / 2.0;
+        let half_width = self.path_thickness as f64 / 2.0;
+        let (dx, dy) = (x - center, y - center);
+
+        let on_spoke = |bit: u8, along: f64, across: f64| {
+            bits & bit != 0 && along >= 0.0 && across.abs() < half_width
+        };
+        let spokes = on_spoke(UP, -dy, dx) ||
+            on_spoke(DOWN, dy, dx) ||
+            on_spoke(LEFT, -dx, dy) ||
+            on_spoke(RIGHT, dx, dy);
+
+        let joint = match self.corners {
+            CornerStyle::Square => dx.abs() < half_width && dy.abs() < half_width,
+            CornerStyle::Round => dx * dx + dy * dy < half_width * half_width,
+        };
+
+        spokes || joint
+    }
+}
+
+/// Where the wall and solution atlases for a tileset come from
+#[derive(Clone, Debug)]
+pub enum AtlasSource {
+    /// Hand-made PNGs. These can't have padding.
+    Files {
+        walls: PathBuf,
+        connections: PathBuf,
+    },
+    /// Draw the atlases from a style
+    Generated(AtlasStyle),
+    /// A theme directory or manifest, see the `theme` module
+    Theme(PathBuf),
+}
+
+impl Default for AtlasSource {
+    fn default() -> Self {
+        Self::Files {
+            walls: PathBuf::from("assets/walls-test.png"),
+            connections: PathBuf::from("assets/connections-test.png"),
+        }
+    }
+}
+
+/// Atlases ready to be written into a tileset
+pub struct AtlasFiles {
+    pub walls_png: Vec<u8>,
+    pub connections_png: Vec<u8>,
+    pub layout: AtlasLayout,
+}
+
+impl AtlasSource {
+    /// Read or draw the atlases. Both atlases must have the same layout
+    /// since the shader indexes them the same way.
+    pub fn load(&self) -> Result<AtlasFiles> {
+        match self {
+            Self::Files { walls, connections } => {
+                let walls_png = read(walls)
+                    .map_err(MazeError::io("could not read walls atlas", walls))?;
+                let connections_png = read(connections).map_err(
+                    MazeError::io("could not read connections atlas", connections)
+                )?;
+
+                let layout = read_layout(&walls_png)?;
+                if read_layout(&connections_png)? != layout {
+                    return Err(MazeError::InvalidInput(format!(
+                        "{} and {} must be the same size",
+                        walls.display(),
+                        connections.display()
+                    )));
+                }
+
+                Ok(AtlasFiles {
+                    walls_png,
+                    connections_png,
+                    layout,
+                })
+            },
+            Self::Generated(style) => Ok(AtlasFiles {
+                walls_png: encode_png(&style.make_walls_atlas()?)?,
+                connections_png: encode_png(&style.make_connections_atlas()?)?,
+                layout: style.get_layout(),
+            }),
+            Self::Theme(path) => Theme::read(path)?.load()
+        }
+    }
+}
+
+fn read_layout(png: &[u8]) -> Result<AtlasLayout> {
+    let (width, height) = image::io::Reader::new(Cursor::new(png))
+        .with_guessed_format()
+        .map_err(|error| MazeError::InvalidInput(error.to_string()))?
+        .into_dimensions()?;
+    AtlasLayout::from_dimensions(width, height, 0)
+}
+
+pub(crate) fn encode_png(image: &RgbaImage) -> Result<Vec<u8>> {
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageOutputFormat::Png)?;
+    Ok(png.into_inner())
+}
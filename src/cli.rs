@@ -0,0 +1,917 @@
+use std::path::PathBuf;
+use std::thread;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use image::Rgba;
+use rand::Rng;
+
+use nested_mazes::atlas::{AtlasSource, AtlasStyle, CornerStyle};
+use nested_mazes::config::{MazeConfig, RootExit, DEFAULT_GRID_SIZE};
+use nested_mazes::direction::Direction;
+use nested_mazes::error::{MazeError, Result};
+use nested_mazes::export::{export_level, export_region, render_walls};
+use nested_mazes::maze_generator::AlgorithmSchedule;
+use nested_mazes::render::{Atlas, AtlasRenderer};
+use nested_mazes::server::TileServer;
+use nested_mazes::subtree::{SubtreeFormat, SubtreeLayout};
+use nested_mazes::svg::{render_svg, SvgStyle};
+use nested_mazes::theme::Theme;
+use nested_mazes::tile::Tile;
+use nested_mazes::tile_format::{
+    ChannelLayout,
+    ContentLayout,
+    ImageContainer,
+    TileFormat,
+    TileGeometry
+};
+use nested_mazes::tileset::{find_missing_files, MazeTileset, TilesetAssets};
+use nested_mazes::verify::verify_tileset;
+use nested_mazes::walls::WallStyle;
+
+/// Generate a 3D Tiles tileset of mazes nested inside the hallways of
+/// their parent mazes.
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Command
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a full tileset on disk
+    Generate(GenerateArgs),
+    /// Print a single tile of the maze without writing a tileset
+    Inspect(InspectArgs),
+    /// Check that a generated tileset directory has all of its files
+    Validate(ValidateArgs),
+    /// Read back every tile of a generated tileset and check that the maze
+    /// is valid, including across the seams between tiles
+    Verify(VerifyArgs),
+    /// Serve a tileset over HTTP, generating tiles as they are requested
+    Serve(ServeArgs),
+    /// Stitch every tile of one level into a single image
+    Export(ExportArgs),
+    /// Draw a tile or a rectangle of tiles as an SVG
+    Svg(SvgArgs),
+    /// Render a tile or a rectangle of tiles to an image using the texture
+    /// atlases, like the CesiumJS shader does
+    Render(RenderArgs),
+}
+
+/// Options that determine the contents of the maze
+#[derive(Args)]
+struct MazeArgs {
+    /// Random seed. If omitted, one is chosen at random and printed so the
+    /// maze can be reproduced later
+    #[arg(short, long)]
+    seed: Option<u64>,
+
+    /// Width and height of each tile in cells. Must be a power of 2 from
+    /// 2 to 256
+    #[arg(short, long, default_value_t = DEFAULT_GRID_SIZE)]
+    grid_size: usize,
+
+    /// An exit in the outer wall of the root tile, written as SIDE:INDEX
+    /// where SIDE is one of right, up, left, down. Repeat for multiple
+    /// exits, there must be an even number of them since the solution
+    /// pairs them up. Defaults to down:3 and up:5 on a 16x16 grid
+    #[arg(short, long = "exit", value_name = "SIDE:INDEX", value_parser = parse_exit)]
+    exits: Vec<RootExit>,
+
+    /// Maze generation algorithm. One of backtracker, prim, kruskal,
+    /// wilson, aldous-broder, eller, binary-tree, sidewinder,
+    /// hunt-and-kill, growing-tree[:PROBABILITY]. To switch algorithms
+    /// at deeper levels, add LEVEL=ALGORITHM entries separated by commas,
+    /// e.g. backtracker,3=wilson
+    #[arg(short, long, value_name = "SCHEDULE", default_value = "backtracker")]
+    algorithm: AlgorithmSchedule,
+}
+
+impl MazeArgs {
+    fn make_config(&self) -> Result<MazeConfig> {
+        let seed = match self.seed {
+            Some(seed) => seed,
+            None => rand::thread_rng().gen()
+        };
+
+        let exits = if self.exits.is_empty() {
+            MazeConfig::default_exits(self.grid_size)
+        } else {
+            self.exits.clone()
+        };
+
+        let config = MazeConfig::new(
+            seed,
+            self.grid_size,
+            exits,
+            self.algorithm.clone()
+        )?;
+        println!("Using seed {}", seed);
+
+        Ok(config)
+    }
+}
+
+#[derive(Args)]
+struct GenerateArgs {
+    #[command(flatten)]
+    maze: MazeArgs,
+
+    /// Directory to write the tileset to. Any existing contents will be
+    /// deleted!
+    #[arg(short, long, default_value = "output/maze")]
+    output: PathBuf,
+
+    /// Number of levels of detail in the tileset
+    #[arg(short, long, default_value_t = 7)]
+    levels: usize,
+
+    #[command(flatten)]
+    assets: AssetArgs,
+
+    #[command(flatten)]
+    subtrees: SubtreeArgs,
+
+    #[command(flatten)]
+    tile_format: TileFormatArgs,
+
+    /// Number of worker threads. Defaults to the number of CPUs
+    #[arg(short, long)]
+    threads: Option<usize>,
+}
+
+/// How the implicit tileset is split into subtree files
+#[derive(Args)]
+struct SubtreeArgs {
+    /// Number of levels in each subtree file, at most 10
+    #[arg(long, default_value_t = 4)]
+    subtree_levels: usize,
+
+    /// Write subtrees as binary .subtree files or as JSON
+    #[arg(long, value_enum, default_value = "binary")]
+    subtree_format: SubtreeFileFormat,
+}
+
+impl SubtreeArgs {
+    fn make_layout(&self) -> Result<SubtreeLayout> {
+        SubtreeLayout::new(self.subtree_levels, self.subtree_format.to_subtree_format())
+    }
+}
+
+/// How each tile is encoded
+#[derive(Args)]
+struct TileFormatArgs {
+    /// Which channels the connection and solution bits go in. r8-packed
+    /// puts both in one channel
+    #[arg(long, value_enum, default_value = "rgb8")]
+    texture_layout: TextureLayout,
+
+    /// Image format of the feature ID textures
+    #[arg(long, value_enum, default_value = "png")]
+    texture_format: TextureFormat,
+
+    /// How each tile is split into files. glb shares the geometry between
+    /// tiles, embedded-glb makes each GLB self-contained and gltf writes
+    /// the feature ID textures as separate images
+    #[arg(long, value_enum, default_value = "glb")]
+    tile_content: TileContent,
+
+    /// Geometry of each tile. quad is a flat square for the custom shader
+    /// to draw on, walls builds real 3D walls so the maze shows up in any
+    /// glTF viewer
+    #[arg(long, value_enum, default_value = "quad")]
+    geometry: Geometry,
+
+    #[command(flatten)]
+    wall_style: WallStyleArgs,
+}
+
+impl TileFormatArgs {
+    fn make_format(&self) -> TileFormat {
+        let geometry = match self.geometry {
+            Geometry::Quad => TileGeometry::Quad,
+            Geometry::Walls => TileGeometry::Walls(self.wall_style.make_style()),
+        };
+
+        TileFormat {
+            layout: self.texture_layout.to_channel_layout(),
+            container: self.texture_format.to_image_container(),
+            content: self.tile_content.to_content_layout(),
+            geometry,
+        }
+    }
+}
+
+/// How the walls look with --geometry walls
+#[derive(Args)]
+struct WallStyleArgs {
+    /// Height of the walls in cells
+    #[arg(long, default_value_t = 0.5)]
+    wall_height: f32,
+
+    /// Thickness of the walls as a fraction of a cell
+    #[arg(long, default_value_t = 0.2)]
+    wall_thickness: f32,
+
+    /// Width of the solution ribbon as a fraction of a cell
+    #[arg(long, default_value_t = 0.3)]
+    solution_width: f32,
+
+    /// Leave out the floor under the walls
+    #[arg(long)]
+    no_floor: bool,
+
+    /// Leave out the ribbon along the solution
+    #[arg(long)]
+    no_solution: bool,
+
+    #[arg(long, value_name = "COLOR", default_value = "606070", value_parser = parse_color)]
+    wall_color: Rgba<u8>,
+
+    #[arg(long, value_name = "COLOR", default_value = "e0e0d0", value_parser = parse_color)]
+    floor_color: Rgba<u8>,
+
+    #[arg(long, value_name = "COLOR", default_value = "e03020", value_parser = parse_color)]
+    solution_color: Rgba<u8>,
+}
+
+impl WallStyleArgs {
+    fn make_style(&self) -> WallStyle {
+        WallStyle {
+            height: self.wall_height,
+            thickness: self.wall_thickness,
+            solution_width: self.solution_width,
+            floor: !self.no_floor,
+            solution: !self.no_solution,
+            wall_color: self.wall_color,
+            floor_color: self.floor_color,
+            solution_color: self.solution_color,
+        }
+    }
+}
+
+/// Static files included in the tileset
+#[derive(Args)]
+struct AssetArgs {
+    /// Texture atlas of walls, indexed by connection bits
+    #[arg(long, default_value = "assets/walls-test.png")]
+    walls: PathBuf,
+
+    /// Texture atlas of solution paths, indexed by solution bits
+    #[arg(long, default_value = "assets/connections-test.png")]
+    connections: PathBuf,
+
+    /// Use the atlases from a theme directory or manifest instead of
+    /// --walls and --connections
+    #[arg(long, value_name = "PATH", conflicts_with = "generate_atlases")]
+    theme: Option<PathBuf>,
+
+    /// Draw the atlases with the --atlas-* options instead of copying
+    /// --walls and --connections
+    #[arg(long)]
+    generate_atlases: bool,
+
+    #[command(flatten)]
+    atlas_style: AtlasStyleArgs,
+}
+
+impl AssetArgs {
+    fn make_assets(&self) -> TilesetAssets {
+        let atlases = if let Some(theme) = &self.theme {
+            AtlasSource::Theme(theme.clone())
+        } else if self.generate_atlases {
+            AtlasSource::Generated(self.atlas_style.make_style())
+        } else {
+            AtlasSource::Files {
+                walls: self.walls.clone(),
+                connections: self.connections.clone(),
+            }
+        };
+
+        TilesetAssets { atlases }
+    }
+}
+
+/// How to draw atlases with --generate-atlases
+#[derive(Args)]
+struct AtlasStyleArgs {
+    /// Width and height of each atlas tile in pixels
+    #[arg(long, default_value_t = 16)]
+    atlas_tile_size: u32,
+
+    /// Thickness of the walls on each side of a cell in pixels
+    #[arg(long, default_value_t = 2)]
+    atlas_wall_thickness: u32,
+
+    /// Width of the solution path in pixels
+    #[arg(long, default_value_t = 2)]
+    atlas_path_thickness: u32,
+
+    /// Colors are written as RRGGBB or RRGGBBAA in hex, with an optional #.
+    /// The Sandcastle shader only looks at the red channel, so it does its
+    /// own coloring
+    #[arg(long, value_name = "COLOR", default_value = "ffffff", value_parser = parse_color)]
+    atlas_wall_color: Rgba<u8>,
+
+    #[arg(long, value_name = "COLOR", default_value = "000000", value_parser = parse_color)]
+    atlas_floor_color: Rgba<u8>,
+
+    #[arg(long, value_name = "COLOR", default_value = "ffffff", value_parser = parse_color)]
+    atlas_path_color: Rgba<u8>,
+
+    #[arg(long, value_enum, default_value_t = Corners::Square)]
+    atlas_corners: Corners,
+
+    /// Pixels of padding around each atlas tile, copied from the tile's
+    /// edges to avoid seams between cells
+    #[arg(long, default_value_t = 1)]
+    atlas_padding: u32,
+}
+
+impl AtlasStyleArgs {
+    fn make_style(&self) -> AtlasStyle {
+        AtlasStyle {
+            tile_size: self.atlas_tile_size,
+            wall_thickness: self.atlas_wall_thickness,
+            path_thickness: self.atlas_path_thickness,
+            wall_color: self.atlas_wall_color,
+            floor_color: self.atlas_floor_color,
+            path_color: self.atlas_path_color,
+            corners: self.atlas_corners.to_corner_style(),
+            padding: self.atlas_padding,
+        }
+    }
+}
+
+#[derive(Args)]
+struct InspectArgs {
+    #[command(flatten)]
+    maze: MazeArgs,
+
+    /// Level of the tile to inspect, 0 is the root
+    #[arg(long, default_value_t = 0)]
+    level: usize,
+
+    /// x coordinate of the tile within its level
+    #[arg(short, default_value_t = 0)]
+    x: usize,
+
+    /// y coordinate of the tile within its level
+    #[arg(short, default_value_t = 0)]
+    y: usize,
+
+    /// Read the tile from a GLB or .gltf file instead of generating it. The maze
+    /// options and tile coordinates are ignored
+    #[arg(long, value_name = "PATH")]
+    glb: Option<PathBuf>,
+
+    /// Also save a contrast-boosted image of the feature IDs to this path
+    #[arg(long)]
+    image: Option<PathBuf>,
+
+    /// File format for --image
+    #[arg(long, value_enum, default_value_t = ImageFormat::Png)]
+    image_format: ImageFormat,
+}
+
+#[derive(Args)]
+struct ValidateArgs {
+    /// Directory containing a generated tileset
+    #[arg(default_value = "output/maze")]
+    directory: PathBuf,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    #[command(flatten)]
+    maze: MazeArgs,
+
+    /// Level to export. The image is (grid size * 2^level) pixels wide
+    #[arg(short, long, default_value_t = 4)]
+    level: usize,
+
+    /// Where to save the image of the connection and solution bits, one
+    /// pixel per cell. The format is chosen from the file extension
+    #[arg(short, long, default_value = "output/level.png")]
+    output: PathBuf,
+
+    /// Also draw the walls as a black and white image, two pixels per cell
+    #[arg(long, value_name = "PATH")]
+    walls: Option<PathBuf>,
+
+    /// Draw the solution in the walls image
+    #[arg(long)]
+    solution: bool,
+}
+
+#[derive(Args)]
+struct SvgArgs {
+    #[command(flatten)]
+    maze: MazeArgs,
+
+    /// Level of the tiles to draw, 0 is the root
+    #[arg(long, default_value_t = 0)]
+    level: usize,
+
+    /// x coordinate of the bottom left tile within its level
+    #[arg(short, default_value_t = 0)]
+    x: usize,
+
+    /// y coordinate of the bottom left tile within its level
+    #[arg(short, default_value_t = 0)]
+    y: usize,
+
+    /// Number of tiles to draw from west to east
+    #[arg(long, default_value_t = 1)]
+    columns: usize,
+
+    /// Number of tiles to draw from south to north
+    #[arg(long, default_value_t = 1)]
+    rows: usize,
+
+    /// Where to save the SVG
+    #[arg(short, long, default_value = "output/maze.svg")]
+    output: PathBuf,
+
+    /// Width and height of each cell in SVG units
+    #[arg(long, default_value_t = 10.0)]
+    cell_size: f64,
+
+    /// Any SVG color, e.g. black or #336699
+    #[arg(long, default_value = "black")]
+    wall_color: String,
+
+    #[arg(long, default_value_t = 1.0)]
+    wall_width: f64,
+
+    /// Also draw the solution path
+    #[arg(long)]
+    solution: bool,
+
+    #[arg(long, default_value = "red")]
+    solution_color: String,
+
+    #[arg(long, default_value_t = 2.0)]
+    solution_width: f64,
+
+    /// Fill color behind the maze. Transparent if omitted
+    #[arg(long, value_name = "COLOR")]
+    background: Option<String>,
+}
+
+impl SvgArgs {
+    fn make_style(&self) -> SvgStyle {
+        SvgStyle {
+            cell_size: self.cell_size,
+            wall_color: self.wall_color.clone(),
+            wall_width: self.wall_width,
+            solution_color: self.solution.then(|| self.solution_color.clone()),
+            solution_width: self.solution_width,
+            background_color: self.background.clone(),
+        }
+    }
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    #[command(flatten)]
+    maze: MazeArgs,
+
+    /// Level of the tiles to render, 0 is the root
+    #[arg(long, default_value_t = 0)]
+    level: usize,
+
+    /// x coordinate of the bottom left tile within its level
+    #[arg(short, default_value_t = 0)]
+    x: usize,
+
+    /// y coordinate of the bottom left tile within its level
+    #[arg(short, default_value_t = 0)]
+    y: usize,
+
+    /// Number of tiles to render from west to east
+    #[arg(long, default_value_t = 1)]
+    columns: usize,
+
+    /// Number of tiles to render from south to north
+    #[arg(long, default_value_t = 1)]
+    rows: usize,
+
+    /// Render a tile from a GLB or .gltf file instead of generating it. The maze
+    /// options and tile coordinates are ignored
+    #[arg(long, value_name = "PATH")]
+    glb: Option<PathBuf>,
+
+    /// Where to save the image. The format is chosen from the file
+    /// extension
+    #[arg(short, long, default_value = "output/render.png")]
+    output: PathBuf,
+
+    /// Width and height of each cell in pixels
+    #[arg(short, long, default_value_t = 16)]
+    pixels_per_cell: u32,
+
+    /// Texture atlas of walls, indexed by connection bits
+    #[arg(long, default_value = "assets/walls-test.png")]
+    walls: PathBuf,
+
+    /// Texture atlas of solution paths, indexed by solution bits
+    #[arg(long, default_value = "assets/connections-test.png")]
+    connections: PathBuf,
+
+    /// Pixels of padding around each atlas tile. This is atlas_padding in
+    /// the tileset metadata
+    #[arg(long, default_value_t = 0)]
+    padding: u32,
+
+    /// Use the atlases from a theme directory or manifest instead of
+    /// --walls, --connections and --padding
+    #[arg(long, value_name = "PATH")]
+    theme: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    /// Directory containing a generated tileset
+    #[arg(default_value = "output/maze")]
+    directory: PathBuf,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    #[command(flatten)]
+    maze: MazeArgs,
+
+    /// Number of levels of detail to advertise in the tileset. Tiles are
+    /// only generated when requested, so this can be much deeper than
+    /// what would fit on disk
+    #[arg(short, long, default_value_t = 16)]
+    levels: usize,
+
+    #[command(flatten)]
+    assets: AssetArgs,
+
+    #[command(flatten)]
+    subtrees: SubtreeArgs,
+
+    #[command(flatten)]
+    tile_format: TileFormatArgs,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// Port to listen on
+    #[arg(short, long, default_value_t = 8080)]
+    port: u16,
+
+    /// Maximum number of generated tiles to keep in memory
+    #[arg(long, default_value_t = 4096)]
+    cache_size: usize,
+
+    /// Number of requests to handle at once. Defaults to the number of CPUs
+    #[arg(short, long)]
+    threads: Option<usize>,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Corners {
+    Square,
+    Round,
+}
+
+impl Corners {
+    fn to_corner_style(self) -> CornerStyle {
+        match self {
+            Self::Square => CornerStyle::Square,
+            Self::Round => CornerStyle::Round,
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum SubtreeFileFormat {
+    Binary,
+    Json,
+}
+
+impl SubtreeFileFormat {
+    fn to_subtree_format(self) -> SubtreeFormat {
+        match self {
+            Self::Binary => SubtreeFormat::Binary,
+            Self::Json => SubtreeFormat::Json,
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum TextureLayout {
+    Rgb8,
+    R8Packed,
+}
+
+impl TextureLayout {
+    fn to_channel_layout(self) -> ChannelLayout {
+        match self {
+            Self::Rgb8 => ChannelLayout::Rgb8,
+            Self::R8Packed => ChannelLayout::R8Packed,
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum TextureFormat {
+    Png,
+    Webp,
+}
+
+impl TextureFormat {
+    fn to_image_container(self) -> ImageContainer {
+        match self {
+            Self::Png => ImageContainer::Png,
+            Self::Webp => ImageContainer::WebP,
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Geometry {
+    Quad,
+    Walls,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum TileContent {
+    Glb,
+    EmbeddedGlb,
+    Gltf,
+}
+
+impl TileContent {
+    fn to_content_layout(self) -> ContentLayout {
+        match self {
+            Self::Glb => ContentLayout::SharedGeometry,
+            Self::EmbeddedGlb => ContentLayout::Embedded,
+            Self::Gltf => ContentLayout::Gltf,
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum ImageFormat {
+    Png,
+    Bmp,
+    Tga,
+}
+
+impl ImageFormat {
+    fn to_output_format(self) -> image::ImageOutputFormat {
+        match self {
+            Self::Png => image::ImageOutputFormat::Png,
+            Self::Bmp => image::ImageOutputFormat::Bmp,
+            Self::Tga => image::ImageOutputFormat::Tga,
+        }
+    }
+}
+
+fn parse_exit(value: &str) -> std::result::Result<RootExit, String> {
+    let (side, index) = value.split_once(':')
+        .ok_or_else(|| "expected SIDE:INDEX, e.g. down:3".to_string())?;
+
+    let direction = match side {
+        "right" => Direction::Right,
+        "up" => Direction::Up,
+        "left" => Direction::Left,
+        "down" => Direction::Down,
+        _ => return Err(format!(
+            "unknown side '{}', expected right, up, left or down",
+            side
+        ))
+    };
+
+    let index = index.parse()
+        .map_err(|_| format!("'{}' is not a valid cell index", index))?;
+
+    Ok(RootExit { direction, index })
+}
+
+/// The number of threads asked for, or one per CPU
+fn get_thread_count(threads: Option<usize>) -> usize {
+    threads.unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(|count| count.get())
+            .unwrap_or(1)
+    })
+}
+
+fn parse_color(value: &str) -> std::result::Result<Rgba<u8>, String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    let error = || format!("'{}' is not a color, expected RRGGBB or RRGGBBAA", value);
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return Err(error());
+    }
+
+    let mut color = [255; 4];
+    for (component, digits) in color.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let digits = std::str::from_utf8(digits).map_err(|_| error())?;
+        *component = u8::from_str_radix(digits, 16).map_err(|_| error())?;
+    }
+
+    Ok(Rgba(color))
+}
+
+impl Cli {
+    pub fn run(&self) -> Result<()> {
+        match &self.command {
+            Command::Generate(args) => Self::generate(args),
+            Command::Inspect(args) => Self::inspect(args),
+            Command::Validate(args) => Self::validate(args),
+            Command::Verify(args) => Self::verify(args),
+            Command::Serve(args) => Self::serve(args),
+            Command::Export(args) => Self::export(args),
+            Command::Svg(args) => Self::svg(args),
+            Command::Render(args) => Self::render(args),
+        }
+    }
+
+    fn generate(args: &GenerateArgs) -> Result<()> {
+        let config = args.maze.make_config()?;
+        let assets = args.assets.make_assets();
+        let tileset = MazeTileset::new(
+            &args.output,
+            args.levels,
+            config,
+            assets,
+            args.subtrees.make_layout()?,
+            args.tile_format.make_format(),
+            get_thread_count(args.threads)
+        )?;
+        tileset.generate()?;
+        println!("Wrote tileset to {}", args.output.display());
+
+        Ok(())
+    }
+
+    fn inspect(args: &InspectArgs) -> Result<()> {
+        let tile = match &args.glb {
+            Some(path) => Tile::read(path)?,
+            None => {
+                let config = args.maze.make_config()?;
+                Tile::generate(&config, args.level, args.x, args.y)?
+            }
+        };
+        let grid_size = tile.get_grid().get_size() as u32;
+
+        println!("Tile {}.{}.{}", tile.level, tile.x, tile.y);
+        print!("{:?}", tile.get_grid());
+
+        if let Some(path) = &args.image {
+            let mut file = std::fs::File::create(path)
+                .map_err(MazeError::io("could not create image", path))?;
+            image::write_buffer_with_format(
+                &mut file,
+                &tile.get_grid().to_debug_image_bytes(),
+                grid_size,
+                grid_size,
+                image::ColorType::Rgb8,
+                args.image_format.to_output_format()
+            )?;
+            println!("Wrote debug image to {}", path.display());
+        }
+
+        Ok(())
+    }
+
+    fn validate(args: &ValidateArgs) -> Result<()> {
+        let missing = find_missing_files(&args.directory)?;
+        if missing.is_empty() {
+            println!("{} is a complete tileset", args.directory.display());
+            return Ok(());
+        }
+
+        for path in &missing {
+            eprintln!("missing {}", path.display());
+        }
+
+        Err(MazeError::InvalidInput(format!(
+            "{} is missing {} files",
+            args.directory.display(),
+            missing.len()
+        )))
+    }
+
+    fn verify(args: &VerifyArgs) -> Result<()> {
+        let report = verify_tileset(&args.directory)?;
+        for violation in &report.violations {
+            eprintln!("{}", violation);
+        }
+        println!("Checked {} tiles", report.tiles_checked);
+
+        if report.violations.is_empty() {
+            println!("{} is a valid maze", args.directory.display());
+            return Ok(());
+        }
+
+        Err(MazeError::InvalidData(format!(
+            "found {} problems in {}",
+            report.violations.len(),
+            args.directory.display()
+        )))
+    }
+
+    fn export(args: &ExportArgs) -> Result<()> {
+        let config = args.maze.make_config()?;
+        let level_image = export_level(&config, args.level)?;
+        level_image.save(&args.output)?;
+        println!(
+            "Wrote {}x{} level image to {}",
+            level_image.width(),
+            level_image.height(),
+            args.output.display()
+        );
+
+        if let Some(path) = &args.walls {
+            render_walls(&level_image, args.solution).save(path)?;
+            println!("Wrote walls image to {}", path.display());
+        }
+
+        Ok(())
+    }
+
+    fn svg(args: &SvgArgs) -> Result<()> {
+        let config = args.maze.make_config()?;
+        let cells = export_region(
+            &config,
+            args.level,
+            args.x..args.x.saturating_add(args.columns),
+            args.y..args.y.saturating_add(args.rows)
+        )?;
+        let svg = render_svg(&cells, &args.make_style());
+        std::fs::write(&args.output, svg)
+            .map_err(MazeError::io("could not write SVG", &args.output))?;
+        println!(
+            "Wrote {}x{} cells to {}",
+            cells.width(),
+            cells.height(),
+            args.output.display()
+        );
+
+        Ok(())
+    }
+
+    fn render(args: &RenderArgs) -> Result<()> {
+        let (walls, connections) = match &args.theme {
+            Some(path) => {
+                let atlases = Theme::read(path)?.load()?;
+                let padding = atlases.layout.padding;
+                (
+                    Atlas::from_png(&atlases.walls_png, padding)?,
+                    Atlas::from_png(&atlases.connections_png, padding)?
+                )
+            },
+            None => (
+                Atlas::read(&args.walls, args.padding)?,
+                Atlas::read(&args.connections, args.padding)?
+            )
+        };
+        let renderer = AtlasRenderer::new(walls, connections, args.pixels_per_cell)?;
+
+        let image = match &args.glb {
+            Some(path) => renderer.render_grid(Tile::read(path)?.get_grid())?,
+            None => {
+                let config = args.maze.make_config()?;
+                let cells = export_region(
+                    &config,
+                    args.level,
+                    args.x..args.x.saturating_add(args.columns),
+                    args.y..args.y.saturating_add(args.rows)
+                )?;
+                renderer.render(&cells)?
+            }
+        };
+        image.save(&args.output)?;
+        println!(
+            "Wrote {}x{} image to {}",
+            image.width(),
+            image.height(),
+            args.output.display()
+        );
+
+        Ok(())
+    }
+
+    fn serve(args: &ServeArgs) -> Result<()> {
+        let config = args.maze.make_config()?;
+        let server = TileServer::new(
+            config,
+            args.levels,
+            args.assets.make_assets(),
+            args.subtrees.make_layout()?,
+            args.tile_format.make_format(),
+            args.cache_size,
+            get_thread_count(args.threads)
+        )?;
+        server.serve(&format!("{}:{}", args.host, args.port))
+    }
+}
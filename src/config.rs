@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+
+use crate::direction::Direction;
+use crate::error::{MazeError, Result};
+use crate::maze_generator::AlgorithmSchedule;
+
+/// Tiles are 16x16 unless otherwise specified
+pub const DEFAULT_GRID_SIZE: usize = 16;
+/// Each tile subdivides into 4 children, so the grid must be at least 2x2
+pub const MIN_GRID_SIZE: usize = 2;
+/// Cell coordinates along a side of the tile must fit in a byte
+pub const MAX_GRID_SIZE: usize = 256;
+
+/// An opening in the outer wall of the root tile.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+pub struct RootExit {
+    /// Which side of the root tile the exit is on
+    pub direction: Direction,
+    /// Which cell along that side has the exit. For the top and bottom
+    /// sides this is the x coordinate, for the left and right sides it is
+    /// the y coordinate.
+    pub index: usize
+}
+
+/// Everything that determines the contents of the maze. Generating twice
+/// with the same config produces identical tiles.
+#[derive(Clone, Debug)]
+pub struct MazeConfig {
+    pub seed: u64,
+    /// Width and height of each tile in cells
+    pub grid_size: usize,
+    pub root_exits: Vec<RootExit>,
+    /// Algorithm used to carve the maze at each level
+    pub algorithms: AlgorithmSchedule,
+}
+
+impl MazeConfig {
+    pub fn new(
+        seed: u64,
+        grid_size: usize,
+        root_exits: Vec<RootExit>,
+        algorithms: AlgorithmSchedule
+    ) -> Result<Self> {
+        let config = Self {
+            seed,
+            grid_size,
+            root_exits,
+            algorithms
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// The deepest tileset that can be generated with this grid size.
+    /// Connections are located by their cell coordinates across a whole
+    /// level, which must fit in 64 bits, and tile coordinates must fit in a
+    /// usize.
+    pub fn max_levels(&self) -> usize {
+        let cell_bits = u64::BITS - self.grid_size.trailing_zeros();
+        cell_bits.min(usize::BITS) as usize
+    }
+
+    /// An entrance at the bottom and an exit at the top. For the default
+    /// 16x16 grid, these are at x = 3 and x = 5 respectively
+    pub fn default_exits(grid_size: usize) -> Vec<RootExit> {
+        vec![
+            RootExit { direction: Direction::Down, index: 3 * grid_size / 16 },
+            RootExit { direction: Direction::Up, index: 5 * grid_size / 16 },
+        ]
+    }
+
+    fn validate(&self) -> Result<()> {
+        let size = self.grid_size;
+        if !size.is_power_of_two() || !(MIN_GRID_SIZE..=MAX_GRID_SIZE).contains(&size) {
+            return Err(MazeError::InvalidInput(format!(
+                "grid size must be a power of 2 from {} to {}, got {}",
+                MIN_GRID_SIZE,
+                MAX_GRID_SIZE,
+                size
+            )));
+        }
+
+        // The solver looks for paths between pairs of exits, so with fewer
+        // than 2 there would be no solution at all, and with an odd number
+        // the leftover exit's path would dead-end.
+        if self.root_exits.len() < 2 {
+            return Err(MazeError::InvalidInput(
+                "the root tile needs at least 2 exits".to_string()
+            ));
+        }
+
+        if !self.root_exits.len().is_multiple_of(2) {
+            return Err(MazeError::InvalidInput(format!(
+                "the root tile needs an even number of exits, got {}",
+                self.root_exits.len()
+            )));
+        }
+
+        let mut seen = HashSet::new();
+        for exit in &self.root_exits {
+            if exit.index >= size {
+                return Err(MazeError::InvalidInput(format!(
+                    "exit index {} is out of range, it must be less than {}",
+                    exit.index,
+                    size
+                )));
+            }
+
+            if !seen.insert(exit) {
+                return Err(MazeError::InvalidInput(format!(
+                    "duplicate exit {:?} {}",
+                    exit.direction,
+                    exit.index
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,16 +1,16 @@
 use std::collections::HashSet;
 
 use rand::Rng;
-use rand::rngs::ThreadRng;
 
-use crate::grid_coords::{GridCoords, GRID_SIZE};
+use crate::grid_coords::GridCoords;
 use crate::direction::Direction;
 use crate::grid::Grid;
+use crate::maze_generator::MazeGenerator;
+use crate::seed::TileRng;
 
 pub struct DFSMaze {
     visited: HashSet<GridCoords>,
     stack: Vec<GridCoords>,
-    rng: ThreadRng
 }
 
 impl DFSMaze {
@@ -18,54 +18,66 @@ impl DFSMaze {
         Self {
             visited: HashSet::new(),
             stack: Vec::new(),
-            rng: rand::thread_rng()
         }
     }
 
-    pub fn make_maze(&mut self, grid: &mut Grid, start_cell: GridCoords) {
+    pub fn make_maze(
+        &mut self,
+        grid: &mut Grid,
+        start_cell: GridCoords,
+        rng: &mut TileRng
+    ) {
         self.stack.clear();
         self.stack.push(start_cell);
 
-        while self.stack.len() > 0 {
+        while !self.stack.is_empty() {
             let current = self.stack[self.stack.len() - 1];
             self.visited.insert(current);
 
-            let unvisited_neighbors: Vec<GridCoords> = current.get_neighbors()
+            let unvisited_neighbors: Vec<GridCoords> = current.get_neighbors(grid.get_size())
                 .into_iter()
                 .filter(|x| !self.visited.contains(x) && grid.can_connect(current, *x))
                 .collect();
 
             // Out of options so backtrack
-            if unvisited_neighbors.len() == 0 {
+            if unvisited_neighbors.is_empty() {
                 self.stack.pop();
                 continue;
             }
 
             // Two roads diverged in a wood and I randomly picked one.
             let rand_index: usize = 
-                self.rng.gen_range(0..unvisited_neighbors.len());
+                rng.gen_range(0..unvisited_neighbors.len());
             let neighbor = unvisited_neighbors[rand_index];
             grid.connect(current, neighbor);
             self.stack.push(neighbor);
         }
     }
+}
 
-    pub fn maze_fill(&mut self, grid: &mut Grid) {
+impl MazeGenerator for DFSMaze {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng) {
         self.visited.clear();
 
         // we need to make a DFS forest since child tiles may have multiple
         // disjoint sections. 
-        for x in 0..GRID_SIZE {
-            for y in 0..GRID_SIZE {
+        for x in 0..grid.get_size() {
+            for y in 0..grid.get_size() {
                 let coords = GridCoords {x, y};
                 if !self.visited.contains(&coords) {
-                    self.make_maze(grid, coords);
+                    self.make_maze(grid, coords, rng);
                 }
             }
         }
     }
 }
 
+impl Default for DFSMaze {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 type ExitCoords = (GridCoords, Direction);
 
 pub struct DFSSolutionFinder {
@@ -104,7 +116,7 @@ impl DFSSolutionFinder {
         let (start_cell, _) = first_exit;
         self.stack.push(start_cell);
 
-        while self.stack.len() > 0 {
+        while !self.stack.is_empty() {
             let current = self.stack[self.stack.len() - 1];
             self.visited_cells.insert(current);
 
@@ -122,7 +134,7 @@ impl DFSSolutionFinder {
             }
 
             // Look for unvisited, connected neighbors
-            let unvisited_neighbors: Vec<GridCoords> = current.get_neighbors()
+            let unvisited_neighbors: Vec<GridCoords> = current.get_neighbors(grid.get_size())
                 .into_iter()
                 .filter(|x| {
                     !self.visited_cells.contains(x) && 
@@ -130,7 +142,7 @@ impl DFSSolutionFinder {
                 })
                 .collect();
 
-            if unvisited_neighbors.len() == 0 {
+            if unvisited_neighbors.is_empty() {
                 self.stack.pop();
                 self.path.pop();
                 continue;
@@ -154,4 +166,10 @@ impl DFSSolutionFinder {
             current_cell = neighbor;
         }
     }
-}
\ No newline at end of file
+}
+
+impl Default for DFSSolutionFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
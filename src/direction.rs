@@ -1,3 +1,5 @@
+use crate::grid::BIT_ORDER;
+
 #[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
 pub enum Direction {
     Right,
@@ -7,8 +9,20 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub const fn to_index(&self) -> usize {
-        *self as usize
+    pub const fn to_index(self) -> usize {
+        self as usize
+    }
+
+    /// The bit for this direction in a cell's connection and solution bits,
+    /// which is its position in `BIT_ORDER`
+    pub const fn bit(self) -> u8 {
+        // PartialEq isn't const, so compare the discriminants
+        let mut position = 0;
+        while BIT_ORDER[position] as usize != self as usize {
+            position += 1;
+        }
+
+        1 << position
     }
 
     pub fn get_opposite(&self) -> Self {
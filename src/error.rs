@@ -0,0 +1,71 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum MazeError {
+    /// A file operation failed. The message describes what we were trying
+    /// to do at the time
+    Io {
+        message: String,
+        source: io::Error
+    },
+    Image(image::ImageError),
+    Json(serde_json::Error),
+    /// The user asked for something that can't be generated
+    InvalidInput(String),
+    /// A file we read back in doesn't look like something we wrote
+    InvalidData(String),
+}
+
+pub type Result<T> = std::result::Result<T, MazeError>;
+
+impl MazeError {
+    /// Make a closure for use with map_err that wraps an I/O error with
+    /// a description of what went wrong and where.
+    pub fn io<'a>(
+        message: &'a str,
+        path: &'a Path
+    ) -> impl FnOnce(io::Error) -> Self + 'a {
+        move |source| Self::Io {
+            message: format!("{} ({})", message, path.display()),
+            source
+        }
+    }
+}
+
+impl Display for MazeError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::Io { message, source } => write!(f, "{}: {}", message, source),
+            Self::Image(error) => write!(f, "image error: {}", error),
+            Self::Json(error) => write!(f, "JSON error: {}", error),
+            Self::InvalidInput(message) => write!(f, "{}", message),
+            Self::InvalidData(message) => write!(f, "invalid data: {}", message),
+        }
+    }
+}
+
+impl Error for MazeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Image(error) => Some(error),
+            Self::Json(error) => Some(error),
+            Self::InvalidInput(_) | Self::InvalidData(_) => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for MazeError {
+    fn from(error: image::ImageError) -> Self {
+        Self::Image(error)
+    }
+}
+
+impl From<serde_json::Error> for MazeError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
@@ -0,0 +1,173 @@
+//! Flatten a whole level of the nested maze into a single image, for
+//! printing or for use in other tools without a 3D Tiles viewer.
+
+use std::ops::Range;
+
+use image::{Rgb, RgbImage};
+
+use crate::config::MazeConfig;
+use crate::dfs::DFSSolutionFinder;
+use crate::direction::Direction;
+use crate::error::{MazeError, Result};
+use crate::tile::Tile;
+
+/// Exports are held in memory, so limit them to 8192x8192 cells. The wall
+/// image for a maze that size is already about 800 MB.
+pub const MAX_EXPORT_SIZE: usize = 8192;
+
+const FLOOR_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+const WALL_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
+const SOLUTION_COLOR: Rgb<u8> = Rgb([220, 40, 40]);
+
+/// Connection bit for each direction as (bit, dx, dy) where dy is
+/// measured in image rows, i.e. pointing south
+const NEIGHBOR_BITS: [(u8, i64, i64); 4] = [
+    (Direction::Up.bit(), 0, -1),
+    (Direction::Down.bit(), 0, 1),
+    (Direction::Left.bit(), -1, 0),
+    (Direction::Right.bit(), 1, 0),
+];
+
+/// Stitch every tile at the given level into one image with a pixel per
+/// cell, so it is (grid size * 2^level) pixels on a side. This uses the same
+/// encoding as the tiles' feature ID textures: red is the connection bits
+/// and green is the solution bits. Also like the tiles, row 0 of the image
+/// is the southern edge of the maze.
+pub fn export_level(config: &MazeConfig, level: usize) -> Result<RgbImage> {
+    let tiles_per_side = 1usize
+        .checked_shl(level as u32)
+        .filter(|tiles_per_side| {
+            tiles_per_side * config.grid_size <= MAX_EXPORT_SIZE
+        })
+        .ok_or_else(|| MazeError::InvalidInput(format!(
+            "level {} is too big to export, it must be at most {} cells wide",
+            level,
+            MAX_EXPORT_SIZE
+        )))?;
+
+    export_region(config, level, 0..tiles_per_side, 0..tiles_per_side)
+}
+
+/// Like `export_level`, but only for a rectangular range of tiles at the
+/// given level. Only the tiles in the range and their ancestors are
+/// generated, so this works at any depth as long as the range is small.
+pub fn export_region(
+    config: &MazeConfig,
+    level: usize,
+    x_tiles: Range<usize>,
+    y_tiles: Range<usize>
+) -> Result<RgbImage> {
+    if level >= config.max_levels() {
+        return Err(MazeError::InvalidInput(format!(
+            "level {} is out of range, the maze has at most {} levels",
+            level,
+            config.max_levels()
+        )));
+    }
+
+    let tiles_per_side = 1 << level;
+    let in_range = !x_tiles.is_empty() &&
+        !y_tiles.is_empty() &&
+        x_tiles.end <= tiles_per_side &&
+        y_tiles.end <= tiles_per_side;
+    if !in_range {
+        return Err(MazeError::InvalidInput(format!(
+            "tiles {:?} x {:?} are out of range for level {}",
+            x_tiles,
+            y_tiles,
+            level
+        )));
+    }
+
+    let grid_size = config.grid_size;
+    let too_big = |tiles: &Range<usize>| {
+        tiles.len()
+            .checked_mul(grid_size)
+            .is_none_or(|size| size > MAX_EXPORT_SIZE)
+    };
+    if too_big(&x_tiles) || too_big(&y_tiles) {
+        return Err(MazeError::InvalidInput(format!(
+            "{} x {} tiles is too big to export, it must be at most {} cells wide",
+            x_tiles.len(),
+            y_tiles.len(),
+            MAX_EXPORT_SIZE
+        )));
+    }
+
+    let width = x_tiles.len() * grid_size;
+    let height = y_tiles.len() * grid_size;
+    let mut pixels = vec![0; 3 * width * height];
+    let mut generators = config.algorithms.make_generators();
+    let mut solver = DFSSolutionFinder::new();
+
+    // Depth-first so only a few tiles per level are in memory at once
+    let mut stack = vec![Tile::make_root(config, &mut generators, &mut solver)];
+    while let Some(tile) = stack.pop() {
+        // Skip any part of the quadtree that doesn't overlap the range
+        let shift = level - tile.level;
+        let overlaps = (tile.x << shift) < x_tiles.end &&
+            x_tiles.start < ((tile.x + 1) << shift) &&
+            (tile.y << shift) < y_tiles.end &&
+            y_tiles.start < ((tile.y + 1) << shift);
+        if !overlaps {
+            continue;
+        }
+
+        if tile.level < level {
+            stack.extend(tile.subdivide(config, &mut generators, &mut solver));
+            continue;
+        }
+
+        // Copy the tile's pixels one row at a time
+        let row_length = 3 * grid_size;
+        let tile_bytes = tile.get_grid().to_image_bytes();
+        let image_x = (tile.x - x_tiles.start) * grid_size;
+        for (y, row) in tile_bytes.chunks(row_length).enumerate() {
+            let image_y = (tile.y - y_tiles.start) * grid_size + y;
+            let start = 3 * (image_y * width + image_x);
+            pixels[start..start + row_length].copy_from_slice(row);
+        }
+    }
+
+    let image = RgbImage::from_raw(width as u32, height as u32, pixels)
+        .expect("pixel buffer should match the image size");
+    Ok(image)
+}
+
+/// Draw the walls of an exported level or region. Cells and the
+/// connections between them are 1 pixel each, with walls in between, so a
+/// level N cells wide becomes a (2N + 1) pixel wide image. Unlike the
+/// exported level, north is at the top of the image. Optionally, the
+/// solution is drawn in red.
+pub fn render_walls(level_image: &RgbImage, show_solution: bool) -> RgbImage {
+    let (width, height) = level_image.dimensions();
+    let mut walls = RgbImage::from_pixel(2 * width + 1, 2 * height + 1, WALL_COLOR);
+
+    for (x, y, pixel) in level_image.enumerate_pixels() {
+        let [connections, solution, _] = pixel.0;
+        let color = |on_solution: bool| {
+            if show_solution && on_solution {
+                SOLUTION_COLOR
+            } else {
+                FLOOR_COLOR
+            }
+        };
+
+        // Flip vertically so north is up
+        let cell_x = 2 * x as i64 + 1;
+        let cell_y = 2 * (height - 1 - y) as i64 + 1;
+        walls.put_pixel(cell_x as u32, cell_y as u32, color(solution != 0));
+
+        for (bit, dx, dy) in NEIGHBOR_BITS {
+            if connections & bit != 0 {
+                walls.put_pixel(
+                    (cell_x + dx) as u32,
+                    (cell_y + dy) as u32,
+                    color(solution & bit != 0)
+                );
+            }
+        }
+    }
+
+    walls
+}
@@ -0,0 +1,231 @@
+//! Feature IDs and metadata for the tiles' GLBs. Each cell's connection
+//! and solution bits are feature IDs (`EXT_mesh_features`) in separate
+//! channels of the same texture. Both feature IDs index into one property
+//! table (`EXT_structural_metadata`) that describes what each of the 16
+//! combinations of bits means, so generic glTF tools can make sense of
+//! the data without knowing the bit layout.
+//!
+//! When both sets of bits are packed into a single channel, there is just
+//! one feature ID with 256 features, and a second property table splits
+//! each of them back into connection and solution bits.
+
+use serde_json::{json, Value};
+
+use crate::direction::Direction;
+use crate::grid::BIT_ORDER;
+use crate::tile_format::{ChannelLayout, TileFormat};
+use crate::tileset::get_direction_name;
+
+/// One feature (row of the property table) for every combination of the
+/// connection bits
+pub const FEATURE_COUNT: usize = 1 << BIT_ORDER.len();
+
+/// Features when the connection and solution bits share a byte
+const PACKED_FEATURE_COUNT: usize = FEATURE_COUNT * FEATURE_COUNT;
+
+/// What a cell looks like, based on how many sides are open and whether
+/// two open sides are opposite each other
+const SHAPES: [&str; 6] = [
+    "CLOSED",
+    "DEAD_END",
+    "STRAIGHT",
+    "TURN",
+    "T_JUNCTION",
+    "CROSSROADS",
+];
+
+/// Buffer views must start on a multiple of 8 bytes for
+/// EXT_structural_metadata
+const ALIGNMENT: usize = 8;
+
+/// The featureIds of the `EXT_mesh_features` extension for a primitive
+/// textured with the given feature ID texture
+pub fn make_feature_ids(texture: usize, format: &TileFormat) -> Value {
+    let feature_id = |label: &str, channel: usize, count: usize, table: usize| json!({
+        "featureCount": count,
+        "label": label,
+        "propertyTable": table,
+        "texture": {
+            "index": texture,
+            "texCoord": 0,
+            "channels": [channel]
+        }
+    });
+
+    let (connections_channel, solution_channel) = format.get_channels();
+    match format.layout {
+        ChannelLayout::R8Packed => json!([
+            feature_id("cells", connections_channel, PACKED_FEATURE_COUNT, 1)
+        ]),
+        _ => json!([
+            feature_id("connections", connections_channel, FEATURE_COUNT, 0),
+            feature_id("solutions", solution_channel, FEATURE_COUNT, 0)
+        ])
+    }
+}
+
+/// Append the property table's values to buffer (the GLB's binary chunk)
+/// and return the `EXT_structural_metadata` extension along with the buffer
+/// views it needs. first_view is the index the first of those buffer views
+/// will have.
+pub fn append_property_table(
+    buffer: &mut Vec<u8>,
+    first_view: usize,
+    format: &TileFormat
+) -> (Value, Vec<Value>) {
+    let mut buffer_views = Vec::new();
+    let mut add_view = |name: &str, data: &[u8]| {
+        buffer.resize(buffer.len().next_multiple_of(ALIGNMENT), 0);
+        buffer_views.push(json!({
+            "name": name,
+            "buffer": 0,
+            "byteOffset": buffer.len(),
+            "byteLength": data.len()
+        }));
+        buffer.extend_from_slice(data);
+        first_view + buffer_views.len() - 1
+    };
+
+    let bits: Vec<u8> = (0..FEATURE_COUNT as u8).collect();
+    let mut properties = json!({
+        "bits": { "values": add_view("Connection bits", &bits) },
+        "opening_count": {
+            "values": add_view(
+                "Opening counts",
+                &bits.iter().map(|bits| bits.count_ones() as u8).collect::<Vec<_>>()
+            )
+        },
+        "shape": {
+            "values": add_view(
+                "Shapes",
+                &bits.iter().map(|&bits| get_shape(bits)).collect::<Vec<_>>()
+            )
+        }
+    });
+
+    for (bit, direction) in BIT_ORDER.iter().enumerate() {
+        // Booleans are a bitstream, lowest bit first
+        let mut values = [0u8; FEATURE_COUNT / 8];
+        for feature in bits.iter().filter(|&&bits| bits & (1 << bit) != 0) {
+            values[*feature as usize / 8] |= 1 << (feature % 8);
+        }
+
+        let name = get_direction_name(*direction).to_lowercase();
+        properties[&name] = json!({ "values": add_view(&name, &values) });
+    }
+
+    let mut property_tables = vec![json!({
+        "name": "Connections",
+        "class": "cell",
+        "count": FEATURE_COUNT,
+        "properties": properties
+    })];
+
+    if format.layout == ChannelLayout::R8Packed {
+        let shift = format.get_solution_shift();
+        let packed: Vec<u8> = (0..PACKED_FEATURE_COUNT).map(|value| value as u8).collect();
+        let connection_bits: Vec<u8> = packed.iter().map(|value| value & 0x0f).collect();
+        let solution_bits: Vec<u8> = packed.iter().map(|value| value >> shift).collect();
+        property_tables.push(json!({
+            "name": "Packed cells",
+            "class": "packed_cell",
+            "count": PACKED_FEATURE_COUNT,
+            "properties": {
+                "connection_bits": {
+                    "values": add_view("Packed connection bits", &connection_bits)
+                },
+                "solution_bits": {
+                    "values": add_view("Packed solution bits", &solution_bits)
+                }
+            }
+        }));
+    }
+
+    let extension = json!({
+        "schema": make_schema(),
+        "propertyTables": property_tables
+    });
+
+    (extension, buffer_views)
+}
+
+fn make_schema() -> Value {
+    let mut properties = json!({
+        "bits": {
+            "type": "SCALAR",
+            "componentType": "UINT8",
+            "description": "The connection bits themselves, i.e. the feature ID"
+        },
+        "opening_count": {
+            "type": "SCALAR",
+            "componentType": "UINT8",
+            "description": "Number of open sides"
+        },
+        "shape": {
+            "type": "ENUM",
+            "enumType": "shape",
+            "description": "What the open sides look like together"
+        }
+    });
+    for direction in BIT_ORDER {
+        let name = get_direction_name(direction).to_lowercase();
+        properties[&name] = json!({
+            "type": "BOOLEAN",
+            "description": format!("Whether the {} side of the cell is open", name)
+        });
+    }
+
+    let shape_values: Vec<Value> = SHAPES.iter()
+        .enumerate()
+        .map(|(value, name)| json!({ "name": name, "value": value }))
+        .collect();
+
+    json!({
+        "id": "nested_mazes_cells",
+        "enums": {
+            "shape": {
+                "valueType": "UINT8",
+                "values": shape_values
+            }
+        },
+        "classes": {
+            "cell": {
+                "name": "Maze cell",
+                "description": "Which sides of a cell are open. For the connections feature ID these are the hallways of the maze, for the solutions feature ID they are the directions the solution takes.",
+                "properties": properties
+            },
+            "packed_cell": {
+                "name": "Packed maze cell",
+                "description": "Connection and solution bits packed into one byte, each is a row of the Connections table",
+                "properties": {
+                    "connection_bits": {
+                        "type": "SCALAR",
+                        "componentType": "UINT8",
+                        "description": "Which hallways leave the cell"
+                    },
+                    "solution_bits": {
+                        "type": "SCALAR",
+                        "componentType": "UINT8",
+                        "description": "Which directions the solution takes"
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// The value of the shape enum for some connection bits
+fn get_shape(bits: u8) -> u8 {
+    let is_open = |direction: Direction| bits & direction.bit() != 0;
+    let straight = BIT_ORDER.iter()
+        .any(|direction| is_open(*direction) && is_open(direction.get_opposite()));
+
+    match bits.count_ones() {
+        0 => 0,
+        1 => 1,
+        2 if straight => 2,
+        2 => 3,
+        3 => 4,
+        _ => 5,
+    }
+}
@@ -74,4 +74,8 @@ pub fn make_buffer() -> Vec<u8> {
 
 pub fn get_buffer_size() -> usize {
     POSITIONS_BYTE_SIZE + UVS_BYTE_SIZE + NORMALS_BYTE_SIZE + INDICES_BYTE_SIZE
-}
\ No newline at end of file
+}
+
+pub fn get_triangle_count() -> usize {
+    INDICES_SIZE / 3
+}
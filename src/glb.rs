@@ -0,0 +1,126 @@
+use std::fs::read;
+use std::path::Path;
+
+use serde_json::{from_slice, Value};
+
+use crate::error::{MazeError, Result};
+
+const HEADER_LENGTH: usize = 12;
+const CHUNK_HEADER_LENGTH: usize = 8;
+
+/// The two chunks of a GLB file. This is only as much of glTF as is needed
+/// to read back the tiles this crate writes, not a general purpose loader.
+pub struct Glb {
+    pub json: Value,
+    /// Contents of the BIN chunk, i.e. buffer 0. Empty if there is no BIN
+    /// chunk
+    pub binary: Vec<u8>,
+}
+
+impl Glb {
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = read(path).map_err(MazeError::io("could not read GLB", path))?;
+        Self::parse(&bytes).map_err(|error| match error {
+            MazeError::InvalidData(message) => MazeError::InvalidData(
+                format!("{}: {}", path.display(), message)
+            ),
+            error => error
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LENGTH || &bytes[0..4] != b"glTF" {
+            return Err(invalid("not a GLB file"));
+        }
+
+        let version = read_u32(bytes, 4)?;
+        if version != 2 {
+            return Err(invalid(&format!("unsupported glTF version {}", version)));
+        }
+
+        let total_length = read_u32(bytes, 8)? as usize;
+        if total_length > bytes.len() {
+            return Err(invalid("file is shorter than its header says"));
+        }
+
+        let mut json = None;
+        let mut binary = Vec::new();
+        let mut offset = HEADER_LENGTH;
+        while offset < total_length {
+            let chunk_length = read_u32(bytes, offset)? as usize;
+            let chunk_type = bytes.get(offset + 4..offset + 8)
+                .ok_or_else(|| invalid("truncated chunk header"))?;
+            let start = offset + CHUNK_HEADER_LENGTH;
+            let chunk = bytes.get(start..start + chunk_length)
+                .ok_or_else(|| invalid("truncated chunk"))?;
+
+            match chunk_type {
+                b"JSON" => json = Some(from_slice(chunk)?),
+                b"BIN\0" => binary = chunk.to_vec(),
+                // Unknown chunks must be ignored according to the spec
+                _ => {}
+            }
+            offset = start + chunk_length;
+        }
+
+        let json = json.ok_or_else(|| invalid("missing JSON chunk"))?;
+        Ok(Self { json, binary })
+    }
+
+    /// The bytes of a buffer view. Only views of the BIN chunk are
+    /// supported since that's where this crate puts images.
+    pub fn get_buffer_view(&self, index: usize) -> Result<&[u8]> {
+        let view = &self.json["bufferViews"][index];
+        let buffer = view["buffer"].as_u64()
+            .ok_or_else(|| invalid(&format!("bufferView {} is missing", index)))?;
+        if buffer != 0 {
+            return Err(invalid(&format!(
+                "bufferView {} is not in the BIN chunk",
+                index
+            )));
+        }
+
+        let offset = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let length = view["byteLength"].as_u64()
+            .ok_or_else(|| invalid(&format!("bufferView {} has no byteLength", index)))?
+            as usize;
+        self.binary.get(offset..offset + length).ok_or_else(|| {
+            invalid(&format!("bufferView {} is out of bounds", index))
+        })
+    }
+
+    /// The encoded bytes and MIME type of the image used by a texture.
+    /// Textures that use an extension like `EXT_texture_webp` name their
+    /// image in the extension instead.
+    pub fn get_texture_image(&self, texture: usize) -> Result<(&[u8], &str)> {
+        let image = get_texture_source(&self.json, texture)?;
+        let image_json = &self.json["images"][image];
+        let view = image_json["bufferView"].as_u64()
+            .ok_or_else(|| invalid(&format!("image {} is not in a bufferView", image)))?;
+        let mime_type = image_json["mimeType"].as_str()
+            .ok_or_else(|| invalid(&format!("image {} has no mimeType", image)))?;
+        Ok((self.get_buffer_view(view as usize)?, mime_type))
+    }
+}
+
+/// Which image a texture uses. This works for `.gltf` files too.
+pub fn get_texture_source(json: &Value, texture: usize) -> Result<usize> {
+    let texture_json = &json["textures"][texture];
+    texture_json["source"].as_u64()
+        .or_else(|| texture_json["extensions"]
+            .as_object()?
+            .values()
+            .find_map(|extension| extension["source"].as_u64()))
+        .map(|image| image as usize)
+        .ok_or_else(|| invalid(&format!("texture {} has no source", texture)))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes.get(offset..offset + 4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .ok_or_else(|| invalid("unexpected end of file"))
+}
+
+fn invalid(message: &str) -> MazeError {
+    MazeError::InvalidData(message.to_string())
+}
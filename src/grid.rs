@@ -1,17 +1,22 @@
 use std::ops::Range;
 use std::fmt::{Debug, Formatter, Result};
 
-use rand::Rng;
-use rand::rngs::ThreadRng;
-
-use crate::grid_coords::{GridCoords, GRID_SIZE};
+use crate::grid_coords::GridCoords;
 use crate::direction::Direction;
 
-const CELL_COUNT: usize = GRID_SIZE * GRID_SIZE;
-const HALF_GRID_SIZE: usize = GRID_SIZE / 2;
+/// Which direction each bit of `Cell::get_connection_bits` and
+/// `Cell::get_solution_bits` means, starting from the lowest bit
+pub const BIT_ORDER: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
 
-// RGB image
-const IMAGE_SIZE: usize = CELL_COUNT * 3;
+/// Channels of the feature ID texture (see `Grid::to_image_bytes`) that
+/// hold the connection and solution bits
+pub const CONNECTIONS_CHANNEL: usize = 0;
+pub const SOLUTION_CHANNEL: usize = 1;
 
 #[derive(Copy, Clone)]
 pub struct Connection {
@@ -19,7 +24,6 @@ pub struct Connection {
     blocked: bool,
     is_solution_connection: bool,
     is_maze_exit: bool,
-    split_bits: u16
 }
 
 impl Connection {
@@ -29,7 +33,6 @@ impl Connection {
             blocked: false,
             is_solution_connection: false,
             is_maze_exit: false,
-            split_bits: 0
         }
     }
 
@@ -38,6 +41,12 @@ impl Connection {
     }
 }
 
+impl Default for Connection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Cell {
     /// 4 connections to neighboring cells. This connection struct will have
@@ -54,57 +63,68 @@ impl Cell {
         }
     }
 
+    /// The directions this cell is connected in, as bits in `BIT_ORDER`
     pub fn get_connection_bits(&self) -> u8 {
-        let up = self.connections[Direction::Up.to_index()]
-            .connected as u8;
-        let down = self.connections[Direction::Down.to_index()]
-            .connected as u8;
-        let left = self.connections[Direction::Left.to_index()]
-            .connected as u8;
-        let right = self.connections[Direction::Right.to_index()]
-            .connected as u8;
-        
-        up | (down << 1) | (left << 2) | (right << 3)
+        self.get_bits(|connection| connection.connected)
     }
 
+    /// The directions the solution leaves this cell in, as bits in
+    /// `BIT_ORDER`
     pub fn get_solution_bits(&self) -> u8 {
-        let up = self.connections[Direction::Up.to_index()]
-            .is_solution_connection as u8;
-        let down = self.connections[Direction::Down.to_index()]
-            .is_solution_connection as u8;
-        let left = self.connections[Direction::Left.to_index()]
-            .is_solution_connection as u8;
-        let right = self.connections[Direction::Right.to_index()]
-            .is_solution_connection as u8;
-        
-        up | (down << 1) | (left << 2) | (right << 3)
+        self.get_bits(|connection| connection.is_solution_connection)
+    }
+
+    fn get_bits(&self, is_set: impl Fn(&Connection) -> bool) -> u8 {
+        BIT_ORDER.iter()
+            .filter(|direction| is_set(&self.connections[direction.to_index()]))
+            .map(|direction| direction.bit())
+            .sum()
+    }
+
+    pub fn is_connected(&self, direction: Direction) -> bool {
+        self.connections[direction.to_index()].connected
+    }
+
+    pub fn is_solution_connection(&self, direction: Direction) -> bool {
+        self.connections[direction.to_index()].is_solution_connection
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 
 pub struct Grid {
+    /// Width and height of the grid in cells. This is always a power of 2
+    size: usize,
     /// Cells, stored in row-major fashion, but the rows are y-up
-    cells: [Cell; CELL_COUNT],
-    rng: ThreadRng
+    cells: Vec<Cell>,
 }
 
 impl Grid {
-    pub fn new() -> Self {
+    pub fn new(size: usize) -> Self {
         Self {
-            cells: [Cell::new(); CELL_COUNT],
-            rng: rand::thread_rng()
+            size,
+            cells: vec![Cell::new(); size * size],
         }
     }
 
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
     pub fn get_cell(&self, coords: GridCoords) -> &Cell {
-        &self.cells[coords.to_index()]
+        &self.cells[coords.to_index(self.size)]
     }
 
     pub fn get_cell_mut(&mut self, coords: GridCoords) -> &mut Cell {
-        &mut self.cells[coords.to_index()]
+        &mut self.cells[coords.to_index(self.size)]
     }
 
-    pub fn can_connect(&mut self, a: GridCoords, b:GridCoords) -> bool {
+    pub fn can_connect(&self, a: GridCoords, b:GridCoords) -> bool {
         let direction = match GridCoords::get_direction(a, b) {
             Some(dir) => dir,
             None => panic!("can_connect can only be called on adjacent coordinates")
@@ -114,7 +134,7 @@ impl Grid {
         !connection.blocked
     }
 
-    pub fn is_connected(&mut self, a: GridCoords, b:GridCoords) -> bool {
+    pub fn is_connected(&self, a: GridCoords, b:GridCoords) -> bool {
         let direction = match GridCoords::get_direction(a, b) {
             Some(dir) => dir,
             None => panic!("is_connected can only be called on adjacent coordinates")
@@ -168,7 +188,7 @@ impl Grid {
     pub fn get_all_exits(&self) -> Vec<(GridCoords, Direction)> {
         let mut result = Vec::new();
 
-        for i in 0..GRID_SIZE {
+        for i in 0..self.size {
             // bottom boundary
             let mut connection = &self.cells[i]
                 .connections[Direction::Down.to_index()];
@@ -178,17 +198,17 @@ impl Grid {
             }
 
             // top boundary
-            connection = &self.cells[(GRID_SIZE - 1) * GRID_SIZE + i]
+            connection = &self.cells[(self.size - 1) * self.size + i]
                 .connections[Direction::Up.to_index()];
 
             if connection.is_boundary_exit() {
                 result.push(
-                    (GridCoords {x: i, y: GRID_SIZE - 1}, Direction::Up)
+                    (GridCoords {x: i, y: self.size - 1}, Direction::Up)
                 );
             }
 
             // left boundary
-            connection = &self.cells[i * GRID_SIZE]
+            connection = &self.cells[i * self.size]
                 .connections[Direction::Left.to_index()];
 
             if connection.is_boundary_exit() {
@@ -196,12 +216,12 @@ impl Grid {
             }
 
             // right boundary
-            connection = &self.cells[i * GRID_SIZE + (GRID_SIZE - 1)]
+            connection = &self.cells[i * self.size + (self.size - 1)]
                 .connections[Direction::Right.to_index()];
             
             if connection.is_boundary_exit() {
                 result.push(
-                    (GridCoords {x: GRID_SIZE - 1, y: i}, Direction::Right)
+                    (GridCoords {x: self.size - 1, y: i}, Direction::Right)
                 );
             }
         }
@@ -209,64 +229,82 @@ impl Grid {
         result
     }
 
-    pub fn to_image_bytes(&self) -> [u8; IMAGE_SIZE] {
-        let mut result = [0; IMAGE_SIZE];
-        for row in 0..GRID_SIZE {
-            let y = (GRID_SIZE - 1) - row;
-            for x in 0..GRID_SIZE {
-                let index = y * GRID_SIZE + x;
+    /// RGB8 image of the connection and solution bits, one pixel per cell
+    pub fn to_image_bytes(&self) -> Vec<u8> {
+        let mut result = vec![0; self.cells.len() * 3];
+        for row in 0..self.size {
+            let y = (self.size - 1) - row;
+            for x in 0..self.size {
+                let index = y * self.size + x;
                 let cell = &self.cells[index];
                 // Red channel is the connection bits
-                result[3 * index] = cell.get_connection_bits();
-                // Blue channel is the solution bits
-                result[3 * index + 1] = cell.get_solution_bits();
-                // Green channel was already initialized to 0
+                result[3 * index + CONNECTIONS_CHANNEL] = cell.get_connection_bits();
+                // Green channel is the solution bits
+                result[3 * index + SOLUTION_CHANNEL] = cell.get_solution_bits();
+                // Blue channel was already initialized to 0
             }
         }
         
         result
     }
 
-    pub fn to_debug_image_bytes(&self) -> [u8; IMAGE_SIZE] {
+    /// Rebuild a grid from the output of `to_image_bytes`. Only the
+    /// connection and solution bits are stored in the image, so which
+    /// connections were blocked while generating is lost.
+    pub fn from_image_bytes(size: usize, image_bytes: &[u8]) -> Self {
+        let mut grid = Self::new(size);
+        for (cell, pixel) in grid.cells.iter_mut().zip(image_bytes.chunks(3)) {
+            for direction in BIT_ORDER {
+                let bit = direction.bit();
+                let connection = &mut cell.connections[direction.to_index()];
+                connection.connected = pixel[CONNECTIONS_CHANNEL] & bit != 0;
+                connection.is_solution_connection = pixel[SOLUTION_CHANNEL] & bit != 0;
+            }
+        }
+
+        grid
+    }
+
+    pub fn to_debug_image_bytes(&self) -> Vec<u8> {
         let mut image_bytes = self.to_image_bytes();
-        for i in 0..image_bytes.len() {
-            // Increase the contrast by shifting the 4 connection bits
-            // into the 4 high bits of each byte.
-            // For the green channel, 0 << 4 == 0 so this is a safe operation.
-            image_bytes[i] <<= 4;
+        for byte in image_bytes.iter_mut() {
+            // Increase the contrast by shifting the 4 connection bits (and
+            // the 4 solution bits in green) into the high 4 bits of each
+            // byte. Blue is always 0, so it stays that way.
+            *byte <<= 4;
         }
         image_bytes
     }
 
     pub fn mark_boundaries(&mut self) {
-        for i in 0..GRID_SIZE {
+        for i in 0..self.size {
             // bottom boundary
             self.cells[i]
                 .connections[Direction::Down.to_index()].blocked = true;
 
             // top boundary
-            self.cells[(GRID_SIZE - 1) * GRID_SIZE + i]
+            self.cells[(self.size - 1) * self.size + i]
                 .connections[Direction::Up.to_index()].blocked = true;
 
             // left boundary
-            self.cells[i * GRID_SIZE]
+            self.cells[i * self.size]
                 .connections[Direction::Left.to_index()].blocked = true;
 
             // right boundary
-            self.cells[i * GRID_SIZE + (GRID_SIZE - 1)]
+            self.cells[i * self.size + (self.size - 1)]
                 .connections[Direction::Right.to_index()].blocked = true;
         }
     }
 
     pub fn mark_exit(&mut self, direction: Direction, index: usize) {
         let (x, y) = match direction {
-            Direction::Right => (GRID_SIZE - 1, index),
+            Direction::Right => (self.size - 1, index),
             Direction::Left => (0, index),
-            Direction::Up => (index, GRID_SIZE - 1),
+            Direction::Up => (index, self.size - 1),
             Direction::Down => (index, 0)
         };
 
-        let cell = &mut self.cells[y * GRID_SIZE + x];
+        let cell = &mut self.cells[y * self.size + x];
         let connection = &mut cell.connections[direction.to_index()];
 
         // Make a connection that leads "outside" the maze
@@ -274,19 +312,16 @@ impl Grid {
         connection.connected = true;
         connection.is_maze_exit = true;
         connection.is_solution_connection = true;
-        // Assign some random bits so when we subdivide we know where exactly
-        // to put the exit as we zoom in.
-        connection.split_bits = self.rng.gen::<u16>();
     }
 
     pub fn get_horizontal_seam(
         &self, 
         y: usize, 
         direction: Direction,
-    ) -> [Connection; GRID_SIZE] {
-        let mut result = [Connection::new(); GRID_SIZE];
-        for x in 0..GRID_SIZE {
-            result[x] = self.cells[y * GRID_SIZE + x]
+    ) -> Vec<Connection> {
+        let mut result = vec![Connection::new(); self.size];
+        for (x, connection) in result.iter_mut().enumerate() {
+            *connection = self.cells[y * self.size + x]
                 .connections[direction.to_index()];
         }
 
@@ -297,41 +332,45 @@ impl Grid {
         &self, 
         x: usize,
         direction: Direction,
-    ) -> [Connection; GRID_SIZE] {
-        let mut result = [Connection::new(); GRID_SIZE];
-        for y in 0..GRID_SIZE {
-            result[y] = self.cells[y * GRID_SIZE + x]
+    ) -> Vec<Connection> {
+        let mut result = vec![Connection::new(); self.size];
+        for (y, connection) in result.iter_mut().enumerate() {
+            *connection = self.cells[y * self.size + x]
                 .connections[direction.to_index()];
         }
 
         result
     }
 
-    pub fn set_boundary(
+    /// Set the boundary of a child tile from the parent's connections.
+    /// Each parent connection covers two child cells, and `split_bit(side,
+    /// index)` chooses which of the two the connection continues through
+    /// (0 or 1) for the index-th parent connection on that side.
+    pub fn set_boundary<F>(
         &mut self,
         right: &[Connection],
         top: &[Connection],
         left: &[Connection],
         bottom: &[Connection],
-    ) {
-        self.set_horizontal_boundary(0, bottom, Direction::Down);
-        self.set_horizontal_boundary(GRID_SIZE - 1, top, Direction::Up);
+        split_bit: F
+    ) where F: Fn(Direction, usize) -> usize {
+        self.set_horizontal_boundary(0, bottom, Direction::Down, &split_bit);
+        self.set_horizontal_boundary(self.size - 1, top, Direction::Up, &split_bit);
 
-        self.set_vertical_boundary(0, left, Direction::Left);
-        self.set_vertical_boundary(GRID_SIZE - 1, right, Direction::Right);
+        self.set_vertical_boundary(0, left, Direction::Left, &split_bit);
+        self.set_vertical_boundary(self.size - 1, right, Direction::Right, &split_bit);
     }
 
-    fn set_horizontal_boundary(
+    fn set_horizontal_boundary<F>(
         &mut self, 
         y: usize,
         boundary: &[Connection],
-        direction: Direction
-    ) {
-        let row_offset = y * GRID_SIZE;
+        direction: Direction,
+        split_bit: &F
+    ) where F: Fn(Direction, usize) -> usize {
+        let row_offset = y * self.size;
         let direction_index = direction.to_index();
-        for x in 0..HALF_GRID_SIZE {
-            let connection = boundary[x];
-
+        for (x, connection) in boundary.iter().enumerate() {
             let child_x = 2 * x;
 
             // Each connection subdivides into two connections in the child
@@ -347,13 +386,12 @@ impl Grid {
                 continue;
             }
 
-            // The split bits determine where exactly the connection is
-            let split_bit = connection.split_bits & 1;
-            let other_half = (!split_bit) & 1;
-            let remaining_bits = connection.split_bits >> 1;
+            // The split bit determines where exactly the connection is
+            let split_half = split_bit(direction, x);
+            let other_half = 1 - split_half;
 
-            let split_index = row_offset + (child_x + split_bit as usize);
-            let other_index = row_offset + (child_x + other_half as usize);
+            let split_index = row_offset + (child_x + split_half);
+            let other_index = row_offset + (child_x + other_half);
 
             {
                 // Propagate the connection wherever the split bit indicated
@@ -362,9 +400,6 @@ impl Grid {
                 split_connection.connected = connection.connected;
                 split_connection.is_solution_connection = connection.is_solution_connection;
                 split_connection.is_maze_exit = connection.is_maze_exit;
-
-                // for further subdivision
-                split_connection.split_bits = remaining_bits;
             }
 
             {
@@ -375,27 +410,25 @@ impl Grid {
                 other_connection.blocked = false;
                 other_connection.is_solution_connection = false;
                 other_connection.is_maze_exit = false;
-                other_connection.split_bits = 0;
             }
         }
     }
 
-    fn set_vertical_boundary(
+    fn set_vertical_boundary<F>(
         &mut self, 
         x: usize,
         boundary: &[Connection],
-        direction: Direction
-    ) {
+        direction: Direction,
+        split_bit: &F
+    ) where F: Fn(Direction, usize) -> usize {
         let direction_index = direction.to_index();
-        for y in 0..HALF_GRID_SIZE {
-            let connection = boundary[y];
-
+        for (y, connection) in boundary.iter().enumerate() {
             let child_y = 2 * y;
 
             // Each connection subdivides into two connections in the child
             // which has twice the resolution.
-            let a_index = child_y * GRID_SIZE + x;
-            let b_index = (child_y + 1) * GRID_SIZE + x;
+            let a_index = child_y * self.size + x;
+            let b_index = (child_y + 1) * self.size + x;
 
             // If it was a wall in the parent, then both halves are a wall
             // in the child
@@ -405,13 +438,12 @@ impl Grid {
                 continue;
             }
 
-            // The split bits determine where exactly the connection is
-            let split_bit = connection.split_bits & 1;
-            let other_half = (!split_bit) & 1;
-            let remaining_bits = connection.split_bits >> 1;
+            // The split bit determines where exactly the connection is
+            let split_half = split_bit(direction, y);
+            let other_half = 1 - split_half;
 
-            let split_index = (child_y + split_bit as usize) * GRID_SIZE + x;
-            let other_index = (child_y + other_half as usize) * GRID_SIZE + x;
+            let split_index = (child_y + split_half) * self.size + x;
+            let other_index = (child_y + other_half) * self.size + x;
             {
                 // Propagate the connection wherever the split bit indicated
                 let split_connection = &mut self.cells[split_index]
@@ -419,9 +451,6 @@ impl Grid {
                 split_connection.connected = connection.connected;
                 split_connection.is_solution_connection = connection.is_solution_connection;
                 split_connection.is_maze_exit = connection.is_maze_exit;
-
-                // for further subdivision
-                split_connection.split_bits = remaining_bits;
             }
 
             {
@@ -432,7 +461,6 @@ impl Grid {
                 other_connection.blocked = false;
                 other_connection.is_solution_connection = false;
                 other_connection.is_maze_exit = false;
-                other_connection.split_bits = 0;
             }
         }
     }
@@ -443,12 +471,14 @@ impl Grid {
         x_range: Range<usize>,
         y_range: Range<usize>
     ) {
+        let half_size = self.size / 2;
+
         // propagate vertical walls on the right edge of cells
         const RIGHT_INDEX: usize = Direction::Right.to_index();
         const LEFT_INDEX: usize = Direction::Left.to_index();
         for y in y_range.clone() {
             for x in x_range.start..(x_range.end - 1) {
-                let parent_cell = &self.cells[y * GRID_SIZE + x];
+                let parent_cell = &self.cells[y * self.size + x];
                 let parent_right = 
                     &parent_cell.connections[RIGHT_INDEX];
 
@@ -459,17 +489,17 @@ impl Grid {
 
                 // At the next level of detail, one wall becomes two adjacent
                 // walls. Both need to be marked as blocked
-                let child_x = 2 * (x % HALF_GRID_SIZE) + 1;
-                let child_y = 2 * (y % HALF_GRID_SIZE);
-                child.cells[child_y * GRID_SIZE + child_x]
+                let child_x = 2 * (x % half_size) + 1;
+                let child_y = 2 * (y % half_size);
+                child.cells[child_y * self.size + child_x]
                     .connections[RIGHT_INDEX].blocked = true;
-                child.cells[(child_y + 1) * GRID_SIZE + child_x]
+                child.cells[(child_y + 1) * self.size + child_x]
                     .connections[RIGHT_INDEX].blocked = true;
 
                 // Also mark the opposite side of the connection
-                child.cells[child_y * GRID_SIZE + (child_x + 1)]
+                child.cells[child_y * self.size + (child_x + 1)]
                     .connections[LEFT_INDEX].blocked = true;
-                child.cells[(child_y + 1) * GRID_SIZE + (child_x + 1)]
+                child.cells[(child_y + 1) * self.size + (child_x + 1)]
                     .connections[LEFT_INDEX].blocked = true;
             }
         }
@@ -479,7 +509,7 @@ impl Grid {
         const DOWN_INDEX: usize = Direction::Down.to_index();
         for y in y_range.start..(y_range.end - 1) {
             for x in x_range.clone() {
-                let parent_cell = &self.cells[y * GRID_SIZE + x];
+                let parent_cell = &self.cells[y * self.size + x];
                 let parent_up = 
                     &parent_cell.connections[UP_INDEX];
 
@@ -490,17 +520,17 @@ impl Grid {
 
                 // At the next level of detail, one wall becomes two adjacent
                 // walls. Both need to be marked as blocked
-                let child_x = 2 * (x % HALF_GRID_SIZE);
-                let child_y = 2 * (y % HALF_GRID_SIZE) + 1;
-                child.cells[child_y * GRID_SIZE + child_x]
+                let child_x = 2 * (x % half_size);
+                let child_y = 2 * (y % half_size) + 1;
+                child.cells[child_y * self.size + child_x]
                     .connections[UP_INDEX].blocked = true;
-                child.cells[child_y * GRID_SIZE + (child_x + 1)]
+                child.cells[child_y * self.size + (child_x + 1)]
                     .connections[UP_INDEX].blocked = true;
 
                 // Also mark the opposite side of the connection
-                child.cells[(child_y + 1) * GRID_SIZE + child_x]
+                child.cells[(child_y + 1) * self.size + child_x]
                     .connections[DOWN_INDEX].blocked = true;
-                child.cells[(child_y + 1) * GRID_SIZE + (child_x + 1)]
+                child.cells[(child_y + 1) * self.size + (child_x + 1)]
                     .connections[DOWN_INDEX].blocked = true;
             }
         }
@@ -531,16 +561,16 @@ const GRID_CHARACTERS: [char; 16] = [
 
 impl Debug for Grid {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        for i in 0..GRID_SIZE {
-            let row = (GRID_SIZE - 1) - i;
-            for j in 0..GRID_SIZE {
-                let index = row * GRID_SIZE + j;
+        for i in 0..self.size {
+            let row = (self.size - 1) - i;
+            for j in 0..self.size {
+                let index = row * self.size + j;
                 let connection_bits = 
                     self.cells[index].get_connection_bits();
                 let grid_char = GRID_CHARACTERS[connection_bits as usize];
-                write!(f, "{}", grid_char);
+                write!(f, "{}", grid_char)?;
             }
-            write!(f, "\n");
+            writeln!(f)?;
         }
 
         Ok(())
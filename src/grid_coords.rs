@@ -1,8 +1,5 @@
 use crate::direction::Direction;
 
-// Each tile is a fixed NxN grid
-pub const GRID_SIZE: usize = 16;
-
 #[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
 pub struct GridCoords {
     pub x: usize,
@@ -11,8 +8,15 @@ pub struct GridCoords {
 }
 
 impl GridCoords {
-    pub fn to_index(&self) -> usize {
-        self.y * GRID_SIZE + self.x
+    pub fn from_index(index: usize, grid_size: usize) -> Self {
+        Self {
+            x: index % grid_size,
+            y: index / grid_size
+        }
+    }
+
+    pub fn to_index(self, grid_size: usize) -> usize {
+        self.y * grid_size + self.x
     }
 
     pub fn get_adjacent(&self, direction: Direction) -> Self {
@@ -36,7 +40,7 @@ impl GridCoords {
         Self {x, y}
     }
 
-    pub fn get_neighbors(&self) -> Vec<Self> {
+    pub fn get_neighbors(&self, grid_size: usize) -> Vec<Self> {
         let mut result = Vec::new();
         if self.x > 0 {
             result.push(Self {
@@ -52,14 +56,14 @@ impl GridCoords {
             });
         }
 
-        if self.x < GRID_SIZE - 1 {
+        if self.x < grid_size - 1 {
             result.push(Self {
                 x: self.x + 1,
                 y: self.y
             });
         }
 
-        if self.y < GRID_SIZE - 1 {
+        if self.y < grid_size - 1 {
             result.push(Self {
                 x: self.x,
                 y: self.y + 1
@@ -0,0 +1,49 @@
+//! Generate mazes nested inside the hallways of their parent mazes, output
+//! as a 3D Tiles implicit tileset.
+//!
+//! Besides generating a whole tileset on disk with [`tileset::MazeTileset`],
+//! any single tile can be regenerated on demand with [`tile::Tile::generate`]:
+//!
+//! ```
+//! use nested_mazes::config::MazeConfig;
+//! use nested_mazes::maze_generator::{Algorithm, AlgorithmSchedule};
+//! use nested_mazes::tile::Tile;
+//! use nested_mazes::tile_format::TileFormat;
+//!
+//! let exits = MazeConfig::default_exits(16);
+//! let algorithms = AlgorithmSchedule::new(vec![
+//!     (0, Algorithm::Backtracker),
+//!     (3, Algorithm::Wilson),
+//! ]).unwrap();
+//! let config = MazeConfig::new(1234, 16, exits, algorithms).unwrap();
+//! let tile = Tile::generate(&config, 5, 10, 21).unwrap();
+//! let glb = tile.make_glb(&TileFormat::default()).unwrap();
+//! assert_eq!(&glb[0..4], b"glTF");
+//! ```
+
+pub mod algorithms;
+pub mod atlas;
+pub mod config;
+pub mod dfs;
+pub mod direction;
+pub mod error;
+pub mod export;
+pub mod features;
+pub mod geometry;
+pub mod glb;
+pub mod grid;
+pub mod grid_coords;
+pub mod maze_generator;
+pub mod render;
+pub mod seed;
+pub mod server;
+pub mod subtree;
+pub mod svg;
+pub mod theme;
+pub mod tile;
+pub mod tile_format;
+pub mod tileset;
+pub mod validate;
+pub mod verify;
+pub mod walls;
+pub mod webp;
@@ -1,14 +1,15 @@
-mod dfs;
-mod direction;
-mod geometry;
-mod grid;
-mod grid_coords;
-mod tile;
-mod tileset;
+mod cli;
 
-use crate::tileset::MazeTileset;
+use std::process::exit;
+
+use clap::Parser;
+
+use crate::cli::Cli;
 
 fn main() {
-    let tileset = MazeTileset::new("output/maze", 7);
-    tileset.generate();
+    let cli = Cli::parse();
+    if let Err(error) = cli.run() {
+        eprintln!("error: {}", error);
+        exit(1);
+    }
 }
@@ -0,0 +1,309 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::algorithms::{
+    AldousBroder,
+    BinaryTree,
+    Eller,
+    GrowingTree,
+    HuntAndKill,
+    Kruskal,
+    Prim,
+    Sidewinder,
+    Wilson
+};
+use crate::dfs::DFSMaze;
+use crate::error::{MazeError, Result};
+use crate::grid::Grid;
+use crate::seed::TileRng;
+
+/// An algorithm that carves a maze into a grid.
+///
+/// Child tiles inherit walls from their parent, marked as blocked
+/// connections in the grid. These split the grid into disjoint regions, so
+/// implementations must never connect cells where `Grid::can_connect` is
+/// false, and must produce a spanning tree of each region. In other words,
+/// every cell must be reachable from every other cell in its region, without
+/// any loops.
+pub trait MazeGenerator {
+    fn maze_fill(&mut self, grid: &mut Grid, rng: &mut TileRng);
+}
+
+/// The maze generation algorithms to choose from
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Algorithm {
+    /// Depth-first search with backtracking. Long, winding corridors.
+    Backtracker,
+    /// Randomized Prim's algorithm. Lots of short dead ends.
+    Prim,
+    /// Randomized Kruskal's algorithm
+    Kruskal,
+    /// Loop-erased random walks. Picks uniformly from all possible mazes.
+    Wilson,
+    /// Random walk. Also uniform, but slower than Wilson's
+    AldousBroder,
+    /// Eller's algorithm, which builds the maze one row at a time
+    Eller,
+    /// Each cell connects up or right. Strongly biased towards the
+    /// northeast.
+    BinaryTree,
+    /// Runs of horizontal corridors that connect upwards
+    Sidewinder,
+    /// Random walk, then hunt for a new starting point when stuck
+    HuntAndKill,
+    /// Growing tree, choosing the newest cell with the given probability
+    /// and a random cell otherwise. Written as growing-tree:0.75 on the
+    /// command line, the probability defaults to 0.5
+    GrowingTree { newest_probability: f64 },
+}
+
+impl Algorithm {
+    pub const ALL: [Self; 10] = [
+        Self::Backtracker,
+        Self::Prim,
+        Self::Kruskal,
+        Self::Wilson,
+        Self::AldousBroder,
+        Self::Eller,
+        Self::BinaryTree,
+        Self::Sidewinder,
+        Self::HuntAndKill,
+        Self::GrowingTree { newest_probability: 0.5 },
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Backtracker => "backtracker",
+            Self::Prim => "prim",
+            Self::Kruskal => "kruskal",
+            Self::Wilson => "wilson",
+            Self::AldousBroder => "aldous-broder",
+            Self::Eller => "eller",
+            Self::BinaryTree => "binary-tree",
+            Self::Sidewinder => "sidewinder",
+            Self::HuntAndKill => "hunt-and-kill",
+            Self::GrowingTree { .. } => "growing-tree",
+        }
+    }
+
+    pub fn make_generator(self) -> Box<dyn MazeGenerator + Send> {
+        match self {
+            Self::Backtracker => Box::new(DFSMaze::new()),
+            Self::Prim => Box::new(Prim::new()),
+            Self::Kruskal => Box::new(Kruskal::new()),
+            Self::Wilson => Box::new(Wilson::new()),
+            Self::AldousBroder => Box::new(AldousBroder::new()),
+            Self::Eller => Box::new(Eller::new()),
+            Self::BinaryTree => Box::new(BinaryTree::new()),
+            Self::Sidewinder => Box::new(Sidewinder::new()),
+            Self::HuntAndKill => Box::new(HuntAndKill::new()),
+            Self::GrowingTree { newest_probability } =>
+                Box::new(GrowingTree::new(newest_probability)),
+        }
+    }
+
+    fn validate(self) -> Result<()> {
+        if let Self::GrowingTree { newest_probability } = self {
+            if !(0.0..=1.0).contains(&newest_probability) {
+                return Err(MazeError::InvalidInput(format!(
+                    "growing tree probability must be from 0 to 1, got {}",
+                    newest_probability
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::GrowingTree { newest_probability }
+                if *newest_probability != 0.5 =>
+                write!(f, "{}:{}", self.name(), newest_probability),
+            _ => write!(f, "{}", self.name())
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
+        // Parameters come after a colon, e.g. growing-tree:0.75
+        let (name, parameter) = match text.split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (text, None)
+        };
+
+        let algorithm = Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL
+                    .iter()
+                    .map(|algorithm| algorithm.name())
+                    .collect();
+                format!(
+                    "unknown algorithm '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })?;
+
+        let algorithm = match (algorithm, parameter) {
+            (algorithm, None) => algorithm,
+            (Self::GrowingTree { .. }, Some(parameter)) => {
+                let newest_probability = parameter.parse().map_err(|_| {
+                    format!("invalid growing tree probability '{}'", parameter)
+                })?;
+                Self::GrowingTree { newest_probability }
+            },
+            (algorithm, Some(_)) => {
+                return Err(format!("{} does not take a parameter", algorithm))
+            }
+        };
+        algorithm.validate().map_err(|error| error.to_string())?;
+
+        Ok(algorithm)
+    }
+}
+
+/// Which algorithm to use at each level of the quadtree. Since every level
+/// is generated inside the hallways of the level above, mixing algorithms
+/// makes each level of detail look different, e.g. long winding corridors
+/// at the root and Wilson's algorithm further down.
+///
+/// This is a list of (first level, algorithm) pairs. Each algorithm is used
+/// from its first level until the next entry takes over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlgorithmSchedule {
+    stages: Vec<(usize, Algorithm)>,
+}
+
+impl AlgorithmSchedule {
+    pub fn new(stages: Vec<(usize, Algorithm)>) -> Result<Self> {
+        match stages.first() {
+            Some((0, _)) => {},
+            _ => return Err(MazeError::InvalidInput(
+                "the algorithm schedule must start at level 0".to_string()
+            ))
+        }
+
+        for pair in stages.windows(2) {
+            if pair[0].0 >= pair[1].0 {
+                return Err(MazeError::InvalidInput(format!(
+                    "algorithm schedule levels must be increasing, got {} then {}",
+                    pair[0].0,
+                    pair[1].0
+                )));
+            }
+        }
+
+        for (_, algorithm) in &stages {
+            algorithm.validate()?;
+        }
+
+        Ok(Self { stages })
+    }
+
+    /// Use the same algorithm at every level
+    pub fn uniform(algorithm: Algorithm) -> Self {
+        Self {
+            stages: vec![(0, algorithm)]
+        }
+    }
+
+    pub fn get_stages(&self) -> &[(usize, Algorithm)] {
+        &self.stages
+    }
+
+    /// The algorithm used for tiles at the given level
+    pub fn get_algorithm(&self, level: usize) -> Algorithm {
+        self.stages[self.find_stage(level)].1
+    }
+
+    /// Make one generator per stage of the schedule. Generators keep
+    /// scratch buffers around between tiles, so each worker thread should
+    /// make its own.
+    pub fn make_generators(&self) -> MazeGenerators {
+        MazeGenerators {
+            schedule: self.clone(),
+            generators: self.stages
+                .iter()
+                .map(|(_, algorithm)| algorithm.make_generator())
+                .collect()
+        }
+    }
+
+    fn find_stage(&self, level: usize) -> usize {
+        // The first stage starts at level 0, so this is never 0
+        let next_stage = self.stages.partition_point(|(start, _)| *start <= level);
+        next_stage - 1
+    }
+}
+
+impl From<Algorithm> for AlgorithmSchedule {
+    fn from(algorithm: Algorithm) -> Self {
+        Self::uniform(algorithm)
+    }
+}
+
+impl Display for AlgorithmSchedule {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for (i, (level, algorithm)) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",{}={}", level, algorithm)?;
+            } else {
+                write!(f, "{}", algorithm)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a schedule such as `backtracker,3=wilson,6=growing-tree:0.9`,
+/// i.e. a comma-separated list of algorithms where all but the first are
+/// prefixed with the level they start at.
+impl FromStr for AlgorithmSchedule {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
+        let mut stages = Vec::new();
+        for (i, stage) in text.split(',').enumerate() {
+            let stage = stage.trim();
+            let (level, algorithm) = match stage.split_once('=') {
+                Some((level, algorithm)) => {
+                    let level = level.trim().parse().map_err(|_| {
+                        format!("invalid level '{}'", level)
+                    })?;
+                    (level, algorithm.trim())
+                },
+                None if i == 0 => (0, stage),
+                None => return Err(format!(
+                    "expected LEVEL=ALGORITHM, got '{}'",
+                    stage
+                ))
+            };
+            stages.push((level, algorithm.parse()?));
+        }
+
+        Self::new(stages).map_err(|error| error.to_string())
+    }
+}
+
+/// A generator for each stage of an [`AlgorithmSchedule`]
+pub struct MazeGenerators {
+    schedule: AlgorithmSchedule,
+    generators: Vec<Box<dyn MazeGenerator + Send>>,
+}
+
+impl MazeGenerators {
+    /// The generator to use for tiles at the given level
+    pub fn get_mut(&mut self, level: usize) -> &mut dyn MazeGenerator {
+        let stage = self.schedule.find_stage(level);
+        self.generators[stage].as_mut()
+    }
+}
@@ -0,0 +1,147 @@
+//! Render the maze on the CPU the same way the CesiumJS custom shader does:
+//! each cell looks up its connection bits in the walls atlas and its
+//! solution bits in the connections atlas. This is handy for previewing
+//! tiles headlessly, or for trying out a new atlas without a browser.
+
+use std::fs::read;
+use std::path::Path;
+
+use image::RgbImage;
+
+use crate::atlas::{AtlasLayout, ATLAS_TILES};
+use crate::error::{MazeError, Result};
+use crate::grid::Grid;
+
+/// Renders are held in memory, so limit them to 16384x16384 pixels
+pub const MAX_RENDER_SIZE: usize = 16384;
+
+// Same colors as the shader
+const FLOOR_COLOR: [f32; 3] = [0.0, 0.1, 0.0];
+const WALL_COLOR: [f32; 3] = [1.0, 0.2, 0.0];
+const PATH_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+
+/// A texture atlas of 16 tiles side by side, indexed by connection bits.
+/// Only the red channel is used, as a mask.
+pub struct Atlas {
+    image: RgbImage,
+    layout: AtlasLayout,
+}
+
+impl Atlas {
+    pub fn new(image: RgbImage, padding: u32) -> Result<Self> {
+        let (width, height) = image.dimensions();
+        let layout = AtlasLayout::from_dimensions(width, height, padding)?;
+        Ok(Self { image, layout })
+    }
+
+    pub fn read(path: &Path, padding: u32) -> Result<Self> {
+        let bytes = read(path).map_err(MazeError::io("could not read atlas", path))?;
+        Self::from_png(&bytes, padding)
+    }
+
+    /// Decode an atlas from an image file in memory, e.g. from
+    /// `AtlasSource::load`
+    pub fn from_png(bytes: &[u8], padding: u32) -> Result<Self> {
+        Self::new(image::load_from_memory(bytes)?.to_rgb8(), padding)
+    }
+
+    /// Sample the mask of one tile with nearest filtering, where (u, v) goes
+    /// from (0, 0) at the southwest corner of the cell to (1, 1) at the
+    /// northeast corner. The shader insets the UVs by half a texel to
+    /// avoid bleeding from neighboring tiles, so this does too.
+    pub fn sample(&self, tile_id: u8, u: f32, v: f32) -> f32 {
+        let AtlasLayout { tile_size, padding } = self.layout;
+        let texel = |uv: f32| {
+            let texel = (0.5 + uv * (tile_size - 1) as f32).floor();
+            (texel.max(0.0) as u32).min(tile_size - 1)
+        };
+
+        let tile_id = tile_id as u32 % ATLAS_TILES;
+        let x = tile_id * self.layout.tile_stride() + padding + texel(u);
+        // Cesium flips textures vertically when loading them, so v = 0 is
+        // the bottom of the image
+        let y = padding + tile_size - 1 - texel(v);
+
+        self.image.get_pixel(x, y)[0] as f32 / 255.0
+    }
+}
+
+/// The CPU version of the custom shader
+pub struct AtlasRenderer {
+    walls: Atlas,
+    connections: Atlas,
+    pixels_per_cell: u32,
+}
+
+impl AtlasRenderer {
+    pub fn new(walls: Atlas, connections: Atlas, pixels_per_cell: u32) -> Result<Self> {
+        if pixels_per_cell == 0 {
+            return Err(MazeError::InvalidInput(
+                "pixels per cell must be at least 1".to_string()
+            ));
+        }
+
+        Ok(Self {
+            walls,
+            connections,
+            pixels_per_cell,
+        })
+    }
+
+    /// Render a single tile's grid
+    pub fn render_grid(&self, grid: &Grid) -> Result<RgbImage> {
+        let size = grid.get_size() as u32;
+        let cells = RgbImage::from_raw(size, size, grid.to_image_bytes())
+            .expect("image bytes should match the grid size");
+        self.render(&cells)
+    }
+
+    /// Render an image with one pixel per cell, like the feature ID
+    /// textures or the output of `export::export_region`. The result is
+    /// pixels_per_cell times bigger, and has north at the top.
+    pub fn render(&self, cells: &RgbImage) -> Result<RgbImage> {
+        let (width, height) = cells.dimensions();
+        let too_big = |cells: u32| {
+            (cells as usize)
+                .checked_mul(self.pixels_per_cell as usize)
+                .is_none_or(|size| size > MAX_RENDER_SIZE)
+        };
+        if too_big(width) || too_big(height) {
+            return Err(MazeError::InvalidInput(format!(
+                "{}x{} cells at {} pixels per cell is too big to render, it must be at most {} pixels wide",
+                width,
+                height,
+                self.pixels_per_cell,
+                MAX_RENDER_SIZE
+            )));
+        }
+
+        let scale = self.pixels_per_cell;
+        let image = RgbImage::from_fn(width * scale, height * scale, |x, y| {
+            // Flip vertically so north is up
+            let cell = cells.get_pixel(x / scale, height - 1 - y / scale);
+            let [connection_id, solution_id, _] = cell.0;
+
+            // Sample at the center of each output pixel
+            let u = ((x % scale) as f32 + 0.5) / scale as f32;
+            let v = 1.0 - ((y % scale) as f32 + 0.5) / scale as f32;
+            let wall_mask = self.walls.sample(connection_id, u, v);
+            let path_mask = self.connections.sample(solution_id, u, v);
+
+            let color = std::array::from_fn(|i| {
+                let color = mix(FLOOR_COLOR[i], WALL_COLOR[i], wall_mask);
+                let color = mix(color, PATH_COLOR[i], path_mask);
+                (color * 255.0).round() as u8
+            });
+
+            image::Rgb(color)
+        });
+
+        Ok(image)
+    }
+}
+
+/// GLSL's mix()
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
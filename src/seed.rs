@@ -0,0 +1,51 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Every tile gets its own random number generator so a tile can be
+/// regenerated on its own no matter what order the tiles are visited in.
+/// ChaCha is used (rather than StdRng) because its output is guaranteed to
+/// be stable across versions of the rand crate.
+pub type TileRng = ChaCha8Rng;
+
+// SplitMix64 finalizer, see https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Hash a sequence of integers into a single 64-bit value. Each step mixes
+/// the running hash before adding the next value so the order of the inputs
+/// matters, i.e. (level, x, y) and (level, y, x) hash differently.
+pub fn hash_values(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0, |hash, value| splitmix64(hash ^ splitmix64(*value)))
+}
+
+/// Pick which half of a connection survives when it is subdivided: 0 for
+/// the half with the smaller coordinate, 1 for the other one.
+///
+/// The connection is identified by its position in the whole level rather
+/// than within a tile, so the tiles on either side of a seam always make the
+/// same choice, and no random bits need to be carried down from the parent.
+/// `line` is the index of the grid line the connection crosses (counting
+/// vertical lines for vertical seams), and `position` is the index of the
+/// parent connection along that line.
+pub fn split_bit(
+    seed: u64,
+    level: usize,
+    vertical: bool,
+    line: u64,
+    position: u64
+) -> usize {
+    let hash = hash_values(&[seed, level as u64, vertical as u64, line, position]);
+    (hash & 1) as usize
+}
+
+/// Derive the random number generator for the tile at (level, x, y)
+pub fn make_tile_rng(seed: u64, level: usize, x: usize, y: usize) -> TileRng {
+    let tile_seed = hash_values(&[seed, level as u64, x as u64, y as u64]);
+    TileRng::seed_from_u64(tile_seed)
+}
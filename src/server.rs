@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::to_string_pretty;
+
+use crate::atlas::AtlasFiles;
+use crate::config::MazeConfig;
+use crate::dfs::DFSSolutionFinder;
+use crate::error::{MazeError, Result};
+use crate::subtree::{SubtreeLayout, TileKey};
+use crate::tile::Tile;
+use crate::tile_format::TileFormat;
+use crate::tileset::{make_tileset_json, TilesetAssets};
+
+/// How long a client gets to send its request or take the response before
+/// the connection is dropped, so a stalled client can't hold on to a worker
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Requests are only a request line and a few headers, anything past this
+/// is ignored
+const MAX_REQUEST_SIZE: u64 = 16 * 1024;
+
+/// A fixed-capacity cache of generated tiles that evicts the least recently
+/// used tile when full.
+struct TileCache {
+    capacity: usize,
+    tiles: HashMap<TileKey, (Arc<Tile>, u64)>,
+    /// Incremented on every access, used as a timestamp for finding the
+    /// least recently used tile.
+    clock: u64,
+}
+
+impl TileCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tiles: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, key: TileKey) -> Option<Arc<Tile>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.tiles.get_mut(&key).map(|(tile, last_used)| {
+            *last_used = clock;
+            tile.clone()
+        })
+    }
+
+    fn insert(&mut self, key: TileKey, tile: Arc<Tile>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.clock += 1;
+        if !self.tiles.contains_key(&key) && self.tiles.len() >= self.capacity {
+            // A linear scan is fine here, generating a tile takes much longer
+            // than scanning a few thousand timestamps.
+            let oldest = self.tiles
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.tiles.remove(&oldest);
+            }
+        }
+        self.tiles.insert(key, (tile, self.clock));
+    }
+}
+
+/// A response to send back to the client
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: "200 OK",
+            content_type,
+            body
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: message.as_bytes().to_vec()
+        }
+    }
+}
+
+/// Serve a tileset over HTTP, generating tiles the first time they are
+/// requested instead of writing them all to disk ahead of time. This makes
+/// it possible to explore far deeper tilesets than could ever be
+/// pre-generated.
+pub struct TileServer {
+    config: MazeConfig,
+    levels: usize,
+    subtrees: SubtreeLayout,
+    format: TileFormat,
+    /// The atlases are read (or drawn) once up front
+    atlases: AtlasFiles,
+    cache: Mutex<TileCache>,
+    /// Number of connections handled at once
+    threads: usize,
+}
+
+impl TileServer {
+    pub fn new(
+        config: MazeConfig,
+        levels: usize,
+        assets: TilesetAssets,
+        subtrees: SubtreeLayout,
+        format: TileFormat,
+        cache_size: usize,
+        threads: usize
+    ) -> Result<Self> {
+        if levels == 0 {
+            return Err(MazeError::InvalidInput(
+                "the tileset must have at least 1 level".to_string()
+            ));
+        }
+
+        if levels > config.max_levels() {
+            return Err(MazeError::InvalidInput(format!(
+                "at most {} levels are supported with a grid size of {}",
+                config.max_levels(),
+                config.grid_size
+            )));
+        }
+
+        format.validate()?;
+
+        if threads == 0 {
+            return Err(MazeError::InvalidInput(
+                "at least 1 thread is needed to handle requests".to_string()
+            ));
+        }
+
+        let atlases = assets.atlases.load()?;
+
+        Ok(Self {
+            config,
+            levels,
+            subtrees: subtrees.for_tileset(levels),
+            format,
+            atlases,
+            cache: Mutex::new(TileCache::new(cache_size)),
+            threads,
+        })
+    }
+
+    /// Listen for requests forever. Connections are handled by a fixed
+    /// number of worker threads, any more wait in the listen backlog.
+    pub fn serve(self, address: &str) -> Result<()> {
+        let listener = TcpListener::bind(address).map_err(|source| {
+            MazeError::Io {
+                message: format!("could not listen on {}", address),
+                source
+            }
+        })?;
+        println!("Serving tileset at http://{}/tileset.json", address);
+
+        // Every worker accepts connections from the same listener
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| self.run_worker(&listener));
+            }
+        });
+
+        Ok(())
+    }
+
+    fn run_worker(&self, listener: &TcpListener) {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    eprintln!("could not accept connection: {}", error);
+                    continue;
+                }
+            };
+
+            if let Err(error) = self.handle_connection(stream) {
+                eprintln!("error handling request: {}", error);
+            }
+        }
+    }
+
+    fn handle_connection(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?.take(MAX_REQUEST_SIZE));
+
+        // We only need the request line, e.g. "GET /tileset.json HTTP/1.1"
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+
+        // Skip the headers, they end with an empty line
+        loop {
+            let mut header = String::new();
+            let length = reader.read_line(&mut header)?;
+            if length == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or("");
+        let target = parts.next().unwrap_or("/");
+        // Drop any query string such as cache-busting parameters
+        let path = target.split('?').next().unwrap_or(target);
+
+        let response = match method {
+            "GET" | "HEAD" => self.route(path),
+            // CORS preflight requests
+            "OPTIONS" => Response::ok("text/plain", Vec::new()),
+            _ => Response::error("405 Method Not Allowed", "method not allowed")
+        };
+
+        println!("{} {} {}", method, path, response.status);
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\n\
+            Content-Type: {}\r\n\
+            Content-Length: {}\r\n\
+            Access-Control-Allow-Origin: *\r\n\
+            Access-Control-Allow-Methods: GET, HEAD, OPTIONS\r\n\
+            Access-Control-Allow-Headers: *\r\n\
+            Connection: close\r\n\
+            \r\n",
+            response.status,
+            response.content_type,
+            response.body.len()
+        )?;
+        if method != "HEAD" {
+            stream.write_all(&response.body)?;
+        }
+        stream.flush()
+    }
+
+    fn route(&self, path: &str) -> Response {
+        let result = match path {
+            "/tileset.json" => self.get_tileset_json(),
+            "/tileset_walls.png" =>
+                Ok(Response::ok("image/png", self.atlases.walls_png.clone())),
+            "/tileset_connections.png" =>
+                Ok(Response::ok("image/png", self.atlases.connections_png.clone())),
+            _ => match Self::parse_tile_path(path) {
+                Some((key, name)) => self.get_tile_file(key, name),
+                None => Ok(self.get_shared_file(path)
+                    .or_else(|| self.get_subtree_file(path))
+                    .unwrap_or_else(|| Response::error("404 Not Found", "not found")))
+            }
+        };
+
+        result.unwrap_or_else(|error| match error {
+            MazeError::InvalidInput(message) =>
+                Response::error("404 Not Found", &message),
+            error => Response::error(
+                "500 Internal Server Error",
+                &error.to_string()
+            )
+        })
+    }
+
+    fn get_tileset_json(&self) -> Result<Response> {
+        let tileset_json = make_tileset_json(
+            &self.config,
+            self.levels,
+            &self.atlases.layout,
+            &self.subtrees,
+            &self.format
+        );
+        let json_string = to_string_pretty(&tileset_json)?;
+        Ok(Response::ok("application/json", json_string.into_bytes()))
+    }
+
+    /// Make the subtree file (or the buffer of a JSON subtree) at path, if
+    /// there is one. Tile metadata would mean generating every tile in the
+    /// subtree up front, so these only describe availability.
+    fn get_subtree_file(&self, path: &str) -> Option<Response> {
+        let name = path.strip_prefix('/')?;
+        let mut parts = name.splitn(4, '.').map(|part| part.parse().ok());
+        let level: usize = parts.next()??;
+        let x: usize = parts.next()??;
+        let y: usize = parts.next()??;
+
+        let is_root = level < self.levels &&
+            level.is_multiple_of(self.subtrees.levels) &&
+            x < (1 << level) &&
+            y < (1 << level);
+        if !is_root {
+            return None;
+        }
+
+        let content_type = if name.ends_with(".json") {
+            "application/json"
+        } else {
+            "application/octet-stream"
+        };
+        self.subtrees.make_files((level, x, y), self.levels, None)
+            .into_iter()
+            .find(|(file_name, _)| file_name == name)
+            .map(|(_, bytes)| Response::ok(content_type, bytes))
+    }
+
+    /// One of the buffers shared by every tile, if path is one
+    fn get_shared_file(&self, path: &str) -> Option<Response> {
+        let name = path.strip_prefix("/tiles/")?;
+        Tile::make_shared_files(&self.format)
+            .into_iter()
+            .find(|(file_name, _)| file_name == name)
+            .map(|(_, bytes)| Response::ok("application/octet-stream", bytes))
+    }
+
+    /// Generate the tile and return the file of its content that was
+    /// requested: the GLB or `.gltf`, or a separate image
+    fn get_tile_file(&self, key: TileKey, name: &str) -> Result<Response> {
+        let tile = self.get_tile(key)?;
+        let bytes = tile.make_file(&self.format, name)?
+            .ok_or_else(|| MazeError::InvalidInput(format!("no such file {}", name)))?;
+
+        let content_type = match name.rsplit('.').next() {
+            Some("glb") => "model/gltf-binary",
+            Some("gltf") => "model/gltf+json",
+            _ => self.format.get_mime_type()
+        };
+        Ok(Response::ok(content_type, bytes))
+    }
+
+    /// Parse a path of the form /tiles/{level}.{x}.{y}.{extension},
+    /// returning the tile and the file name
+    fn parse_tile_path(path: &str) -> Option<(TileKey, &str)> {
+        let filename = path.strip_prefix("/tiles/")?;
+        let mut parts = filename.split('.');
+        let mut next_number = || parts.next()?.parse().ok();
+        let level = next_number()?;
+        let x = next_number()?;
+        let y = next_number()?;
+        // Just the extension should be left
+        if parts.next().is_none() || parts.next().is_some() {
+            return None;
+        }
+
+        Some(((level, x, y), filename))
+    }
+
+    /// Get a tile from the cache, or generate it. Rather than start from the
+    /// root every time, this starts from the closest ancestor that is
+    /// still in the cache. Since the viewer loads tiles from the root down,
+    /// this is usually the parent.
+    fn get_tile(&self, key: TileKey) -> Result<Arc<Tile>> {
+        let (level, x, y) = key;
+        if level >= self.levels {
+            return Err(MazeError::InvalidInput(format!(
+                "level {} is not available, the tileset has {} levels",
+                level,
+                self.levels
+            )));
+        }
+
+        // Check that the coordinates are valid before walking the tree.
+        if x >= (1 << level) || y >= (1 << level) {
+            return Err(MazeError::InvalidInput(format!(
+                "tile ({}, {}) is out of range for level {}",
+                x,
+                y,
+                level
+            )));
+        }
+
+        let mut ancestor = None;
+        {
+            let mut cache = self.cache.lock().expect("tile cache was poisoned");
+            for ancestor_level in (0..=level).rev() {
+                let shift = level - ancestor_level;
+                let ancestor_key = (ancestor_level, x >> shift, y >> shift);
+                if let Some(tile) = cache.get(ancestor_key) {
+                    ancestor = Some(tile);
+                    break;
+                }
+            }
+        }
+
+        let mut generators = self.config.algorithms.make_generators();
+        let mut solver = DFSSolutionFinder::new();
+        let mut tile = match ancestor {
+            Some(tile) => tile,
+            None => {
+                let root = Arc::new(
+                    Tile::make_root(&self.config, &mut generators, &mut solver)
+                );
+                self.cache_tile(root.clone());
+                root
+            }
+        };
+
+        // Walk down to the requested tile, caching tiles along the way
+        while tile.level < level {
+            let shift = level - tile.level - 1;
+            let x_bit = (x >> shift) & 1;
+            let y_bit = (y >> shift) & 1;
+            let quadrant = (y_bit << 1) | x_bit;
+            let child = Arc::new(tile.make_child(
+                quadrant,
+                &self.config,
+                &mut generators,
+                &mut solver
+            ));
+            self.cache_tile(child.clone());
+            tile = child;
+        }
+
+        Ok(tile)
+    }
+
+    fn cache_tile(&self, tile: Arc<Tile>) {
+        let key = (tile.level, tile.x, tile.y);
+        self.cache.lock().expect("tile cache was poisoned").insert(key, tile);
+    }
+}
@@ -0,0 +1,395 @@
+//! Subtree files for the implicit tileset. Every tile is always available,
+//! but the subtrees also carry per-tile metadata (3D Tiles 1.1 tile
+//! metadata) so a viewer can tell tiles apart without decoding their
+//! textures, e.g. to highlight the tiles on the solution.
+//!
+//! The tileset is split into subtrees of a fixed number of levels, so no
+//! single file has to describe a whole deep tileset.
+
+use serde_json::{json, Value};
+
+use crate::config::MazeConfig;
+use crate::direction::Direction;
+use crate::error::{MazeError, Result};
+use crate::grid_coords::GridCoords;
+use crate::maze_generator::Algorithm;
+use crate::tile::Tile;
+
+const SUBTREE_MAGIC: &[u8; 4] = b"subt";
+const SUBTREE_VERSION: u32 = 1;
+/// A subtree has 4^levels tiles' worth of availability and metadata, which
+/// is already over a million tiles at 10 levels
+pub const MAX_SUBTREE_LEVELS: usize = 10;
+const HEADER_LENGTH: usize = 24;
+
+/// (level, x, y) of a tile
+pub type TileKey = (usize, usize, usize);
+
+/// Fill in the {level}, {x} and {y} of an implicit tiling URI template
+pub fn fill_uri_template(template: &str, (level, x, y): TileKey) -> String {
+    template
+        .replace("{level}", &level.to_string())
+        .replace("{x}", &x.to_string())
+        .replace("{y}", &y.to_string())
+}
+
+/// Summary of a single tile, stored in the subtree's property table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileStats {
+    /// Number of connections across the edges of the tile
+    pub exit_count: u16,
+    /// Number of cells on the solution path
+    pub solution_length: u32,
+    /// Number of cells with only one connection
+    pub dead_end_count: u32,
+    pub algorithm: Algorithm,
+    /// Whether any part of the solution of the whole maze passes through
+    /// this tile
+    pub on_solution: bool,
+    /// Number of triangles in the tile's content
+    pub triangle_count: u32,
+}
+
+impl TileStats {
+    /// The triangle count comes from building the tile's content, see
+    /// `Tile::write_files`
+    pub fn new(config: &MazeConfig, tile: &Tile, triangle_count: usize) -> Self {
+        let grid = tile.get_grid();
+        let size = grid.get_size();
+
+        let mut exit_count = 0;
+        let mut solution_length = 0;
+        let mut dead_end_count = 0;
+        for y in 0..size {
+            for x in 0..size {
+                let cell = grid.get_cell(GridCoords { x, y });
+                if cell.get_connection_bits().count_ones() == 1 {
+                    dead_end_count += 1;
+                }
+                if cell.get_solution_bits() != 0 {
+                    solution_length += 1;
+                }
+
+                let boundaries = [
+                    (Direction::Right, x == size - 1),
+                    (Direction::Up, y == size - 1),
+                    (Direction::Left, x == 0),
+                    (Direction::Down, y == 0),
+                ];
+                for (direction, on_boundary) in boundaries {
+                    if on_boundary && cell.is_connected(direction) {
+                        exit_count += 1;
+                    }
+                }
+            }
+        }
+
+        Self {
+            exit_count,
+            solution_length,
+            dead_end_count,
+            algorithm: config.algorithms.get_algorithm(tile.level),
+            on_solution: solution_length > 0,
+            triangle_count: triangle_count as u32,
+        }
+    }
+}
+
+/// Index of a tile within a subtree, relative to the subtree root: all the tiles
+/// of the levels above come first, then the tiles of its level in Morton
+/// order. This is the order of the rows in the tile metadata.
+///
+/// Panics if the level is too deep for the index to fit in a usize, which
+/// `SubtreeLayout` keeps well clear of.
+pub fn get_subtree_index(level: usize, x: usize, y: usize) -> usize {
+    let tiles_in_levels = u32::try_from(2 * level)
+        .ok()
+        .and_then(|shift| 1usize.checked_shl(shift))
+        .expect("subtree level too deep to index");
+    let tiles_above = (tiles_in_levels - 1) / 3;
+    tiles_above + morton_index(x, y)
+}
+
+/// Interleave the bits of x and y, with x in the lower bit
+fn morton_index(x: usize, y: usize) -> usize {
+    let mut index = 0;
+    for bit in 0..(usize::BITS / 2) {
+        index |= ((x >> bit) & 1) << (2 * bit);
+        index |= ((y >> bit) & 1) << (2 * bit + 1);
+    }
+
+    index
+}
+
+/// How the tileset is split into subtrees, and how they are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubtreeLayout {
+    /// Number of levels in each subtree (subtreeLevels)
+    pub levels: usize,
+    pub format: SubtreeFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubtreeFormat {
+    /// A single .subtree file with the JSON and binary chunks together
+    Binary,
+    /// A .subtree.json file, plus a .subtree.bin buffer when the subtree
+    /// has tile metadata or partial availability
+    Json,
+}
+
+impl Default for SubtreeLayout {
+    fn default() -> Self {
+        Self {
+            levels: 4,
+            format: SubtreeFormat::Binary,
+        }
+    }
+}
+
+impl SubtreeLayout {
+    pub fn new(levels: usize, format: SubtreeFormat) -> Result<Self> {
+        if levels == 0 {
+            return Err(MazeError::InvalidInput(
+                "subtrees must have at least 1 level".to_string()
+            ));
+        }
+        if levels > MAX_SUBTREE_LEVELS {
+            return Err(MazeError::InvalidInput(format!(
+                "subtrees can have at most {} levels, not {}",
+                MAX_SUBTREE_LEVELS,
+                levels
+            )));
+        }
+
+        Ok(Self { levels, format })
+    }
+
+    /// Subtrees never need more levels than the tileset has, and the
+    /// availability of a subtree grows with 4^levels, so clamp the levels
+    /// to the tileset
+    pub fn for_tileset(self, tileset_levels: usize) -> Self {
+        Self {
+            levels: self.levels.min(tileset_levels),
+            ..self
+        }
+    }
+
+    pub fn get_uri_template(&self) -> &'static str {
+        match self.format {
+            SubtreeFormat::Binary => "{level}.{x}.{y}.subtree",
+            SubtreeFormat::Json => "{level}.{x}.{y}.subtree.json",
+        }
+    }
+
+    pub fn get_file_name(&self, key: TileKey) -> String {
+        fill_uri_template(self.get_uri_template(), key)
+    }
+
+    /// Find the root of the subtree that holds the given tile
+    pub fn get_root(&self, (level, x, y): TileKey) -> TileKey {
+        let root_level = level - level % self.levels;
+        let depth = level - root_level;
+        (root_level, x >> depth, y >> depth)
+    }
+
+    /// Find a tile's row in the tile metadata of its subtree
+    pub fn get_row(&self, (level, x, y): TileKey) -> usize {
+        let depth = level % self.levels;
+        let mask = (1 << depth) - 1;
+        get_subtree_index(depth, x & mask, y & mask)
+    }
+
+    /// List the roots of every subtree in a tileset, top down
+    pub fn get_roots(&self, tileset_levels: usize) -> Vec<TileKey> {
+        let mut roots = Vec::new();
+        for level in (0..tileset_levels).step_by(self.levels) {
+            let tiles_per_side = 1 << level;
+            for y in 0..tiles_per_side {
+                for x in 0..tiles_per_side {
+                    roots.push((level, x, y));
+                }
+            }
+        }
+
+        roots
+    }
+
+    /// Make the files for the subtree at root. Every tile down to the
+    /// bottom of the tileset is available, and so is every child subtree.
+    /// If tile stats are given, there must be one for every tile in the
+    /// subtree, in the order of `get_row`. This returns the file names and
+    /// their contents.
+    pub fn make_files(
+        &self,
+        root: TileKey,
+        tileset_levels: usize,
+        stats: Option<&[TileStats]>
+    ) -> Vec<(String, Vec<u8>)> {
+        let (root_level, _, _) = root;
+        // The last subtree is cut short if the subtree levels don't divide
+        // the tileset levels evenly
+        let levels = self.levels.min(tileset_levels - root_level);
+        let has_children = root_level + self.levels < tileset_levels;
+
+        let mut binary = BinaryChunk::new();
+        let availability = if levels == self.levels {
+            json!({ "constant": 1 })
+        } else {
+            // Only the tiles in the levels that exist are available, which
+            // are the first ones in the bitstream
+            let total = get_subtree_index(self.levels, 0, 0);
+            let available = get_subtree_index(levels, 0, 0);
+            let mut bits = vec![0u8; total.div_ceil(8)];
+            for i in 0..available {
+                bits[i / 8] |= 1 << (i % 8);
+            }
+
+            json!({
+                "bitstream": binary.add_buffer_view(&bits),
+                "availableCount": available
+            })
+        };
+
+        let mut subtree_json = json!({
+            "tileAvailability": availability,
+            "contentAvailability": [availability],
+            "childSubtreeAvailability": {
+                "constant": has_children as u8
+            }
+        });
+
+        if let Some(stats) = stats {
+            assert_eq!(
+                stats.len(),
+                get_subtree_index(levels, 0, 0),
+                "there must be stats for every tile in the subtree"
+            );
+            let table = make_property_table(&mut binary, stats);
+            subtree_json["propertyTables"] = json!([table]);
+            subtree_json["tileMetadata"] = json!(0);
+        }
+
+        let file_name = self.get_file_name(root);
+        // JSON subtrees keep their buffer in a separate file, binary
+        // subtrees use their own binary chunk
+        let buffer_name = file_name.replace(".json", ".bin");
+        if !binary.bytes.is_empty() {
+            let mut buffer = json!({ "byteLength": binary.bytes.len() });
+            if self.format == SubtreeFormat::Json {
+                buffer["uri"] = json!(buffer_name);
+            }
+            subtree_json["buffers"] = json!([buffer]);
+            subtree_json["bufferViews"] = Value::Array(binary.buffer_views.clone());
+        }
+
+        match self.format {
+            SubtreeFormat::Binary => vec![
+                (file_name, make_binary_subtree(&subtree_json, binary.bytes))
+            ],
+            SubtreeFormat::Json => {
+                let json_bytes = subtree_json.to_string().into_bytes();
+                let mut files = vec![(file_name, json_bytes)];
+                if !binary.bytes.is_empty() {
+                    files.push((buffer_name, binary.bytes));
+                }
+                files
+            }
+        }
+    }
+}
+
+/// Put the JSON and binary chunks together into a .subtree file
+fn make_binary_subtree(subtree_json: &Value, binary_bytes: Vec<u8>) -> Vec<u8> {
+    let mut json_bytes = subtree_json.to_string().into_bytes();
+    // Both chunks must be padded to 8 bytes, the JSON with spaces. The
+    // buffer views already pad the binary chunk.
+    json_bytes.resize(json_bytes.len().next_multiple_of(8), b' ');
+
+    let mut result = Vec::with_capacity(HEADER_LENGTH + json_bytes.len() + binary_bytes.len());
+    result.extend_from_slice(SUBTREE_MAGIC);
+    result.extend_from_slice(&SUBTREE_VERSION.to_le_bytes());
+    result.extend_from_slice(&(json_bytes.len() as u64).to_le_bytes());
+    result.extend_from_slice(&(binary_bytes.len() as u64).to_le_bytes());
+    result.extend_from_slice(&json_bytes);
+    result.extend_from_slice(&binary_bytes);
+
+    result
+}
+
+fn make_property_table(binary: &mut BinaryChunk, stats: &[TileStats]) -> Value {
+    let exit_counts: Vec<u8> = stats.iter()
+        .flat_map(|stats| stats.exit_count.to_le_bytes())
+        .collect();
+    let solution_lengths: Vec<u8> = stats.iter()
+        .flat_map(|stats| stats.solution_length.to_le_bytes())
+        .collect();
+    let dead_end_counts: Vec<u8> = stats.iter()
+        .flat_map(|stats| stats.dead_end_count.to_le_bytes())
+        .collect();
+    let algorithms: Vec<u8> = stats.iter()
+        .map(|stats| get_algorithm_value(stats.algorithm))
+        .collect();
+    let triangle_counts: Vec<u8> = stats.iter()
+        .flat_map(|stats| stats.triangle_count.to_le_bytes())
+        .collect();
+
+    // Booleans are a bitstream, lowest bit first
+    let mut on_solution = vec![0u8; stats.len().div_ceil(8)];
+    for (i, stats) in stats.iter().enumerate() {
+        on_solution[i / 8] |= (stats.on_solution as u8) << (i % 8);
+    }
+
+    json!({
+        "class": "tile",
+        "count": stats.len(),
+        "properties": {
+            "exit_count": { "values": binary.add_buffer_view(&exit_counts) },
+            "solution_length": { "values": binary.add_buffer_view(&solution_lengths) },
+            "dead_end_count": { "values": binary.add_buffer_view(&dead_end_counts) },
+            "algorithm": { "values": binary.add_buffer_view(&algorithms) },
+            "on_solution": { "values": binary.add_buffer_view(&on_solution) },
+            "triangle_count": { "values": binary.add_buffer_view(&triangle_counts) }
+        }
+    })
+}
+
+/// The value of an algorithm in the algorithm enum of the schema, i.e. its
+/// index in `Algorithm::ALL`. The parameters of the growing tree algorithm
+/// aren't included.
+pub fn get_algorithm_value(algorithm: Algorithm) -> u8 {
+    Algorithm::ALL
+        .iter()
+        .position(|other| other.name() == algorithm.name())
+        .expect("Algorithm::ALL should have every algorithm") as u8
+}
+
+/// The binary chunk of the subtree file, which holds all the buffer views
+struct BinaryChunk {
+    bytes: Vec<u8>,
+    buffer_views: Vec<Value>,
+}
+
+impl BinaryChunk {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            buffer_views: Vec::new(),
+        }
+    }
+
+    /// Add a buffer view, returning its index. Buffer views are aligned to
+    /// 8 bytes.
+    fn add_buffer_view(&mut self, data: &[u8]) -> usize {
+        let offset = self.bytes.len();
+        self.bytes.extend_from_slice(data);
+        self.bytes.resize(self.bytes.len().next_multiple_of(8), 0);
+
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": data.len()
+        }));
+        self.buffer_views.len() - 1
+    }
+}
@@ -0,0 +1,199 @@
+//! Vector drawings of the maze, e.g. for documentation or laser cutting.
+//! This draws from the same one-pixel-per-cell images as the `export`
+//! module, so it works for a single tile or any rectangle of tiles.
+
+use std::fmt::Write;
+
+use image::RgbImage;
+
+use crate::direction::Direction;
+
+const UP: u8 = Direction::Up.bit();
+const DOWN: u8 = Direction::Down.bit();
+const LEFT: u8 = Direction::Left.bit();
+const RIGHT: u8 = Direction::Right.bit();
+
+/// How to draw the maze. Colors can be anything SVG understands, e.g.
+/// "black" or "#ff8800"
+#[derive(Clone, Debug)]
+pub struct SvgStyle {
+    /// Width and height of each cell in SVG units
+    pub cell_size: f64,
+    pub wall_color: String,
+    pub wall_width: f64,
+    /// Color of the solution path, or None to leave it out
+    pub solution_color: Option<String>,
+    pub solution_width: f64,
+    /// Fill color behind the maze, or None for transparent
+    pub background_color: Option<String>,
+}
+
+impl Default for SvgStyle {
+    fn default() -> Self {
+        Self {
+            cell_size: 10.0,
+            wall_color: "black".to_string(),
+            wall_width: 1.0,
+            solution_color: None,
+            solution_width: 2.0,
+            background_color: None,
+        }
+    }
+}
+
+/// Draw an image from `export::export_level` or `export::export_region` as
+/// an SVG, with north at the top. Walls are merged into the longest
+/// possible straight lines, which keeps the file small and makes a laser
+/// cutter's job easier. Openings around the edge are where the maze
+/// continues into neighboring tiles (or the exits of the root tile).
+pub fn render_svg(cells: &RgbImage, style: &SvgStyle) -> String {
+    let (width, height) = cells.dimensions();
+    let connections = |x: u32, y: u32| cells.get_pixel(x, y)[0];
+    let solution = |x: u32, y: u32| cells.get_pixel(x, y)[1];
+
+    // Image row 0 is the south edge, but SVG y points down
+    let cell = style.cell_size;
+    let to_x = |x: f64| x * cell;
+    let to_y = |y: f64| (height as f64 - y) * cell;
+
+    let mut walls = String::new();
+
+    // Horizontal walls, along the grid lines y = 0, 1, ... height
+    for line in 0..=height {
+        let is_wall = |x: u32| {
+            if line == 0 {
+                connections(x, 0) & DOWN == 0
+            } else {
+                connections(x, line - 1) & UP == 0
+            }
+        };
+        for (start, end) in find_runs(width, is_wall) {
+            let y = to_y(line as f64);
+            add_line(&mut walls, (to_x(start as f64), y), (to_x(end as f64), y));
+        }
+    }
+
+    // Vertical walls, along the grid lines x = 0, 1, ... width
+    for line in 0..=width {
+        let is_wall = |y: u32| {
+            if line == 0 {
+                connections(0, y) & LEFT == 0
+            } else {
+                connections(line - 1, y) & RIGHT == 0
+            }
+        };
+        for (start, end) in find_runs(height, is_wall) {
+            let x = to_x(line as f64);
+            add_line(&mut walls, (x, to_y(start as f64)), (x, to_y(end as f64)));
+        }
+    }
+
+    // The solution goes from the center of each cell to the center of the
+    // next one, or to the edge of the drawing
+    let mut path = String::new();
+    if style.solution_color.is_some() {
+        for y in 0..height {
+            for x in 0..width {
+                let bits = solution(x, y);
+                let center = (to_x(x as f64 + 0.5), to_y(y as f64 + 0.5));
+                if bits & RIGHT != 0 {
+                    let end_x = if x + 1 < width { x as f64 + 1.5 } else { width as f64 };
+                    add_line(&mut path, center, (to_x(end_x), center.1));
+                }
+                if bits & UP != 0 {
+                    let end_y = if y + 1 < height { y as f64 + 1.5 } else { height as f64 };
+                    add_line(&mut path, center, (center.0, to_y(end_y)));
+                }
+                if bits & LEFT != 0 && x == 0 {
+                    add_line(&mut path, center, (to_x(0.0), center.1));
+                }
+                if bits & DOWN != 0 && y == 0 {
+                    add_line(&mut path, center, (center.0, to_y(0.0)));
+                }
+            }
+        }
+    }
+
+    // Leave room for the strokes around the edge
+    let margin = style.wall_width.max(style.solution_width);
+    let svg_width = width as f64 * cell + 2.0 * margin;
+    let svg_height = height as f64 * cell + 2.0 * margin;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+        svg_width,
+        svg_height,
+        -margin,
+        -margin,
+        svg_width,
+        svg_height
+    );
+    if let Some(color) = &style.background_color {
+        let _ = writeln!(
+            svg,
+            r#"  <rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            -margin,
+            -margin,
+            svg_width,
+            svg_height,
+            escape(color)
+        );
+    }
+    if let (Some(color), false) = (&style.solution_color, path.is_empty()) {
+        let _ = writeln!(
+            svg,
+            r#"  <path d="{}" fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+            path.trim_end(),
+            escape(color),
+            style.solution_width
+        );
+    }
+    if !walls.is_empty() {
+        let _ = writeln!(
+            svg,
+            r#"  <path d="{}" fill="none" stroke="{}" stroke-width="{}" stroke-linecap="square"/>"#,
+            walls.trim_end(),
+            escape(&style.wall_color),
+            style.wall_width
+        );
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+/// Find runs of consecutive indices in 0..length where is_wall is true, as
+/// (start, end) pairs with end exclusive
+fn find_runs(length: u32, is_wall: impl Fn(u32) -> bool) -> Vec<(u32, u32)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for i in 0..length {
+        match (is_wall(i), start) {
+            (true, None) => start = Some(i),
+            (false, Some(run_start)) => {
+                runs.push((run_start, i));
+                start = None;
+            },
+            _ => {}
+        }
+    }
+    if let Some(run_start) = start {
+        runs.push((run_start, length));
+    }
+
+    runs
+}
+
+fn add_line(path: &mut String, from: (f64, f64), to: (f64, f64)) {
+    let _ = write!(path, "M{} {}L{} {} ", from.0, from.1, to.0, to.1);
+}
+
+/// Colors end up in attributes, so make sure they can't break out of them
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
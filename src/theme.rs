@@ -0,0 +1,197 @@
+//! Themes are sets of hand-made atlases described by a small JSON manifest,
+//! so alternate tilemaps (like pixel art) can be swapped in at generation
+//! time. A theme is a directory with a `theme.json` like this:
+//!
+//! ```json
+//! {
+//!     "name": "pixel-art",
+//!     "walls": "walls.png",
+//!     "connections": "connections.png",
+//!     "columns": 4,
+//!     "padding": 0,
+//!     "bit_order": ["right", "up", "left", "down"]
+//! }
+//! ```
+//!
+//! `columns` is how many tiles are in each row of the images (16 by
+//! default, i.e. a single row), `padding` is the number of pixels around
+//! each tile, and `bit_order` is which direction each bit of a tile's index
+//! means, lowest bit first. The default is the order used by
+//! `Cell::get_connection_bits`. Themes are rearranged into a single row in
+//! that order when loaded, so the shader doesn't have to know about any of
+//! this.
+
+use std::fs::{read, read_to_string};
+use std::path::{Path, PathBuf};
+
+use image::{GenericImage, GenericImageView, RgbaImage};
+use serde_json::{from_str, Value};
+
+use crate::atlas::{encode_png, AtlasFiles, AtlasLayout, ATLAS_TILES};
+use crate::direction::Direction;
+use crate::error::{MazeError, Result};
+use crate::grid::BIT_ORDER;
+
+pub const MANIFEST_NAME: &str = "theme.json";
+
+pub struct Theme {
+    pub name: String,
+    pub walls: PathBuf,
+    pub connections: PathBuf,
+    /// Number of tiles in each row of the atlas images
+    pub columns: u32,
+    pub padding: u32,
+    /// Which direction each bit of an atlas tile's index means, lowest bit
+    /// first
+    pub bit_order: [Direction; 4],
+}
+
+impl Theme {
+    /// Read a theme from its manifest. path can either be the manifest
+    /// itself or the directory containing it. The atlas paths are relative
+    /// to the manifest.
+    pub fn read(path: &Path) -> Result<Self> {
+        let manifest_path = if path.is_dir() {
+            path.join(MANIFEST_NAME)
+        } else {
+            path.to_path_buf()
+        };
+        let json_string = read_to_string(&manifest_path)
+            .map_err(MazeError::io("could not read theme manifest", &manifest_path))?;
+        let manifest: Value = from_str(&json_string)?;
+
+        let invalid = |message: String| MazeError::InvalidInput(format!(
+            "{}: {}",
+            manifest_path.display(),
+            message
+        ));
+        let get_string = |key: &str| manifest[key]
+            .as_str()
+            .ok_or_else(|| invalid(format!("{} must be a string", key)));
+        let get_number = |key: &str, default: u32| match &manifest[key] {
+            Value::Null => Ok(default),
+            value => value.as_u64()
+                .and_then(|number| u32::try_from(number).ok())
+                .ok_or_else(|| invalid(format!("{} must be a positive integer", key)))
+        };
+
+        let directory = manifest_path.parent().unwrap_or(Path::new(""));
+        let bit_order = match &manifest["bit_order"] {
+            Value::Null => BIT_ORDER,
+            value => parse_bit_order(value).ok_or_else(|| invalid(
+                "bit_order must list right, up, left and down once each".to_string()
+            ))?
+        };
+
+        let theme = Self {
+            name: get_string("name")?.to_string(),
+            walls: directory.join(get_string("walls")?),
+            connections: directory.join(get_string("connections")?),
+            columns: get_number("columns", ATLAS_TILES)?,
+            padding: get_number("padding", 0)?,
+            bit_order,
+        };
+
+        if theme.columns == 0 || !ATLAS_TILES.is_multiple_of(theme.columns) {
+            return Err(invalid(format!(
+                "columns must divide the {} atlas tiles evenly",
+                ATLAS_TILES
+            )));
+        }
+
+        Ok(theme)
+    }
+
+    /// Read both atlases, check that they have a tile for every
+    /// combination of connection bits and rearrange them into the layout
+    /// the shader expects.
+    pub fn load(&self) -> Result<AtlasFiles> {
+        let (walls, layout) = self.read_atlas(&self.walls)?;
+        let (connections, connections_layout) = self.read_atlas(&self.connections)?;
+        if connections_layout != layout {
+            return Err(MazeError::InvalidInput(format!(
+                "{} and {} must be the same size",
+                self.walls.display(),
+                self.connections.display()
+            )));
+        }
+
+        Ok(AtlasFiles {
+            walls_png: encode_png(&walls)?,
+            connections_png: encode_png(&connections)?,
+            layout,
+        })
+    }
+
+    fn read_atlas(&self, path: &Path) -> Result<(RgbaImage, AtlasLayout)> {
+        let bytes = read(path).map_err(MazeError::io("could not read atlas", path))?;
+        let image = image::load_from_memory(&bytes)?.to_rgba8();
+
+        let (width, height) = image.dimensions();
+        let rows = ATLAS_TILES / self.columns;
+        let stride = width / self.columns;
+        let fits = width.is_multiple_of(self.columns) &&
+            height == rows * stride &&
+            stride > 2 * self.padding;
+        if !fits {
+            return Err(MazeError::InvalidInput(format!(
+                "{} is {}x{}, which can't be split into {}x{} square tiles (one for each combination of connection bits) with {} pixels of padding",
+                path.display(),
+                width,
+                height,
+                self.columns,
+                rows,
+                self.padding
+            )));
+        }
+
+        let layout = AtlasLayout {
+            tile_size: stride - 2 * self.padding,
+            padding: self.padding,
+        };
+        let (atlas_width, atlas_height) = layout.dimensions();
+        let mut atlas = RgbaImage::new(atlas_width, atlas_height);
+        for bits in 0..ATLAS_TILES {
+            let index = self.get_theme_index(bits as u8);
+            let tile = image.view(
+                (index % self.columns) * stride,
+                (index / self.columns) * stride,
+                stride,
+                stride
+            );
+            atlas.copy_from(&*tile, bits * stride, 0)?;
+        }
+
+        Ok((atlas, layout))
+    }
+
+    /// Find the index of the tile in this theme's images for the given
+    /// connection bits
+    fn get_theme_index(&self, bits: u8) -> u32 {
+        self.bit_order
+            .iter()
+            .enumerate()
+            .filter(|(_, direction)| bits & direction.bit() != 0)
+            .map(|(theme_bit, _)| 1 << theme_bit)
+            .sum()
+    }
+}
+
+fn parse_bit_order(value: &Value) -> Option<[Direction; 4]> {
+    let names = value.as_array().filter(|names| names.len() == 4)?;
+
+    let mut bit_order = BIT_ORDER;
+    for (direction, name) in bit_order.iter_mut().zip(names) {
+        *direction = match name.as_str()? {
+            "right" => Direction::Right,
+            "up" => Direction::Up,
+            "left" => Direction::Left,
+            "down" => Direction::Down,
+            _ => return None
+        };
+    }
+
+    // Every direction must appear exactly once
+    let all_present = BIT_ORDER.iter().all(|direction| bit_order.contains(direction));
+    all_present.then_some(bit_order)
+}
@@ -1,18 +1,26 @@
-use std::fs::File;
-use std::io::Cursor;
-use std::io::prelude::*;
+use std::fs::{read, write};
 use std::path::Path;
 
 use chrono::{Datelike, Utc};
-use serde_json::{json, to_string};
+use image::{Rgba, RgbaImage};
+use serde_json::{json, to_string, to_string_pretty};
 
+use crate::config::MazeConfig;
 use crate::direction::Direction;
-use crate::dfs::{DFSMaze, DFSSolutionFinder};
-use crate::geometry::get_buffer_size;
-use crate::grid::Grid;
-use crate::grid_coords::GRID_SIZE;
+use crate::dfs::DFSSolutionFinder;
+use crate::error::{MazeError, Result};
+use crate::features::{append_property_table, make_feature_ids};
+use crate::geometry::{self, get_buffer_size, make_buffer};
+use crate::glb::{get_texture_source, Glb};
+use crate::grid::{Grid, CONNECTIONS_CHANNEL, SOLUTION_CHANNEL};
+use crate::maze_generator::MazeGenerators;
+use crate::seed::{make_tile_rng, split_bit};
+use crate::tile_format::{decode_image, ContentLayout, TileFormat, TileGeometry};
+use crate::walls::{make_meshes, WallStyle};
 
-const HALF_GRID_SIZE: usize = GRID_SIZE / 2;
+/// Shared buffers, which go in the tiles directory next to the tiles
+const GEOMETRY_URI: &str = "geometry.bin";
+const PROPERTIES_URI: &str = "properties.bin";
 
 pub struct Tile {
     pub level: usize,
@@ -22,163 +30,513 @@ pub struct Tile {
 }
 
 impl Tile {
-    pub fn new() -> Self {
+    pub fn new(grid_size: usize) -> Self {
         Self {
             level: 0,
             x: 0,
             y: 0,
-            grid: Grid::new()
+            grid: Grid::new(grid_size)
         }
     }
 
+    /// Wrap an existing grid, e.g. one read back from a GLB file
+    pub fn from_grid(level: usize, x: usize, y: usize, grid: Grid) -> Self {
+        Self {
+            level,
+            x,
+            y,
+            grid
+        }
+    }
+
+    pub fn get_grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn into_grid(self) -> Grid {
+        self.grid
+    }
+
     pub fn make_root(
-        maze_gen: &mut DFSMaze,
+        config: &MazeConfig,
+        generators: &mut MazeGenerators,
         solver: &mut DFSSolutionFinder
     ) -> Self {
-        let mut root = Self::new();
+        let mut root = Self::new(config.grid_size);
+        let mut rng = make_tile_rng(config.seed, root.level, root.x, root.y);
         root.grid.mark_boundaries();
-        root.grid.mark_exit(Direction::Down, 3);
-        root.grid.mark_exit(Direction::Up, 5);
-        maze_gen.maze_fill(&mut root.grid);
+        for exit in &config.root_exits {
+            root.grid.mark_exit(exit.direction, exit.index);
+        }
+        generators.get_mut(root.level).maze_fill(&mut root.grid, &mut rng);
         solver.solve_all_paths(&mut root.grid);
 
         root
     }
 
+    /// Regenerate the tile at (level, x, y) without generating the rest of
+    /// the tileset. Only the chain of ancestors from the root down to the
+    /// tile is generated, and only in memory. Since each tile has its own
+    /// RNG, the result is identical to the same tile in a full tileset.
+    pub fn generate(
+        config: &MazeConfig,
+        level: usize,
+        x: usize,
+        y: usize
+    ) -> Result<Self> {
+        let in_range = level < config.max_levels() &&
+            x < (1 << level) &&
+            y < (1 << level);
+        if !in_range {
+            return Err(MazeError::InvalidInput(format!(
+                "tile ({}, {}) is out of range for level {}",
+                x,
+                y,
+                level
+            )));
+        }
+
+        let mut generators = config.algorithms.make_generators();
+        let mut solver = DFSSolutionFinder::new();
+        let mut tile = Self::make_root(config, &mut generators, &mut solver);
+
+        // Walk down the quadtree towards the requested tile. The bits of
+        // x and y select which child to keep at each level
+        for child_level in (0..level).rev() {
+            let x_bit = (x >> child_level) & 1;
+            let y_bit = (y >> child_level) & 1;
+            let quadrant = (y_bit << 1) | x_bit;
+            tile = tile.make_child(quadrant, config, &mut generators, &mut solver);
+        }
+
+        Ok(tile)
+    }
+
+    /// Subdivide the tile into its 4 children in Morton order:
+    /// southwest, southeast, northwest, northeast
     pub fn subdivide(
         &self,
-        maze_gen: &mut DFSMaze,
+        config: &MazeConfig,
+        generators: &mut MazeGenerators,
         solver: &mut DFSSolutionFinder
     ) -> [Self; 4] {
-        let bottom = self.grid.get_horizontal_seam(0, Direction::Down);
-        let h_middle = 
-            self.grid.get_horizontal_seam(HALF_GRID_SIZE, Direction::Down);
-        let top = self.grid.get_horizontal_seam(GRID_SIZE - 1, Direction::Up);
-        
-        let left = self.grid.get_vertical_seam(0, Direction::Left);
-        let v_middle = 
-            self.grid.get_vertical_seam(HALF_GRID_SIZE, Direction::Left);
-        let right = 
-            self.grid.get_vertical_seam(GRID_SIZE - 1, Direction::Right);
-        
-        // In Morton order:
-        // Southwest
-        let mut sw = Self::new();
-        sw.level = self.level + 1;
-        sw.x = self.x << 1;
-        sw.y = self.y << 1;
-        sw.grid.set_boundary(
-            // Right
-            &v_middle[0..HALF_GRID_SIZE],
-            // Up
-            &h_middle[0..HALF_GRID_SIZE],
-            // Left
-            &left[0..HALF_GRID_SIZE],
-            // Down
-            &bottom[0..HALF_GRID_SIZE]
-        );
-        self.grid.propagate_interior(
-            &mut sw.grid,
-            0..HALF_GRID_SIZE,
-            0..HALF_GRID_SIZE
-        );
+        [0, 1, 2, 3].map(|quadrant| {
+            self.make_child(quadrant, config, generators, solver)
+        })
+    }
 
-        // Southeast
-        let mut se = Self::new();
-        se.level = self.level + 1;
-        se.x = self.x << 1 | 1;
-        se.y = self.y << 1;
-        se.grid.set_boundary(
-            // Right
-            &right[0..HALF_GRID_SIZE],
-            // Up
-            &h_middle[HALF_GRID_SIZE..GRID_SIZE],
-            // Left
-            &v_middle[0..HALF_GRID_SIZE],
-            // Down
-            &bottom[HALF_GRID_SIZE..GRID_SIZE]
-        );
-        self.grid.propagate_interior(
-            &mut se.grid, 
-            HALF_GRID_SIZE..GRID_SIZE, 
-            0..HALF_GRID_SIZE
-        );
+    /// Generate one of the 4 children of this tile. The quadrant is the
+    /// child's index in Morton order, i.e. bit 0 is set for the eastern
+    /// children and bit 1 is set for the northern children.
+    pub fn make_child(
+        &self,
+        quadrant: usize,
+        config: &MazeConfig,
+        generators: &mut MazeGenerators,
+        solver: &mut DFSSolutionFinder
+    ) -> Self {
+        let grid_size = self.grid.get_size();
+        let half_size = grid_size / 2;
 
-        // Northwest
-        let mut nw = Self::new();
-        nw.level = self.level + 1;
-        nw.x = self.x << 1;
-        nw.y = self.y << 1 | 1;
-        nw.grid.set_boundary(
-            // Right
-            &v_middle[HALF_GRID_SIZE..GRID_SIZE],
-            // Up
-            &top[0..HALF_GRID_SIZE],
-            // Left
-            &left[HALF_GRID_SIZE..GRID_SIZE],
-            // Down
-            &h_middle[0..HALF_GRID_SIZE]
-        );
-        self.grid.propagate_interior(
-            &mut nw.grid,
-            0..HALF_GRID_SIZE,
-            HALF_GRID_SIZE..GRID_SIZE
-        );
+        let x_bit = quadrant & 1;
+        let y_bit = (quadrant >> 1) & 1;
 
-        // Northeast
-        let mut ne = Self::new();
-        ne.level = self.level + 1;
-        ne.x = self.x << 1 | 1;
-        ne.y = self.y << 1 | 1;
-        ne.grid.set_boundary(
-            // Right
-            &right[HALF_GRID_SIZE..GRID_SIZE],
-            // Up
-            &top[HALF_GRID_SIZE..GRID_SIZE],
-            // Left
-            &v_middle[HALF_GRID_SIZE..GRID_SIZE],
-            // Down
-            &h_middle[HALF_GRID_SIZE..GRID_SIZE],
+        // The range of parent cells that this child covers
+        let x_range = (x_bit * half_size)..((x_bit + 1) * half_size);
+        let y_range = (y_bit * half_size)..((y_bit + 1) * half_size);
+
+        // Each side of the child is either part of the parent's boundary or
+        // part of the seams through the middle of the parent.
+        let (left, right) = if x_bit == 0 {
+            (
+                self.grid.get_vertical_seam(0, Direction::Left),
+                self.grid.get_vertical_seam(half_size, Direction::Left)
+            )
+        } else {
+            (
+                self.grid.get_vertical_seam(half_size, Direction::Left),
+                self.grid.get_vertical_seam(grid_size - 1, Direction::Right)
+            )
+        };
+
+        let (bottom, top) = if y_bit == 0 {
+            (
+                self.grid.get_horizontal_seam(0, Direction::Down),
+                self.grid.get_horizontal_seam(half_size, Direction::Down)
+            )
+        } else {
+            (
+                self.grid.get_horizontal_seam(half_size, Direction::Down),
+                self.grid.get_horizontal_seam(grid_size - 1, Direction::Up)
+            )
+        };
+
+        let mut child = Self::new(grid_size);
+        child.level = self.level + 1;
+        child.x = self.x << 1 | x_bit;
+        child.y = self.y << 1 | y_bit;
+
+        // Position of the child's bottom left corner in cells, counting
+        // across the whole level
+        let size = grid_size as u64;
+        let cell_x = child.x as u64 * size;
+        let cell_y = child.y as u64 * size;
+        let seed = config.seed;
+        let level = child.level;
+        child.grid.set_boundary(
+            &right[y_range.clone()],
+            &top[x_range.clone()],
+            &left[y_range.clone()],
+            &bottom[x_range.clone()],
+            |side, index| {
+                let (vertical, line, start) = match side {
+                    Direction::Right => (true, cell_x + size, cell_y),
+                    Direction::Left => (true, cell_x, cell_y),
+                    Direction::Up => (false, cell_y + size, cell_x),
+                    Direction::Down => (false, cell_y, cell_x),
+                };
+                // Each parent connection covers 2 cells of the child
+                split_bit(seed, level, vertical, line, start / 2 + index as u64)
+            }
         );
-        self.grid.propagate_interior(
-            &mut ne.grid,
-            HALF_GRID_SIZE..GRID_SIZE,
-            HALF_GRID_SIZE..GRID_SIZE
+        self.grid.propagate_interior(&mut child.grid, x_range, y_range);
+
+        // Each child gets its own RNG so the result doesn't depend on
+        // the order the tiles are generated in.
+        let mut rng = make_tile_rng(
+            config.seed,
+            child.level,
+            child.x,
+            child.y
         );
+        generators.get_mut(child.level).maze_fill(&mut child.grid, &mut rng);
+        solver.solve_all_paths(&mut child.grid);
+
+        child
+    }
+
+    /// Write the tile's content into tiles_dir. Returns how many triangles
+    /// it has, for the tile metadata.
+    pub fn write_files(&self, tiles_dir: &Path, format: &TileFormat) -> Result<usize> {
+        let content = self.make_files(format)?;
+        for (name, bytes) in content.files {
+            let path = tiles_dir.join(name);
+            write(&path, bytes)
+                .map_err(MazeError::io("could not write tile content", &path))?;
+        }
+
+        Ok(content.triangle_count)
+    }
+
+    /// The files of the tile's content, named relative to the tiles
+    /// directory. The files shared by every tile come from
+    /// `make_shared_files`.
+    pub fn make_files(&self, format: &TileFormat) -> Result<TileContent> {
+        let content_name = self.get_file_name(format.get_content_extension());
+
+        if format.content != ContentLayout::Gltf {
+            let (geometry, binary_buffer) = self.make_glb_geometry(format);
+            let triangle_count = geometry.triangle_count;
+            let glb = self.finish_glb(format, geometry, binary_buffer)?;
+            return Ok(TileContent { files: vec![(content_name, glb)], triangle_count });
+        }
+
+        let mut content = self.make_gltf_content(format)?;
+        let image = format.encode_image(&self.grid)?;
+        let image_name = self.get_file_name(format.get_image_extension());
+        content.files.insert(1, (image_name, image));
+        Ok(content)
+    }
+
+    /// Make just one of the files from `make_files`, for when only one of
+    /// them was asked for. Returns None if the tile has no file by that name.
+    pub fn make_file(&self, format: &TileFormat, file_name: &str) -> Result<Option<Vec<u8>>> {
+        if file_name == self.get_file_name(format.get_content_extension()) {
+            return match format.content {
+                ContentLayout::Gltf => self.make_gltf_content(format)
+                    .map(|content| content.files.into_iter().next().map(|(_, gltf)| gltf)),
+                _ => self.make_glb(format).map(Some)
+            };
+        }
+
+        if format.content != ContentLayout::Gltf {
+            return Ok(None);
+        }
+
+        if file_name == self.get_file_name(format.get_image_extension()) {
+            return format.encode_image(&self.grid).map(Some);
+        }
+
+        match &format.geometry {
+            TileGeometry::Walls(style) if file_name == self.get_file_name("bin") => {
+                let mut buffer = Vec::new();
+                self.make_wall_geometry(style, &mut buffer, 1);
+                Ok(Some(buffer))
+            },
+            _ => Ok(None)
+        }
+    }
+
+    /// Name of one of the tile's files, e.g. `3.2.5.glb`
+    fn get_file_name(&self, extension: &str) -> String {
+        format!("{}.{}.{}.{}", self.level, self.x, self.y, extension)
+    }
+
+    /// The `.gltf` of a tile, followed by the buffer file for its geometry
+    /// if it isn't shared. Everything but the image, in other words. The
+    /// glTF has to know how big that buffer is, so the walls are built even
+    /// if only the `.gltf` is wanted.
+    fn make_gltf_content(&self, format: &TileFormat) -> Result<TileContent> {
+        // The walls are different for every tile, so they get their own
+        // buffer
+        let (geometry, geometry_file) = match &format.geometry {
+            TileGeometry::Quad => (Self::make_quad_geometry(1), None),
+            TileGeometry::Walls(style) => {
+                let mut buffer = Vec::new();
+                let geometry = self.make_wall_geometry(style, &mut buffer, 1);
+                (geometry, Some((self.get_file_name("bin"), buffer)))
+            }
+        };
+        let geometry_buffer = match &geometry_file {
+            Some((uri, buffer)) => Self::make_external_buffer("Geometry", uri, buffer.len()),
+            None => Self::make_external_buffer("Shared Geometry", GEOMETRY_URI, get_buffer_size())
+        };
+
+        let triangle_count = geometry.triangle_count;
+        let image_name = self.get_file_name(format.get_image_extension());
+        let gltf = self.make_gltf(format, geometry, geometry_buffer, &image_name)?;
+        let mut files = vec![(self.get_file_name(format.get_content_extension()), gltf)];
+        files.extend(geometry_file);
+        Ok(TileContent { files, triangle_count })
+    }
+
+    /// Files that every tile refers to, named relative to the tiles
+    /// directory
+    pub fn make_shared_files(format: &TileFormat) -> Vec<(String, Vec<u8>)> {
+        let mut files = Vec::new();
+        if format.shares_geometry() {
+            files.push((GEOMETRY_URI.to_string(), make_buffer()));
+        }
+        if format.content == ContentLayout::Gltf {
+            let mut properties = Vec::new();
+            append_property_table(&mut properties, 0, format);
+            files.push((PROPERTIES_URI.to_string(), properties));
+        }
+
+        files
+    }
+
+    /// Read a tile back from a file written by `write_files`, either a GLB
+    /// or a `.gltf` with its image next to it
+    pub fn read(path: &Path) -> Result<Self> {
+        let tile = if path.extension().is_some_and(|extension| extension == "gltf") {
+            Self::read_gltf(path)
+        } else {
+            read(path)
+                .map_err(MazeError::io("could not read GLB", path))
+                .and_then(|glb| Self::from_glb(&glb))
+        };
+
+        tile.map_err(|error| match error {
+            MazeError::InvalidData(message) => MazeError::InvalidData(
+                format!("{}: {}", path.display(), message)
+            ),
+            error => error
+        })
+    }
 
-        let mut result = [sw, se, nw, ne];
+    fn read_gltf(path: &Path) -> Result<Self> {
+        let json: serde_json::Value = serde_json::from_slice(
+            &read(path).map_err(MazeError::io("could not read glTF", path))?
+        )?;
 
-        for i in 0..4 {
-            maze_gen.maze_fill(&mut result[i].grid);
-            solver.solve_all_paths(&mut result[i].grid);
+        Self::from_gltf_json(&json, |texture| {
+            let image_json = &json["images"][get_texture_source(&json, texture)?];
+            let (Some(uri), Some(mime_type)) =
+                (image_json["uri"].as_str(), image_json["mimeType"].as_str()) else {
+                return Err(MazeError::InvalidData(
+                    "the feature ID image needs a uri and mimeType".to_string()
+                ));
+            };
+
+            let image_path = path.with_file_name(uri);
+            let bytes = read(&image_path)
+                .map_err(MazeError::io("could not read feature ID image", &image_path))?;
+            decode_image(mime_type, &bytes)
+        })
+    }
+
+    /// Decode a GLB made by `make_glb`. The grid comes from the feature ID
+    /// texture, and the tile's level and coordinates come from the node's
+    /// transform. Which connections were blocked isn't stored, so those are
+    /// lost.
+    pub fn from_glb(glb_bytes: &[u8]) -> Result<Self> {
+        let glb = Glb::parse(glb_bytes)?;
+        Self::from_gltf_json(&glb.json, |texture| {
+            let (image_bytes, mime_type) = glb.get_texture_image(texture)?;
+            decode_image(mime_type, image_bytes)
+        })
+    }
+
+    /// Decode a tile from its glTF JSON. read_texture decodes the feature
+    /// ID texture with the given index, wherever it's stored.
+    fn from_gltf_json(
+        json: &serde_json::Value,
+        read_texture: impl FnOnce(usize) -> Result<RgbaImage>
+    ) -> Result<Self> {
+        // The connection and solution bits are both stored in the texture
+        // of the first feature ID, in the channels it declares
+        let feature_ids = &json["meshes"][0]["primitives"][0]["extensions"]
+            ["EXT_mesh_features"]["featureIds"];
+        let texture = feature_ids[0]["texture"]["index"]
+            .as_u64()
+            .ok_or_else(|| MazeError::InvalidData(
+                "no feature ID texture".to_string()
+            ))?;
+        let get_channel = |feature_id: &serde_json::Value| {
+            feature_id["texture"]["channels"][0]
+                .as_u64()
+                .map(|channel| channel as usize)
+                .filter(|&channel| channel < 4)
+        };
+        // Packed textures have a single feature ID with the solution bits
+        // in the high 4 bits
+        let (connections_channel, solution_channel, solution_shift) =
+            match feature_ids.as_array().map(Vec::len) {
+                Some(1) => {
+                    let channel = get_channel(&feature_ids[0]);
+                    (channel, channel, 4)
+                },
+                _ => (get_channel(&feature_ids[0]), get_channel(&feature_ids[1]), 0)
+            };
+        let (Some(connections_channel), Some(solution_channel)) =
+            (connections_channel, solution_channel) else {
+            return Err(MazeError::InvalidData(
+                "feature IDs don't declare valid texture channels".to_string()
+            ));
+        };
+
+        let image = read_texture(texture as usize)?;
+
+        let size = image.width() as usize;
+        if image.height() as usize != size || !size.is_power_of_two() {
+            return Err(MazeError::InvalidData(format!(
+                "the feature ID texture is {}x{}, expected a square power of 2",
+                image.width(),
+                image.height()
+            )));
         }
 
-        result
+        let matrix: Vec<f64> = json["nodes"][0]["matrix"]
+            .as_array()
+            .map(|values| values.iter().filter_map(|value| value.as_f64()).collect())
+            .unwrap_or_default();
+        let (level, x, y) = Self::find_coords(&matrix).ok_or_else(|| {
+            MazeError::InvalidData(format!(
+                "{:?} is not the transform of any tile",
+                matrix
+            ))
+        })?;
+
+        let image_bytes: Vec<u8> = image.pixels()
+            .flat_map(|pixel| {
+                let mut rgb = [0; 3];
+                rgb[CONNECTIONS_CHANNEL] = pixel[connections_channel] & 0x0f;
+                rgb[SOLUTION_CHANNEL] = pixel[solution_channel] >> solution_shift & 0x0f;
+                rgb
+            })
+            .collect();
+        let grid = Grid::from_image_bytes(size, &image_bytes);
+        Ok(Self::from_grid(level, x, y, grid))
     }
 
-    pub fn write_glb(&self, tiles_dir: &Path) {
-        let glb_path = tiles_dir.join(self.make_filename());
+    /// Encode the tile as a GLB file in memory. The quad is in the shared
+    /// geometry buffer unless the format is `ContentLayout::Embedded`,
+    /// anything else goes in the BIN chunk.
+    pub fn make_glb(&self, format: &TileFormat) -> Result<Vec<u8>> {
+        let (geometry, binary_buffer) = self.make_glb_geometry(format);
+        self.finish_glb(format, geometry, binary_buffer)
+    }
 
-        let image_buffer = self.make_image_buffer();
-        let image_length = image_buffer.len() as u32;
-        let binary_padding_length = Self::get_padding_length(image_length);
-        let binary_chunk_length = image_length + binary_padding_length;
+    /// The geometry of a GLB, along with the start of its BIN chunk
+    fn make_glb_geometry(&self, format: &TileFormat) -> (GltfGeometry, Vec<u8>) {
+        let mut binary_buffer = Vec::new();
+        let geometry = match &format.geometry {
+            TileGeometry::Quad if format.shares_geometry() => Self::make_quad_geometry(1),
+            TileGeometry::Quad => {
+                binary_buffer = make_buffer();
+                Self::make_quad_geometry(0)
+            },
+            TileGeometry::Walls(style) =>
+                self.make_wall_geometry(style, &mut binary_buffer, 0),
+        };
+        (geometry, binary_buffer)
+    }
+
+    /// Add the image and property table to the BIN chunk and put the GLB
+    /// together
+    fn finish_glb(
+        &self,
+        format: &TileFormat,
+        geometry: GltfGeometry,
+        mut binary_buffer: Vec<u8>
+    ) -> Result<Vec<u8>> {
+        let image_view_index = geometry.buffer_views.len();
+        let image = format.encode_image(&self.grid)?;
+        let image_view = json!({
+            "name": "Feature ID Texture",
+            "buffer": 0,
+            "byteOffset": binary_buffer.len(),
+            "byteLength": image.len()
+        });
+        binary_buffer.extend(image);
+        // The property table goes after the image, and so do its buffer
+        // views
+        let (metadata, metadata_views) =
+            append_property_table(&mut binary_buffer, image_view_index + 1, format);
+        let binary_length = binary_buffer.len() as u32;
+        let binary_padding_length = Self::get_padding_length(binary_length);
+        let binary_chunk_length = binary_length + binary_padding_length;
         let binary_padding = Self::make_padding(binary_padding_length, b'\0');
         assert!(
-            binary_chunk_length % 4 == 0,
+            binary_chunk_length.is_multiple_of(4),
             "binary chunk not a multiple of 4 bytes"
         );
 
-        let gltf_json = self.make_gltf_json(image_length);
-        let json_string = to_string(&gltf_json)
-            .expect("could not serialize glTF JSON");
+        let mut buffers = vec![json!({
+            "name": "Binary Chunk",
+            "byteLength": binary_length
+        })];
+        if format.shares_geometry() {
+            buffers.push(
+                Self::make_external_buffer("Shared Geometry", GEOMETRY_URI, get_buffer_size())
+            );
+        }
+
+        let mut gltf_json = self.make_gltf_json(format, geometry);
+        gltf_json["images"] = json!([
+            {
+                "name": "Feature ID Texture",
+                "bufferView": image_view_index,
+                "mimeType": format.get_mime_type()
+            }
+        ]);
+        gltf_json["buffers"] = json!(buffers);
+        gltf_json["extensions"] = json!({
+            "EXT_structural_metadata": metadata
+        });
+        if let Some(buffer_views) = gltf_json["bufferViews"].as_array_mut() {
+            buffer_views.push(image_view);
+            buffer_views.extend(metadata_views);
+        }
+        let json_string = to_string(&gltf_json)?;
         let json_bytes = json_string.as_bytes();
         let json_length = json_bytes.len() as u32;
         let json_padding_length = Self::get_padding_length(json_length);
         let json_padding = Self::make_padding(json_padding_length, b' ');
         let json_chunk_length = json_length + json_padding_length;
         assert!(
-            json_chunk_length % 4 == 0,
+            json_chunk_length.is_multiple_of(4),
             "json chunk not a multiple of 4 bytes"
         );
         
@@ -193,29 +551,71 @@ impl Tile {
 
         const GLTF_VERSION: u32 = 2;
 
-        let mut file = File::create(glb_path).expect("Could not create file");
+        let mut glb = Vec::with_capacity(total_length as usize);
         // GLB header
-        file.write_all(b"glTF").expect("Could not write magic");
-        file.write_all(&GLTF_VERSION.to_le_bytes())
-            .expect("Could not write version");
-        file.write_all(&total_length.to_le_bytes())
-            .expect("Could not write glTF length");
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&GLTF_VERSION.to_le_bytes());
+        glb.extend_from_slice(&total_length.to_le_bytes());
         
         // JSON chunk
-        file.write_all(&json_chunk_length.to_le_bytes())
-            .expect("Could not write JSON chunk length");
-        file.write_all(b"JSON").expect("Could not write JSON chunk magic");
-        file.write_all(&json_bytes).expect("Could not write JSON data");
-        file.write_all(&json_padding).expect("Could not write JSON padding");
+        glb.extend_from_slice(&json_chunk_length.to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(json_bytes);
+        glb.extend_from_slice(&json_padding);
 
         // Binary chunk
-        file.write_all(&binary_chunk_length.to_le_bytes())
-            .expect("Could not write BIN chunk length");
-        file.write_all(b"BIN\0").expect("Could not write BIN chunk magic");
-        file.write_all(&image_buffer).expect("Could not write binary buffer");
-        file.write_all(&binary_padding)
-            .expect("Could not write binary padding");
+        glb.extend_from_slice(&binary_chunk_length.to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&binary_buffer);
+        glb.extend_from_slice(&binary_padding);
 
+        Ok(glb)
+    }
+
+    /// Make the `.gltf` JSON for the tile. The image is a separate file
+    /// named image_uri, the geometry is in buffer 1 and the property table
+    /// is in a shared buffer.
+    fn make_gltf(
+        &self,
+        format: &TileFormat,
+        geometry: GltfGeometry,
+        geometry_buffer: serde_json::Value,
+        image_uri: &str
+    ) -> Result<Vec<u8>> {
+        // The property table is the same for every tile, see
+        // make_shared_files
+        let mut properties = Vec::new();
+        let (metadata, metadata_views) =
+            append_property_table(&mut properties, geometry.buffer_views.len(), format);
+
+        let mut gltf_json = self.make_gltf_json(format, geometry);
+        gltf_json["images"] = json!([
+            {
+                "name": "Feature ID Texture",
+                "uri": image_uri,
+                "mimeType": format.get_mime_type()
+            }
+        ]);
+        gltf_json["buffers"] = json!([
+            Self::make_external_buffer("Property Table", PROPERTIES_URI, properties.len()),
+            geometry_buffer
+        ]);
+        gltf_json["extensions"] = json!({
+            "EXT_structural_metadata": metadata
+        });
+        if let Some(buffer_views) = gltf_json["bufferViews"].as_array_mut() {
+            buffer_views.extend(metadata_views);
+        }
+
+        Ok(to_string_pretty(&gltf_json)?.into_bytes())
+    }
+
+    fn make_external_buffer(name: &str, uri: &str, length: usize) -> serde_json::Value {
+        json!({
+            "name": name,
+            "byteLength": length,
+            "uri": uri
+        })
     }
 
     fn get_padding_length(length: u32) -> u32 {
@@ -230,28 +630,9 @@ impl Tile {
         (0..length).map(|_| padding_char).collect()
     }
 
-    fn make_image_buffer(&self) -> Vec<u8> {
-        let mut cursor = Cursor::new(Vec::new());
-
-        image::write_buffer_with_format(
-            &mut cursor,
-            &self.grid.to_image_bytes(),
-            GRID_SIZE as u32, 
-            GRID_SIZE as u32,
-            image::ColorType::Rgb8,
-            image::ImageOutputFormat::Png
-        ).expect("could not serialize image");
-
-        cursor.into_inner()
-    }
-
-    fn make_filename(&self) -> String {
-        format!("{}.{}.{}.glb", self.level, self.x, self.y)
-    }
-
-    fn make_matrix(&self) -> [f64; 16] {
+    fn make_matrix(level: usize, x: usize, y: usize) -> [f64; 16] {
         // 2^level = 1, 2, 4, 8, ...
-        let power_of_two = (1 << self.level) as f64;
+        let power_of_two = 2.0f64.powi(level as i32);
         // 1 / 2^level = 1, 1/2, 1/4, ...
         let inv_power_of_two = 1.0 / power_of_two;
 
@@ -275,8 +656,8 @@ impl Tile {
         let offset_x = -offset_distance;
         let offset_z = offset_distance;
         
-        let dx = (self.x as f64) * tile_width;
-        let dz = -(self.y as f64) * tile_width;
+        let dx = (x as f64) * tile_width;
+        let dz = -(y as f64) * tile_width;
 
         let tx = offset_x + dx;
         let ty = 0.0;
@@ -290,14 +671,96 @@ impl Tile {
         ]
     }
 
-    fn make_gltf_json(&self, image_byte_length: u32) -> serde_json::Value {
-        json!({
+    /// Undo `make_matrix`, i.e. find which tile has this transform
+    fn find_coords(matrix: &[f64]) -> Option<(usize, usize, usize)> {
+        if matrix.len() != 16 {
+            return None;
+        }
+
+        // The scale is 1 / 2^level
+        let scale = matrix[0];
+        if !(scale > 0.0 && scale <= 1.0) {
+            return None;
+        }
+        let level = -scale.log2().round();
+        if level >= usize::BITS as f64 {
+            return None;
+        }
+        let level = level as usize;
+
+        // The translation is (-offset + x * width, 0, offset - y * width)
+        let power_of_two = 2.0f64.powi(level as i32);
+        let offset_distance = (power_of_two - 1.0) / power_of_two;
+        let tile_width = 2.0 / power_of_two;
+        let x = ((matrix[12] + offset_distance) / tile_width).round();
+        let y = ((offset_distance - matrix[14]) / tile_width).round();
+        if x < 0.0 || y < 0.0 || x >= power_of_two || y >= power_of_two {
+            return None;
+        }
+        let (x, y) = (x as usize, y as usize);
+
+        // Make sure the rest of the matrix matches too. Past 50 or so levels
+        // the translations can't be represented exactly, so allow for
+        // rounding error as well as a fraction of the tile's size.
+        let expected = Self::make_matrix(level, x, y);
+        let matches = expected
+            .iter()
+            .zip(matrix)
+            .all(|(a, b)| (a - b).abs() <= 1e-12 + 1e-6 * scale);
+        if matches {
+            Some((level, x, y))
+        } else {
+            None
+        }
+    }
+
+    /// The parts of the glTF JSON that don't depend on where the image and
+    /// property table are stored. The caller fills in the images, the
+    /// buffers and the rest of the buffer views.
+    fn make_gltf_json(&self, format: &TileFormat, geometry: GltfGeometry) -> serde_json::Value {
+        let mut extensions_used = vec!["EXT_mesh_features", "EXT_structural_metadata"];
+        let mut extensions_required = Vec::new();
+        // Images other than PNG need an extension, and since there's no
+        // fallback PNG it's required
+        let texture = match format.get_texture_extension() {
+            Some(extension) => {
+                extensions_used.push(extension);
+                extensions_required.push(extension);
+                json!({
+                    "sampler": 0,
+                    "extensions": {
+                        extension: {
+                            "source": 0
+                        }
+                    }
+                })
+            },
+            None => json!({
+                "sampler": 0,
+                "source": 0
+            })
+        };
+
+        // Every primitive can look up its cell in the feature ID texture
+        let primitives: Vec<serde_json::Value> = geometry.primitives
+            .into_iter()
+            .map(|mut primitive| {
+                primitive["extensions"] = json!({
+                    "EXT_mesh_features": {
+                        "featureIds": make_feature_ids(0, format)
+                    }
+                });
+                primitive
+            })
+            .collect();
+
+        let mut gltf_json = json!({
             "asset": {
                 "version": "2.0",
                 "generator": "Nested mazes generator from https://github.com/ptrgags/nested-mazes",
                 "copyright": format!("© {} Peter Gagliardi", Utc::now().year())
             },
-            "extensionsUsed": ["EXT_mesh_features"],
+            "extensionsUsed": extensions_used,
             "scene": 0,
             "scenes": [
                 {
@@ -310,70 +773,53 @@ impl Tile {
             "nodes": [
                 {
                     "mesh": 0,
-                    "name": "Maze Quad",
-                    "matrix": self.make_matrix()
+                    "name": geometry.name,
+                    "matrix": Self::make_matrix(self.level, self.x, self.y)
                 }
             ],
             "meshes": [
                 {
-                    "name": "Maze Quad",
-                    "primitives": [
-                        {
-                            "attributes": {
-                                "POSITION": 0,
-                                "TEXCOORD_0": 1,
-                                "NORMAL": 2
-                            },
-                            "indices": 3,
-                            "extensions": {
-                                "EXT_mesh_features": {
-                                    "featureIds": [
-                                        {
-                                            "featureCount": 16,
-                                            "label": "connections",
-                                            "texture": {
-                                                "index": 0,
-                                                "texCoord": 0,
-                                                "channels": [0]
-                                            }
-                                        },
-                                        {
-                                            "featureCount": 16,
-                                            "label": "solutions",
-                                            "texture": {
-                                                "index": 0,
-                                                "texCoord": 0,
-                                                "channels": [1]
-                                            }
-                                        }
-                                    ]
-                                }
-                            }
-                        }
-                    ]
-                }
-            ],
-            "textures": [
-                {
-                    "sampler": 0,
-                    "source": 0
+                    "name": geometry.name,
+                    "primitives": primitives
                 }
             ],
+            "textures": [texture],
             "samplers": [
                 {
                     "magFilter": 9728,
                     "minFilter": 9728
                 }
             ],
-            "images": [
-                {
-                    "name": "Feature ID Texture",
-                    "bufferView": 4,
-                    "mimeType": "image/png"
-                }
-            ],
-            "accessors": [
-                {
+            "accessors": geometry.accessors,
+            "bufferViews": geometry.buffer_views
+        });
+
+        if !geometry.materials.is_empty() {
+            gltf_json["materials"] = json!(geometry.materials);
+        }
+        if !extensions_required.is_empty() {
+            gltf_json["extensionsRequired"] = json!(extensions_required);
+        }
+        gltf_json
+    }
+
+    /// The quad from the `geometry` module, stored at the start of
+    /// geometry_buffer
+    fn make_quad_geometry(geometry_buffer: usize) -> GltfGeometry {
+        GltfGeometry {
+            name: "Maze Quad",
+            triangle_count: geometry::get_triangle_count(),
+            primitives: vec![json!({
+                "attributes": {
+                    "POSITION": 0,
+                    "TEXCOORD_0": 1,
+                    "NORMAL": 2
+                },
+                "indices": 3
+            })],
+            materials: Vec::new(),
+            accessors: vec![
+                json!({
                     "name": "Position",
                     "bufferView": 0,
                     "type": "VEC3",
@@ -381,76 +827,209 @@ impl Tile {
                     "count": 4,
                     "max": [1, 0, 1],
                     "min": [-1, 0, -1]
-                },
-                {
+                }),
+                json!({
                     "name": "UVs",
                     "bufferView": 1,
                     "type": "VEC2",
                     "componentType": 5126, // float
                     "count": 4,
-                },
-                {
+                }),
+                json!({
                     "name": "Normals",
                     "bufferView": 2,
                     "type": "VEC3",
                     "componentType": 5126, // float
                     "count": 4,
-                },
-                {
+                }),
+                json!({
                     "name": "Indices",
                     "bufferView": 3,
                     "type": "SCALAR",
                     "componentType": 5121, // unsigned byte
                     "count": 6
-                }
+                })
             ],
-            "bufferViews": [
-                {
+            buffer_views: vec![
+                json!({
                     "name": "Position",
-                    "buffer": 1,
+                    "buffer": geometry_buffer,
                     "byteOffset": 0,
                     "byteLength": 48,
                     "target": 34962 // array buffer
-                },
-                {
+                }),
+                json!({
                     "name": "UVs",
-                    "buffer": 1,
+                    "buffer": geometry_buffer,
                     "byteOffset": 48,
                     "byteLength": 32,
                     "target": 34962 // array buffer
-                },
-                {
+                }),
+                json!({
                     "name": "Normals",
-                    "buffer": 1,
+                    "buffer": geometry_buffer,
                     "byteOffset": 48 + 32,
                     "byteLength": 48,
                     "target": 34962 // array buffer
-                },
-                {
+                }),
+                json!({
                     "name": "Indices",
-                    "buffer": 1,
+                    "buffer": geometry_buffer,
                     "byteOffset": 48 + 32 + 48,
                     "byteLength": 6,
                     "target": 34963 // element array buffer
-                },
-                {
-                    "name": "Feature ID Texture",
-                    "buffer": 0,
-                    "byteOffset": 0,
-                    "byteLength": image_byte_length
-                }
+                })
             ],
-            "buffers": [
-                {
-                    "name": "Binary Chunk",
-                    "byteLength": image_byte_length
-                },
-                {
-                    "name": "Shared Geometry",
-                    "byteLength": get_buffer_size(),
-                    "uri": "geometry.bin"
+        }
+    }
+
+    /// Build the walls and append them to buffer, which is buffer number
+    /// buffer_index. Each mesh from `walls::make_meshes` becomes a
+    /// primitive with its own material.
+    fn make_wall_geometry(
+        &self,
+        style: &WallStyle,
+        buffer: &mut Vec<u8>,
+        buffer_index: usize
+    ) -> GltfGeometry {
+        let mut geometry = GltfGeometry {
+            name: "Maze Walls",
+            triangle_count: 0,
+            primitives: Vec::new(),
+            materials: Vec::new(),
+            accessors: Vec::new(),
+            buffer_views: Vec::new(),
+        };
+
+        for maze_mesh in make_meshes(&self.grid, style) {
+            let mesh = &maze_mesh.mesh;
+            geometry.triangle_count += mesh.get_triangle_count();
+            // The texture is projected straight down onto the tile, the
+            // same way it's mapped onto the quad
+            let uvs: Vec<[f32; 2]> = mesh.positions
+                .iter()
+                .map(|[x, _, z]| [0.5 * (x + 1.0), 0.5 * (1.0 - z)])
+                .collect();
+            let (min, max) = get_bounds(&mesh.positions);
+
+            let mut add_accessor = |name: &str, data: Vec<u8>, mut accessor: serde_json::Value| {
+                let name = format!("{} {}", maze_mesh.name, name);
+                // Everything here is 4 byte floats or integers
+                buffer.resize(buffer.len().next_multiple_of(4), 0);
+                geometry.buffer_views.push(json!({
+                    "name": name,
+                    "buffer": buffer_index,
+                    "byteOffset": buffer.len(),
+                    "byteLength": data.len()
+                }));
+                buffer.extend(data);
+
+                accessor["name"] = json!(name);
+                accessor["bufferView"] = json!(geometry.buffer_views.len() - 1);
+                geometry.accessors.push(accessor);
+                geometry.accessors.len() - 1
+            };
+
+            let count = mesh.positions.len();
+            let position = add_accessor("Position", to_bytes(&mesh.positions), json!({
+                "type": "VEC3",
+                "componentType": 5126, // float
+                "count": count,
+                "min": min,
+                "max": max
+            }));
+            let uv = add_accessor("UVs", to_bytes(&uvs), json!({
+                "type": "VEC2",
+                "componentType": 5126, // float
+                "count": count
+            }));
+            let normal = add_accessor("Normals", to_bytes(&mesh.normals), json!({
+                "type": "VEC3",
+                "componentType": 5126, // float
+                "count": count
+            }));
+            let indices = add_accessor(
+                "Indices",
+                mesh.indices.iter().flat_map(|index| index.to_le_bytes()).collect(),
+                json!({
+                    "type": "SCALAR",
+                    "componentType": 5125, // unsigned int
+                    "count": mesh.indices.len()
+                })
+            );
+
+            geometry.primitives.push(json!({
+                "attributes": {
+                    "POSITION": position,
+                    "TEXCOORD_0": uv,
+                    "NORMAL": normal
                 },
-            ]
-        })
+                "indices": indices,
+                "material": geometry.materials.len()
+            }));
+            geometry.materials.push(json!({
+                "name": maze_mesh.name,
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": to_linear_color(maze_mesh.color),
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 1.0
+                }
+            }));
+        }
+
+        geometry
     }
-}
\ No newline at end of file
+}
+
+/// The files of a tile's content, from `Tile::make_files`
+pub struct TileContent {
+    /// File names relative to the tiles directory, and their contents
+    pub files: Vec<(String, Vec<u8>)>,
+    /// How many triangles the content has, for the tile metadata
+    pub triangle_count: usize,
+}
+
+/// The parts of a tile's glTF that describe its geometry
+struct GltfGeometry {
+    name: &'static str,
+    triangle_count: usize,
+    /// Primitives without their feature IDs, those are added by
+    /// `Tile::make_gltf_json`
+    primitives: Vec<serde_json::Value>,
+    materials: Vec<serde_json::Value>,
+    accessors: Vec<serde_json::Value>,
+    buffer_views: Vec<serde_json::Value>,
+}
+
+fn to_bytes<const N: usize>(values: &[[f32; N]]) -> Vec<u8> {
+    values.iter()
+        .flatten()
+        .flat_map(|component| component.to_le_bytes())
+        .collect()
+}
+
+/// The smallest and largest value of each component, which glTF needs for
+/// positions
+fn get_bounds(positions: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    positions.iter().fold(
+        ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+        |(min, max), position| (
+            [0, 1, 2].map(|i| min[i].min(position[i])),
+            [0, 1, 2].map(|i| max[i].max(position[i]))
+        )
+    )
+}
+
+/// glTF colors are linear, but colors are usually written in sRGB
+fn to_linear_color(color: Rgba<u8>) -> [f32; 4] {
+    let to_linear = |value: u8| {
+        let value = value as f32 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let [red, green, blue, alpha] = color.0;
+    [to_linear(red), to_linear(green), to_linear(blue), alpha as f32 / 255.0]
+}
@@ -0,0 +1,192 @@
+//! How the feature ID texture of each tile is encoded. The default is an
+//! RGB PNG with the connection bits in red and the solution bits in green,
+//! but the blue channel is always empty and both sets of bits only need 4
+//! bits each, so there are smaller options too.
+
+use std::io::Cursor;
+
+use image::{ColorType, ImageOutputFormat, Rgba, RgbaImage};
+
+use crate::error::{MazeError, Result};
+use crate::grid::{Grid, CONNECTIONS_CHANNEL, SOLUTION_CHANNEL};
+use crate::walls::WallStyle;
+use crate::webp;
+
+/// Which channels of the feature ID texture hold what
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChannelLayout {
+    /// Connection bits in red, solution bits in green, blue unused
+    #[default]
+    Rgb8,
+    /// A single channel, with the connection bits in the low 4 bits and
+    /// the solution bits in the high 4 bits
+    R8Packed,
+}
+
+/// The image format of the feature ID texture
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageContainer {
+    #[default]
+    Png,
+    /// Lossless WebP, using `EXT_texture_webp`
+    WebP,
+}
+
+/// How the glTF of each tile is split into files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContentLayout {
+    /// A GLB per tile. The quad is the same for every tile, so it's in a
+    /// `geometry.bin` they all share
+    #[default]
+    SharedGeometry,
+    /// A self-contained GLB per tile with the quad in its own BIN chunk, so
+    /// a tile can be copied or served on its own
+    Embedded,
+    /// A `.gltf` per tile with its feature ID texture as a separate image.
+    /// Both the quad and the property table are in shared buffers.
+    Gltf,
+}
+
+/// The geometry of each tile
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TileGeometry {
+    /// A flat quad, leaving the rest to a custom shader
+    #[default]
+    Quad,
+    /// Extruded walls (and optionally a floor and the solution), which
+    /// any glTF viewer can show. These are different for every tile, so
+    /// they are never in a shared buffer.
+    Walls(WallStyle),
+}
+
+/// How to write each tile
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TileFormat {
+    pub layout: ChannelLayout,
+    pub container: ImageContainer,
+    pub content: ContentLayout,
+    pub geometry: TileGeometry,
+}
+
+impl TileFormat {
+    /// Channels of the texture (as a viewer sees it after decoding) with the
+    /// connection and solution bits.
+    pub fn get_channels(&self) -> (usize, usize) {
+        match self.layout {
+            ChannelLayout::Rgb8 => (CONNECTIONS_CHANNEL, SOLUTION_CHANNEL),
+            ChannelLayout::R8Packed => (0, 0),
+        }
+    }
+
+    /// How far the solution bits are shifted within their channel
+    pub fn get_solution_shift(&self) -> u8 {
+        match self.layout {
+            ChannelLayout::R8Packed => 4,
+            _ => 0,
+        }
+    }
+
+    pub fn get_mime_type(&self) -> &'static str {
+        match self.container {
+            ImageContainer::Png => "image/png",
+            ImageContainer::WebP => "image/webp",
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match &self.geometry {
+            TileGeometry::Quad => Ok(()),
+            TileGeometry::Walls(style) => style.validate(),
+        }
+    }
+
+    /// Whether the tiles refer to the quad in the shared geometry buffer
+    pub fn shares_geometry(&self) -> bool {
+        self.geometry == TileGeometry::Quad && self.content != ContentLayout::Embedded
+    }
+
+    /// File extension of the feature ID images, when they are separate
+    /// files
+    pub fn get_image_extension(&self) -> &'static str {
+        match self.container {
+            ImageContainer::Png => "png",
+            ImageContainer::WebP => "webp",
+        }
+    }
+
+    /// File extension of the tile contents
+    pub fn get_content_extension(&self) -> &'static str {
+        match self.content {
+            ContentLayout::SharedGeometry | ContentLayout::Embedded => "glb",
+            ContentLayout::Gltf => "gltf",
+        }
+    }
+
+    /// The glTF extension that lets a texture use the image, if it isn't
+    /// a PNG
+    pub fn get_texture_extension(&self) -> Option<&'static str> {
+        match self.container {
+            ImageContainer::Png => None,
+            ImageContainer::WebP => Some("EXT_texture_webp"),
+        }
+    }
+
+    /// Encode the grid's connection and solution bits as an image
+    pub fn encode_image(&self, grid: &Grid) -> Result<Vec<u8>> {
+        let size = grid.get_size() as u32;
+        let (channels, pixels) = self.pack_channels(grid);
+
+        match self.container {
+            ImageContainer::Png => {
+                let color_type = match channels {
+                    1 => ColorType::L8,
+                    _ => ColorType::Rgb8,
+                };
+                let mut cursor = Cursor::new(Vec::new());
+                image::write_buffer_with_format(
+                    &mut cursor,
+                    &pixels,
+                    size,
+                    size,
+                    color_type,
+                    ImageOutputFormat::Png
+                )?;
+                Ok(cursor.into_inner())
+            },
+            ImageContainer::WebP => {
+                // WebP is always RGBA
+                let image = RgbaImage::from_fn(size, size, |x, y| {
+                    let start = (y * size + x) as usize * channels;
+                    let mut pixel = [0, 0, 0, 255];
+                    pixel[..channels].copy_from_slice(&pixels[start..start + channels]);
+                    Rgba(pixel)
+                });
+                webp::encode_lossless(&image)
+            },
+        }
+    }
+
+    /// Rearrange the bytes from `Grid::to_image_bytes` into this layout,
+    /// returning the number of channels and the pixels
+    fn pack_channels(&self, grid: &Grid) -> (usize, Vec<u8>) {
+        let rgb = grid.to_image_bytes();
+
+        match self.layout {
+            ChannelLayout::Rgb8 => (3, rgb),
+            ChannelLayout::R8Packed => (1, rgb.chunks(3)
+                .map(|pixel| pixel[CONNECTIONS_CHANNEL] | pixel[SOLUTION_CHANNEL] << 4)
+                .collect()),
+        }
+    }
+}
+
+/// Decode a feature ID texture into RGBA, the way a viewer would see it
+pub fn decode_image(mime_type: &str, bytes: &[u8]) -> Result<RgbaImage> {
+    match mime_type {
+        "image/png" | "image/webp" => Ok(image::load_from_memory(bytes)?.to_rgba8()),
+        _ => Err(MazeError::InvalidData(format!(
+            "unsupported feature ID texture type {}",
+            mime_type
+        )))
+    }
+}
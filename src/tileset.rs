@@ -1,144 +1,665 @@
-use std::fs::{create_dir_all, copy, remove_dir_all, write};
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{create_dir_all, read, read_to_string, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
 
-use serde_json::{json, to_string_pretty};
+use serde_json::{from_slice, from_str, json, to_string_pretty, Value};
 
-use crate::dfs::{DFSMaze, DFSSolutionFinder};
+use crate::atlas::{AtlasLayout, AtlasSource, ATLAS_TILES};
+use crate::config::MazeConfig;
+use crate::dfs::DFSSolutionFinder;
+use crate::direction::Direction;
+use crate::error::{MazeError, Result};
+use crate::glb::Glb;
+use crate::grid::BIT_ORDER;
+use crate::maze_generator::Algorithm;
+use crate::subtree::{
+    fill_uri_template,
+    get_algorithm_value,
+    SubtreeFormat,
+    SubtreeLayout,
+    TileKey,
+    TileStats
+};
 use crate::tile::Tile;
-use crate::geometry::make_buffer;
+use crate::tile_format::TileFormat;
+
+/// How many cells across the deepest level of a tileset on disk can be for
+/// it to be checked. `verify_tileset` holds a whole level in memory and
+/// checks it as one maze, so this keeps that to about 4 million cells, or 8
+/// levels of a 16x16 grid. It also keeps a bad tileset.json from sending
+/// `find_missing_files` off on an endless walk.
+pub const MAX_CHECKED_SIZE: usize = 2048;
+
+/// The most levels a tileset with the given grid size can be checked with,
+/// see `MAX_CHECKED_SIZE`
+pub fn get_max_checked_levels(grid_size: usize) -> usize {
+    match MAX_CHECKED_SIZE.checked_div(grid_size) {
+        Some(tiles) if tiles > 0 => 1 + tiles.ilog2() as usize,
+        _ => 0
+    }
+}
+
+/// Static files that get copied into every tileset
+#[derive(Default)]
+pub struct TilesetAssets {
+    pub atlases: AtlasSource,
+}
 
 pub struct MazeTileset {
-    output_directory: String,
-    levels: usize
+    output_directory: PathBuf,
+    levels: usize,
+    config: MazeConfig,
+    assets: TilesetAssets,
+    subtrees: SubtreeLayout,
+    format: TileFormat,
+    threads: usize,
 }
 
 impl MazeTileset {
-    pub fn new(output_directory: &str, levels: usize) -> Self {
-        Self {
-            output_directory: output_directory.to_string(), 
-            levels
+    pub fn new(
+        output_directory: &Path,
+        levels: usize,
+        config: MazeConfig,
+        assets: TilesetAssets,
+        subtrees: SubtreeLayout,
+        format: TileFormat,
+        threads: usize
+    ) -> Result<Self> {
+        if levels == 0 {
+            return Err(MazeError::InvalidInput(
+                "the tileset must have at least 1 level".to_string()
+            ));
         }
+
+        if levels > config.max_levels() {
+            return Err(MazeError::InvalidInput(format!(
+                "at most {} levels are supported with a grid size of {}",
+                config.max_levels(),
+                config.grid_size
+            )));
+        }
+
+        format.validate()?;
+
+        if threads == 0 {
+            return Err(MazeError::InvalidInput(
+                "at least 1 thread is needed to generate tiles".to_string()
+            ));
+        }
+
+        Ok(Self {
+            output_directory: output_directory.to_path_buf(), 
+            levels,
+            config,
+            assets,
+            subtrees: subtrees.for_tileset(levels),
+            format,
+            threads
+        })
     }
 
-    pub fn generate(&self) {
-        self.init_directory();
+    pub fn generate(&self) -> Result<()> {
+        self.init_directory()?;
         
-        self.generate_common_files();
-        self.generate_maze();
+        self.generate_common_files()?;
+        self.generate_maze()
+    }
+
+    fn init_directory(&self) -> Result<()> {
+        if self.output_directory.exists() {
+            remove_dir_all(&self.output_directory).map_err(MazeError::io(
+                "could not remove output directory",
+                &self.output_directory
+            ))?;
+        }
+
+        let tiles_dir = self.output_directory.join("tiles");
+        create_dir_all(&tiles_dir)
+            .map_err(MazeError::io("could not create tiles directory", &tiles_dir))
     }
 
-    fn init_directory(&self) {
-        remove_dir_all(&self.output_directory)
-            .expect("could not remove output directory");
+    fn generate_common_files(&self) -> Result<()> {
+        let atlases = self.assets.atlases.load()?;
+        self.generate_tileset_json(&atlases.layout)?;
+
+        let tileset_walls = self.output_directory.join("tileset_walls.png");
+        write(&tileset_walls, atlases.walls_png)
+            .map_err(MazeError::io("could not write walls atlas", &tileset_walls))?;
+        let tileset_connections = self.output_directory
+            .join("tileset_connections.png");
+        write(&tileset_connections, atlases.connections_png).map_err(
+            MazeError::io("could not write connections atlas", &tileset_connections)
+        )?;
+
+        for (name, bytes) in Tile::make_shared_files(&self.format) {
+            let path = self.output_directory.join("tiles").join(name);
+            write(&path, bytes)
+                .map_err(MazeError::io("could not write shared buffer", &path))?;
+        }
+
+        Ok(())
+    }
 
-        let tiles_dir = Path::new(&self.output_directory).join("tiles");
-        create_dir_all(tiles_dir)
-            .expect("could not create tiles directory");
+    fn generate_tileset_json(&self, layout: &AtlasLayout) -> Result<()> {
+        let tileset_json = make_tileset_json(
+            &self.config,
+            self.levels,
+            layout,
+            &self.subtrees,
+            &self.format
+        );
+        let tileset_path = self.output_directory.join("tileset.json");
+        let json_string = to_string_pretty(&tileset_json)?;
+        write(&tileset_path, json_string)
+            .map_err(MazeError::io("could not write tileset JSON", &tileset_path))
     }
 
-    fn generate_common_files(&self) {
-        self.generate_tileset_json();
+    fn generate_maze(&self) -> Result<()> {
+        let mut generators = self.config.algorithms.make_generators();
+        let mut solver = DFSSolutionFinder::new();
+        let root = Tile::make_root(&self.config, &mut generators, &mut solver);
+        let queue = WorkQueue::new(root);
+        let stats = Mutex::new(Vec::new());
 
-        let subtree_file = Path::new(&self.output_directory)
-            .join("0.0.0.subtree.json");
-        copy("assets/subtree.json", subtree_file)
-            .expect("could not copy subtree file");
+        // Each tile only depends on its parent, so the tiles can be generated
+        // by a pool of workers pulling from a shared queue. Since every tile
+        // has its own RNG, the output is the same no matter which worker
+        // generates which tile.
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| self.run_worker(&queue, &stats));
+            }
+        });
+        queue.into_result()?;
 
-        let tileset_walls = Path::new(&self.output_directory)
-            .join("tileset_walls.png");
-        copy("assets/walls-test.png", tileset_walls)
-            .expect("could not copy tileset");
-        let tileset_connections = Path::new(&self.output_directory)
-            .join("tileset_connections.png");
-        copy("assets/connections-test.png", tileset_connections)
-            .expect("could not copy connections tileset");
+        // The workers finish tiles in any order, so group the stats by
+        // subtree and sort them into the order of the subtree's rows
+        let mut subtrees: BTreeMap<TileKey, Vec<(usize, TileStats)>> = BTreeMap::new();
+        let stats = stats.into_inner().expect("tile stats were poisoned");
+        for (key, tile_stats) in stats {
+            subtrees.entry(self.subtrees.get_root(key))
+                .or_default()
+                .push((self.subtrees.get_row(key), tile_stats));
+        }
+
+        for (root, mut rows) in subtrees {
+            rows.sort_by_key(|(row, _)| *row);
+            let rows: Vec<TileStats> = rows.into_iter()
+                .map(|(_, stats)| stats)
+                .collect();
+
+            for (name, bytes) in self.subtrees.make_files(root, self.levels, Some(&rows)) {
+                let path = self.output_directory.join(name);
+                write(&path, bytes)
+                    .map_err(MazeError::io("could not write subtree file", &path))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_worker(&self, queue: &WorkQueue, stats: &Mutex<Vec<(TileKey, TileStats)>>) {
+        let mut generators = self.config.algorithms.make_generators();
+        let mut solver = DFSSolutionFinder::new();
+        let tiles_dir = self.output_directory.join("tiles");
+
+        while let Some((tile, in_progress)) = queue.pop() {
+            let triangle_count = match tile.write_files(&tiles_dir, &self.format) {
+                Ok(triangle_count) => triangle_count,
+                Err(error) => {
+                    in_progress.fail(error);
+                    return;
+                }
+            };
+
+            let tile_stats = TileStats::new(&self.config, &tile, triangle_count);
+            stats.lock()
+                .expect("tile stats were poisoned")
+                .push(((tile.level, tile.x, tile.y), tile_stats));
+
+            let children = if tile.level < self.levels - 1 {
+                tile.subdivide(&self.config, &mut generators, &mut solver)
+                    .into_iter()
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            in_progress.finish(children);
+        }
+    }
+}
+
+struct QueueState {
+    /// Tiles waiting to be written and subdivided. This is used as a stack
+    /// so the traversal is (roughly) depth-first. That way only a few
+    /// tiles per level are in memory at once rather than an entire level.
+    tiles: Vec<Tile>,
+    /// How many tiles have been popped but not finished yet. Workers must
+    /// not give up while this is nonzero since more tiles may be coming.
+    in_progress: usize,
+    /// The first error any worker encountered, if any. This stops all the
+    /// workers.
+    error: Option<MazeError>,
+    /// Whether a worker panicked. `thread::scope` passes the panic on, so
+    /// this only needs to stop the other workers.
+    panicked: bool,
+}
+
+/// A shared stack of tiles for the worker threads.
+struct WorkQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+impl WorkQueue {
+    fn new(root: Tile) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                tiles: vec![root],
+                in_progress: 0,
+                error: None,
+                panicked: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// The state is never left half-updated, so it's still usable if a
+    /// worker panicked while holding the lock. Carrying on matters since
+    /// `InProgress` locks the queue while unwinding.
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wait for the next tile to process. This returns None once all the
+    /// work is done or a worker failed.
+    fn pop(&self) -> Option<(Tile, InProgress<'_>)> {
+        let mut state = self.lock();
+        loop {
+            if state.error.is_some() || state.panicked {
+                return None;
+            }
+
+            if let Some(tile) = state.tiles.pop() {
+                state.in_progress += 1;
+                return Some((tile, InProgress { queue: self }));
+            }
+
+            if state.in_progress == 0 {
+                return None;
+            }
+
+            state = self.changed.wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    fn into_result(self) -> Result<()> {
+        let state = self.state.into_inner().unwrap_or_else(PoisonError::into_inner);
+        match state.error {
+            Some(error) => Err(error),
+            None => Ok(())
+        }
+    }
+}
+
+/// A tile that a worker popped from the queue. The tile stops counting as
+/// in progress when this is dropped, even if the worker panics partway
+/// through. Otherwise the other workers would wait forever for children
+/// that are never coming.
+struct InProgress<'a> {
+    queue: &'a WorkQueue,
+}
+
+impl InProgress<'_> {
+    /// Mark the tile as finished, queueing up its children (if any).
+    fn finish(self, children: Vec<Tile>) {
+        // Since we're using a stack, push the tiles
+        // backwards so the DFS is more like Morton order.
+        self.queue.lock().tiles.extend(children.into_iter().rev());
+    }
 
-        let geometry_data = make_buffer();
-        let geometry_path = Path::new(&self.output_directory)
-            .join("tiles/geometry.bin");
-        write(geometry_path, geometry_data)
-            .expect("could not write geometry buffer");
+    fn fail(self, error: MazeError) {
+        let mut state = self.queue.lock();
+        if state.error.is_none() {
+            state.error = Some(error);
+        }
     }
+}
 
-    fn generate_tileset_json(&self) {
-        let tileset_json = json!({
-            "asset": {
-                "version": "1.1",
+impl Drop for InProgress<'_> {
+    fn drop(&mut self) {
+        let mut state = self.queue.lock();
+        state.in_progress -= 1;
+        if thread::panicking() {
+            state.panicked = true;
+        }
+        self.queue.changed.notify_all();
+    }
+}
+
+/// Make the tileset JSON for an implicit quadtree with the given number of
+/// available levels, split into subtrees as described by subtrees. The
+/// tileset metadata describes everything a viewer needs to render the
+/// tiles, so nothing has to be hard-coded in the shader.
+pub fn make_tileset_json(
+    config: &MazeConfig,
+    levels: usize,
+    atlas_layout: &AtlasLayout,
+    subtrees: &SubtreeLayout,
+    format: &TileFormat
+) -> Value {
+    let (connections_channel, solution_channel) = format.get_channels();
+    json!({
+        "asset": {
+            "version": "1.1",
+        },
+        "geometricError": 2.0f64.powi(levels as i32 + 1),
+        "schema": make_schema(),
+        "metadata": {
+            "class": "tileset",
+            "properties": {
+                "wall_tileset_uri": "tileset_walls.png",
+                "connection_tileset_uri": "tileset_connections.png",
+                "atlas_tile_count": ATLAS_TILES,
+                "atlas_tile_size": atlas_layout.tile_size,
+                "atlas_padding": atlas_layout.padding,
+                "grid_size": config.grid_size,
+                "connections_channel": connections_channel,
+                "solution_channel": solution_channel,
+                "solution_shift": format.get_solution_shift(),
+                "bit_directions": BIT_ORDER.map(get_direction_name),
+                // u64 seeds don't fit in a JavaScript number, so this is
+                // a string
+                "seed": config.seed.to_string(),
+                "algorithm": config.algorithms.to_string(),
+                "level_count": levels,
+                "generator": env!("CARGO_PKG_NAME"),
+                "generator_version": env!("CARGO_PKG_VERSION")
+            }
+        },
+        "refine": "REPLACE",
+        "root": {
+            "boundingVolume": {
+                "box": [
+                    0, 0, 0,
+                    1, 0, 0,
+                    0, 1, 0,
+                    0, 0, 1
+                ]
             },
-            "geometricError": 1 << (self.levels + 1),
-            "schema": {
-                "classes": {
-                    "tileset": {
-                        "properties": {
-                            "wall_tileset_uri": {
-                                "type": "STRING"
-                            },
-                            "wall_tileset_uri": {
-                                "type": "STRING"
-                            }
-                        }
-                    }
+            "geometricError": 2.0f64.powi(levels as i32),
+            "content": {
+                "uri": format!("tiles/{{level}}.{{x}}.{{y}}.{}", format.get_content_extension())
+            },
+            "implicitTiling": {
+                "subdivisionScheme": "QUADTREE",
+                "availableLevels": levels,
+                "subtreeLevels": subtrees.levels,
+                "subtrees": {
+                    "uri": subtrees.get_uri_template()
                 }
+            }
+        }
+    })
+}
+
+/// The 3D Tiles metadata schema for the tileset and tile properties
+fn make_schema() -> Value {
+    let scalar = |component_type: &str, description: &str| json!({
+        "type": "SCALAR",
+        "componentType": component_type,
+        "description": description
+    });
+    let string = |description: &str| json!({
+        "type": "STRING",
+        "description": description
+    });
+
+    let directions = [
+        Direction::Right,
+        Direction::Up,
+        Direction::Left,
+        Direction::Down
+    ];
+    let direction_values = directions.map(|direction| json!({
+        "name": get_direction_name(direction),
+        "value": direction.to_index()
+    }));
+    let algorithm_values = Algorithm::ALL.map(|algorithm| json!({
+        "name": algorithm.name(),
+        "value": get_algorithm_value(algorithm)
+    }));
+
+    json!({
+        "id": "nested_mazes",
+        "enums": {
+            "direction": {
+                "values": direction_values
             },
-            "metadata": {
-                "class": "tileset",
+            "algorithm": {
+                "valueType": "UINT8",
+                "values": algorithm_values
+            }
+        },
+        "classes": {
+            "tileset": {
+                "name": "Nested maze",
                 "properties": {
-                    "wall_tileset_uri": "tileset_walls.png",
-                    "connection_tileset_uri": "tileset_connections.png"
+                    "wall_tileset_uri": string(
+                        "Texture atlas of walls, indexed by connection bits"
+                    ),
+                    "connection_tileset_uri": string(
+                        "Texture atlas of solution paths, indexed by solution bits"
+                    ),
+                    "atlas_tile_count": scalar(
+                        "UINT32",
+                        "Number of tiles in each atlas, in a single row"
+                    ),
+                    "atlas_tile_size": scalar(
+                        "UINT32",
+                        "Width and height of each atlas tile in pixels, not counting padding"
+                    ),
+                    "atlas_padding": scalar(
+                        "UINT32",
+                        "Pixels of padding around each atlas tile"
+                    ),
+                    "grid_size": scalar(
+                        "UINT32",
+                        "Width and height of each tile in cells"
+                    ),
+                    "connections_channel": scalar(
+                        "UINT8",
+                        "Channel of the feature ID texture with each cell's connection bits"
+                    ),
+                    "solution_channel": scalar(
+                        "UINT8",
+                        "Channel of the feature ID texture with each cell's solution bits"
+                    ),
+                    "solution_shift": scalar(
+                        "UINT8",
+                        "Bits to shift the solution channel right by, 4 when both sets of bits share a channel"
+                    ),
+                    "bit_directions": {
+                        "type": "ENUM",
+                        "enumType": "direction",
+                        "array": true,
+                        "count": 4,
+                        "description": "Direction of each connection bit, lowest bit first"
+                    },
+                    "seed": string("Random seed, in decimal"),
+                    "algorithm": string(
+                        "Maze generation algorithm, or LEVEL=ALGORITHM stages separated by commas"
+                    ),
+                    "level_count": scalar("UINT32", "Number of levels of detail"),
+                    "generator": string("Program that generated the tileset"),
+                    "generator_version": string("Version of the generator")
                 }
             },
-            "refine": "REPLACE",
-            "root": {
-                "boundingVolume": {
-                    "box": [
-                        0, 0, 0,
-                        1, 0, 0,
-                        0, 1, 0,
-                        0, 0, 1
-                    ]
-                },
-                "geometricError": 1 << self.levels,
-                "content": {
-                    "uri": "tiles/{level}.{x}.{y}.glb"
-                },
-                "implicitTiling": {
-                    "subdivisionScheme": "QUADTREE",
-                    "availableLevels": self.levels,
-                    "subtreeLevels": self.levels,
-                    "subtrees": {
-                        "uri": "{level}.{x}.{y}.subtree.json"
-                    }
+            "tile": {
+                "name": "Maze tile",
+                "properties": {
+                    "exit_count": scalar(
+                        "UINT16",
+                        "Number of connections across the edges of the tile"
+                    ),
+                    "solution_length": scalar(
+                        "UINT32",
+                        "Number of cells in the tile on the solution path"
+                    ),
+                    "dead_end_count": scalar(
+                        "UINT32",
+                        "Number of cells in the tile with only one connection"
+                    ),
+                    "algorithm": {
+                        "type": "ENUM",
+                        "enumType": "algorithm",
+                        "description": "Maze generation algorithm used for this tile's level"
+                    },
+                    "on_solution": {
+                        "type": "BOOLEAN",
+                        "description": "Whether the solution of the whole maze passes through the tile"
+                    },
+                    "triangle_count": scalar(
+                        "UINT32",
+                        "Number of triangles in the tile's content"
+                    )
                 }
             }
-        });
+        }
+    })
+}
 
-        let tileset_path = Path::new(&self.output_directory)
-            .join("tileset.json");
-        let json_string = to_string_pretty(&tileset_json)
-            .expect("Could not serialize JSON");
-        write(tileset_path, json_string)
-            .expect("could not write tileset JSON");
+pub(crate) fn get_direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Right => "RIGHT",
+        Direction::Up => "UP",
+        Direction::Left => "LEFT",
+        Direction::Down => "DOWN",
     }
+}
 
-    fn generate_maze(&self) {
-        let mut maze_gen = DFSMaze::new();
-        let mut solver = DFSSolutionFinder::new();
-        let root = Tile::make_root(&mut maze_gen, &mut solver);
-        let mut stack = vec![root];
+fn read_tileset_json(output_directory: &Path) -> Result<(PathBuf, Value)> {
+    let tileset_path = output_directory.join("tileset.json");
+    let json_string = read_to_string(&tileset_path)
+        .map_err(MazeError::io("could not read tileset JSON", &tileset_path))?;
+    Ok((tileset_path, from_str(&json_string)?))
+}
+
+/// Read the number of levels of a tileset in output_directory from its
+/// tileset.json. This is for checking the tileset, so anything deeper than
+/// `get_max_checked_levels` allows for its grid size is rejected.
+pub fn read_available_levels(output_directory: &Path) -> Result<usize> {
+    let (tileset_path, tileset_json) = read_tileset_json(output_directory)?;
+
+    let grid_size = tileset_json["metadata"]["properties"]["grid_size"]
+        .as_u64()
+        .and_then(|grid_size| usize::try_from(grid_size).ok())
+        .ok_or_else(|| MazeError::InvalidInput(format!(
+            "{} does not have a valid grid_size",
+            tileset_path.display()
+        )))?;
+    let max_levels = get_max_checked_levels(grid_size);
+
+    tileset_json["root"]["implicitTiling"]["availableLevels"]
+        .as_u64()
+        .and_then(|levels| usize::try_from(levels).ok())
+        .filter(|levels| (1..=max_levels).contains(levels))
+        .ok_or_else(|| MazeError::InvalidInput(format!(
+            "{} does not have a valid availableLevels, it must be from 1 to {} for a grid size of {}",
+            tileset_path.display(),
+            max_levels,
+            grid_size
+        )))
+}
 
-        let tiles_dir = Path::new(&self.output_directory).join("tiles");
+/// Read how a tileset in output_directory is split into subtrees from its
+/// tileset.json
+pub fn read_subtree_layout(output_directory: &Path) -> Result<SubtreeLayout> {
+    let (tileset_path, tileset_json) = read_tileset_json(output_directory)?;
+    let implicit_tiling = &tileset_json["root"]["implicitTiling"];
 
-        // depth-first pre-order generation of the tileset using a stack
-        while let Some(tile) = stack.pop() {
-            tile.write_glb(&tiles_dir);
+    let levels = implicit_tiling["subtreeLevels"].as_u64().unwrap_or(0) as usize;
+    let format = match implicit_tiling["subtrees"]["uri"].as_str() {
+        Some(uri) if uri.ends_with(".json") => SubtreeFormat::Json,
+        Some(_) => SubtreeFormat::Binary,
+        None => return Err(MazeError::InvalidInput(format!(
+            "{} does not have a subtree URI",
+            tileset_path.display()
+        )))
+    };
 
-            if tile.level < self.levels - 1 {
-                let child_tiles = tile.subdivide(&mut maze_gen, &mut solver);
+    SubtreeLayout::new(levels, format)
+}
+
+/// Read the URI template of the tile contents of a tileset in
+/// output_directory from its tileset.json, e.g. `tiles/{level}.{x}.{y}.glb`
+pub fn read_content_uri(output_directory: &Path) -> Result<String> {
+    let (tileset_path, tileset_json) = read_tileset_json(output_directory)?;
+
+    tileset_json["root"]["content"]["uri"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| MazeError::InvalidInput(format!(
+            "{} does not have a content URI",
+            tileset_path.display()
+        )))
+}
+
+/// The files a tile's content refers to: the shared buffers, and its
+/// image if that's a separate file. These are relative to the content.
+fn read_external_uris(content_path: &Path) -> Result<Vec<String>> {
+    let json = if content_path.extension().is_some_and(|extension| extension == "gltf") {
+        let bytes = read(content_path)
+            .map_err(MazeError::io("could not read glTF", content_path))?;
+        from_slice(&bytes)?
+    } else {
+        Glb::read(content_path)?.json
+    };
+
+    Ok(["buffers", "images"].iter()
+        .flat_map(|key| json[key].as_array().into_iter().flatten())
+        .filter_map(|item| item["uri"].as_str().map(str::to_string))
+        .collect())
+}
 
-                // Since we're using a stack, push the tiles
-                // backwards so the DFS is more like Morton order.
-                stack.extend(child_tiles.into_iter().rev());
+/// List the files that a tileset generated in output_directory should
+/// have but doesn't. The number of levels, subtrees and where the tiles
+/// are are read from tileset.json. The root tile says which shared
+/// buffers there are, and each `.gltf` tile says where its image is.
+pub fn find_missing_files(output_directory: &Path) -> Result<Vec<PathBuf>> {
+    let levels = read_available_levels(output_directory)?;
+    let subtrees = read_subtree_layout(output_directory)?;
+    let content_uri = read_content_uri(output_directory)?;
+    let is_gltf = content_uri.ends_with(".gltf");
+
+    let mut expected = vec![
+        output_directory.join("tileset_walls.png"),
+        output_directory.join("tileset_connections.png"),
+    ];
+    for root in subtrees.get_roots(levels) {
+        expected.push(output_directory.join(subtrees.get_file_name(root)));
+    }
+
+    let mut referenced = BTreeSet::new();
+    for level in 0..levels {
+        let tiles_per_side = 1 << level;
+        for y in 0..tiles_per_side {
+            for x in 0..tiles_per_side {
+                let path = output_directory
+                    .join(fill_uri_template(&content_uri, (level, x, y)));
+                if path.exists() && (level == 0 || is_gltf) {
+                    for uri in read_external_uris(&path)? {
+                        referenced.insert(path.with_file_name(uri));
+                    }
+                }
+                expected.push(path);
             }
         }
     }
-}
\ No newline at end of file
+    expected.extend(referenced);
+
+    Ok(expected.into_iter().filter(|path| !path.exists()).collect())
+}
@@ -0,0 +1,515 @@
+//! Checks for the invariants every tile of the nested maze should satisfy.
+//! These are used by the test suite, but they work on any tiles, so they
+//! are also handy when experimenting with new maze algorithms.
+
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+use crate::algorithms::UnionFind;
+use crate::config::MazeConfig;
+use crate::dfs::DFSSolutionFinder;
+use crate::direction::Direction;
+use crate::error::{MazeError, Result};
+use crate::grid::Grid;
+use crate::grid_coords::GridCoords;
+use crate::tile::Tile;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Right,
+    Direction::Up,
+    Direction::Left,
+    Direction::Down
+];
+
+/// A broken invariant, and which tile it was found in
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub level: usize,
+    pub x: usize,
+    pub y: usize,
+    pub message: String,
+}
+
+impl Violation {
+    fn new(tile: &Tile, message: String) -> Self {
+        Self {
+            level: tile.level,
+            x: tile.x,
+            y: tile.y,
+            message
+        }
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "tile {}.{}.{}: {}", self.level, self.x, self.y, self.message)
+    }
+}
+
+/// Check the invariants of a single grid:
+///
+/// * Connections are symmetric, and the solution only goes through
+///   connected cells
+/// * The connections form a spanning forest, i.e. there are no loops
+/// * Every cell can be reached from an opening in the boundary
+/// * The solution is made of simple paths between openings in the
+///   boundary, i.e. it never dead-ends
+pub fn check_grid(grid: &Grid) -> Vec<String> {
+    let mut messages = Vec::new();
+    check_consistency(grid, &mut messages);
+    check_spanning_forest(grid, &mut messages);
+    check_reachability(grid, &mut messages);
+    check_solution(grid, &mut messages);
+
+    messages
+}
+
+/// Check a single tile on its own. See [`check_grid`]
+pub fn check_tile(tile: &Tile) -> Vec<Violation> {
+    check_grid(tile.get_grid())
+        .into_iter()
+        .map(|message| Violation::new(tile, message))
+        .collect()
+}
+
+/// Check that a tile and its neighbor in the given direction agree on
+/// every connection along the seam between them.
+pub fn check_seam(
+    tile: &Tile,
+    neighbor: &Tile,
+    direction: Direction
+) -> Vec<Violation> {
+    let (dx, dy) = match direction {
+        Direction::Right => (1, 0),
+        Direction::Up => (0, 1),
+        Direction::Left => (-1, 0),
+        Direction::Down => (0, -1),
+    };
+    let adjacent = neighbor.level == tile.level &&
+        neighbor.x as i128 == tile.x as i128 + dx &&
+        neighbor.y as i128 == tile.y as i128 + dy;
+    if !adjacent {
+        return vec![Violation::new(tile, format!(
+            "tile {}.{}.{} is not the neighbor in the {:?} direction",
+            neighbor.level,
+            neighbor.x,
+            neighbor.y,
+            direction
+        ))];
+    }
+
+    let grid = tile.get_grid();
+    let neighbor_grid = neighbor.get_grid();
+    let size = grid.get_size();
+    let opposite = direction.get_opposite();
+    let mut violations = Vec::new();
+    for i in 0..size {
+        let (cell, neighbor_cell) = match direction {
+            Direction::Right => (GridCoords { x: size - 1, y: i }, GridCoords { x: 0, y: i }),
+            Direction::Up => (GridCoords { x: i, y: size - 1 }, GridCoords { x: i, y: 0 }),
+            Direction::Left => (GridCoords { x: 0, y: i }, GridCoords { x: size - 1, y: i }),
+            Direction::Down => (GridCoords { x: i, y: 0 }, GridCoords { x: i, y: size - 1 }),
+        };
+        let ours = grid.get_cell(cell);
+        let theirs = neighbor_grid.get_cell(neighbor_cell);
+
+        if ours.is_connected(direction) != theirs.is_connected(opposite) {
+            violations.push(Violation::new(tile, format!(
+                "connection at {:?} {:?} does not match the neighboring tile",
+                cell,
+                direction
+            )));
+        }
+
+        let solution_matches = ours.is_solution_connection(direction) ==
+            theirs.is_solution_connection(opposite);
+        if !solution_matches {
+            violations.push(Violation::new(tile, format!(
+                "solution at {:?} {:?} does not match the neighboring tile",
+                cell,
+                direction
+            )));
+        }
+    }
+
+    violations
+}
+
+/// Check that a child tile refines its parent exactly. Each parent cell
+/// becomes a 2x2 block of child cells, and for every parent connection
+/// between two cells of the child's quadrant:
+///
+/// * A wall in the parent stays a wall across both child cells
+/// * A hallway in the parent stays open across at least one child cell,
+///   and the child's solution goes through it if the parent's did. The
+///   child's solution may also detour through hallways the parent's
+///   didn't, since each 2x2 block is only connected by the child's maze.
+///
+/// Connections on the quadrant's edge become the child's boundary, and must
+/// continue through exactly one of the two child cells.
+pub fn check_refinement(parent: &Tile, child: &Tile) -> Vec<Violation> {
+    let is_child = child.level == parent.level + 1 &&
+        child.x >> 1 == parent.x &&
+        child.y >> 1 == parent.y;
+    if !is_child {
+        return vec![Violation::new(child, format!(
+            "not a child of tile {}.{}.{}",
+            parent.level,
+            parent.x,
+            parent.y
+        ))];
+    }
+
+    let parent_grid = parent.get_grid();
+    let child_grid = child.get_grid();
+    let half_size = parent_grid.get_size() / 2;
+    let x_start = (child.x & 1) * half_size;
+    let y_start = (child.y & 1) * half_size;
+
+    let mut violations = Vec::new();
+    for y in 0..half_size {
+        for x in 0..half_size {
+            let parent_coords = GridCoords { x: x_start + x, y: y_start + y };
+            let parent_cell = parent_grid.get_cell(parent_coords);
+
+            for direction in DIRECTIONS {
+                let on_edge = match direction {
+                    Direction::Right => x == half_size - 1,
+                    Direction::Up => y == half_size - 1,
+                    Direction::Left => x == 0,
+                    Direction::Down => y == 0,
+                };
+
+                // Interior connections are shared by two parent cells,
+                // only check them once.
+                let is_duplicate = matches!(
+                    direction,
+                    Direction::Left | Direction::Down
+                );
+                if is_duplicate && !on_edge {
+                    continue;
+                }
+
+                // The 2 child cells along this side of the 2x2 block
+                let (child_x, child_y) = (2 * x, 2 * y);
+                let child_cells = match direction {
+                    Direction::Right => [(child_x + 1, child_y), (child_x + 1, child_y + 1)],
+                    Direction::Up => [(child_x, child_y + 1), (child_x + 1, child_y + 1)],
+                    Direction::Left => [(child_x, child_y), (child_x, child_y + 1)],
+                    Direction::Down => [(child_x, child_y), (child_x + 1, child_y)],
+                };
+                let (open_count, solution_count) = child_cells
+                    .into_iter()
+                    .map(|(x, y)| child_grid.get_cell(GridCoords { x, y }))
+                    .fold((0, 0), |(open, solution), cell| (
+                        open + cell.is_connected(direction) as usize,
+                        solution + cell.is_solution_connection(direction) as usize
+                    ));
+
+                let parent_open = parent_cell.is_connected(direction);
+                let parent_solution = parent_cell.is_solution_connection(direction);
+                let refined = if on_edge {
+                    open_count == parent_open as usize &&
+                        solution_count == parent_solution as usize
+                } else {
+                    (open_count > 0) == parent_open &&
+                        (solution_count > 0 || !parent_solution)
+                };
+
+                if !refined {
+                    violations.push(Violation::new(child, format!(
+                        "parent cell {:?} {:?} (open: {}, solution: {}) became \
+                        {} open and {} solution connections",
+                        parent_coords,
+                        direction,
+                        parent_open,
+                        parent_solution,
+                        open_count,
+                        solution_count
+                    )));
+                }
+            }
+        }
+    }
+
+    violations
+}
+
+/// Check a whole level as one maze, which the checks on single tiles and
+/// seams can't see: the connections between tiles mustn't close a loop,
+/// and every cell of the level must be connected to all the others. The
+/// tiles can be in any order, but they must cover the whole level.
+pub fn check_level(tiles: &[&Tile]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let tiles_per_side = tiles.len().isqrt();
+    assert_eq!(tiles_per_side * tiles_per_side, tiles.len(), "the level must be square");
+    let size = tiles[0].get_grid().get_size();
+    let width = tiles_per_side * size;
+    let get_index = |tile: &Tile, coords: GridCoords| {
+        (tile.y * size + coords.y) * width + tile.x * size + coords.x
+    };
+
+    // Connections inside each tile go first. Loops made only of those were
+    // already reported by check_tile, so only the seams are reported here.
+    let mut sets = UnionFind::new(width * width);
+    let mut seams = Vec::new();
+    for tile in tiles {
+        let grid = tile.get_grid();
+        for y in 0..size {
+            for x in 0..size {
+                let coords = GridCoords { x, y };
+                let cell = grid.get_cell(coords);
+                let neighbors = [
+                    (Direction::Right, x + 1 < size, tile.x + 1 < tiles_per_side, 1),
+                    (Direction::Up, y + 1 < size, tile.y + 1 < tiles_per_side, width)
+                ];
+                for (direction, inside_tile, inside_level, step) in neighbors {
+                    if !cell.is_connected(direction) {
+                        continue;
+                    }
+
+                    let index = get_index(tile, coords);
+                    if inside_tile {
+                        sets.union(index, index + step);
+                    } else if inside_level {
+                        seams.push((tile, coords, direction, index + step));
+                    }
+                }
+            }
+        }
+    }
+
+    for (tile, coords, direction, neighbor) in seams {
+        if !sets.union(get_index(tile, coords), neighbor) {
+            violations.push(Violation::new(tile, format!(
+                "the connection at {:?} {:?} makes a loop through other tiles",
+                coords,
+                direction
+            )));
+        }
+    }
+
+    // Everything should have ended up in one set. Report the unconnected
+    // cells against the first tile that has any.
+    let root = sets.find(0);
+    let mut unconnected = 0;
+    let mut first_unconnected = None;
+    for tile in tiles {
+        for index in 0..size * size {
+            let coords = GridCoords::from_index(index, size);
+            if sets.find(get_index(tile, coords)) != root {
+                unconnected += 1;
+                first_unconnected.get_or_insert((*tile, coords));
+            }
+        }
+    }
+    if let Some((tile, coords)) = first_unconnected {
+        violations.push(Violation::new(tile, format!(
+            "{} cells of the level aren't connected to the rest of it, e.g. {:?}",
+            unconnected,
+            coords
+        )));
+    }
+
+    violations
+}
+
+/// Generate the first few levels of a maze in memory and run every check on
+/// every tile. A whole level is kept in memory at once so the seams between
+/// tiles can be compared and the level checked as one maze, so this is only
+/// practical for small tilesets.
+pub fn validate_maze(config: &MazeConfig, levels: usize) -> Result<Vec<Violation>> {
+    if levels == 0 || levels > config.max_levels() {
+        return Err(MazeError::InvalidInput(format!(
+            "levels must be from 1 to {}, got {}",
+            config.max_levels(),
+            levels
+        )));
+    }
+
+    let mut generators = config.algorithms.make_generators();
+    let mut solver = DFSSolutionFinder::new();
+    let mut violations = Vec::new();
+
+    // Tiles of the current level in row-major order
+    let mut tiles = vec![Tile::make_root(config, &mut generators, &mut solver)];
+    for level in 0..levels {
+        let tiles_per_side = 1 << level;
+        for tile in &tiles {
+            violations.extend(check_tile(tile));
+
+            if tile.x + 1 < tiles_per_side {
+                let right = &tiles[tile.y * tiles_per_side + tile.x + 1];
+                violations.extend(check_seam(tile, right, Direction::Right));
+            }
+
+            if tile.y + 1 < tiles_per_side {
+                let up = &tiles[(tile.y + 1) * tiles_per_side + tile.x];
+                violations.extend(check_seam(tile, up, Direction::Up));
+            }
+        }
+        violations.extend(check_level(&tiles.iter().collect::<Vec<_>>()));
+
+        if level + 1 == levels {
+            break;
+        }
+
+        let mut children: Vec<Option<Tile>> = Vec::new();
+        children.resize_with(4 * tiles.len(), || None);
+        for tile in &tiles {
+            for child in tile.subdivide(config, &mut generators, &mut solver) {
+                violations.extend(check_refinement(tile, &child));
+                let index = child.y * (2 * tiles_per_side) + child.x;
+                children[index] = Some(child);
+            }
+        }
+        tiles = children.into_iter().flatten().collect();
+    }
+
+    Ok(violations)
+}
+
+/// Connections must look the same from both sides, and the solution can
+/// only go through connected cells
+fn check_consistency(grid: &Grid, messages: &mut Vec<String>) {
+    let size = grid.get_size();
+    for y in 0..size {
+        for x in 0..size {
+            let coords = GridCoords { x, y };
+            let cell = grid.get_cell(coords);
+            for direction in DIRECTIONS {
+                if cell.is_solution_connection(direction) && !cell.is_connected(direction) {
+                    messages.push(format!(
+                        "solution goes through a wall at {:?} {:?}",
+                        coords,
+                        direction
+                    ));
+                }
+            }
+
+            for direction in [Direction::Right, Direction::Up] {
+                let in_bounds = match direction {
+                    Direction::Right => x + 1 < size,
+                    _ => y + 1 < size,
+                };
+                if !in_bounds {
+                    continue;
+                }
+
+                let neighbor = grid.get_cell(coords.get_adjacent(direction));
+                let opposite = direction.get_opposite();
+                let symmetric = cell.is_connected(direction) == neighbor.is_connected(opposite) &&
+                    cell.is_solution_connection(direction) ==
+                        neighbor.is_solution_connection(opposite);
+                if !symmetric {
+                    messages.push(format!(
+                        "connection at {:?} {:?} is one-sided",
+                        coords,
+                        direction
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn check_spanning_forest(grid: &Grid, messages: &mut Vec<String>) {
+    let size = grid.get_size();
+    let mut sets = UnionFind::new(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            let coords = GridCoords { x, y };
+            let cell = grid.get_cell(coords);
+            let neighbors = [
+                (Direction::Right, x + 1 < size),
+                (Direction::Up, y + 1 < size)
+            ];
+            for (direction, in_bounds) in neighbors {
+                if !in_bounds || !cell.is_connected(direction) {
+                    continue;
+                }
+
+                let neighbor = coords.get_adjacent(direction);
+                if !sets.union(coords.to_index(size), neighbor.to_index(size)) {
+                    messages.push(format!(
+                        "the connection at {:?} {:?} makes a loop",
+                        coords,
+                        direction
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Every cell must be reachable from an opening in the tile's boundary,
+/// otherwise part of the maze would be sealed off from the rest of the level.
+fn check_reachability(grid: &Grid, messages: &mut Vec<String>) {
+    let size = grid.get_size();
+    let mut reached = vec![false; size * size];
+    let mut queue = VecDeque::new();
+    for (coords, direction) in boundary_connections(size) {
+        let index = coords.to_index(size);
+        if grid.get_cell(coords).is_connected(direction) && !reached[index] {
+            reached[index] = true;
+            queue.push_back(coords);
+        }
+    }
+
+    while let Some(coords) = queue.pop_front() {
+        let cell = grid.get_cell(coords);
+        for neighbor in coords.get_neighbors(size) {
+            let direction = GridCoords::get_direction(coords, neighbor)
+                .expect("neighbors are adjacent");
+            let index = neighbor.to_index(size);
+            if cell.is_connected(direction) && !reached[index] {
+                reached[index] = true;
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    let unreached: Vec<GridCoords> = (0..size * size)
+        .filter(|index| !reached[*index])
+        .map(|index| GridCoords::from_index(index, size))
+        .collect();
+    if let Some(first) = unreached.first() {
+        messages.push(format!(
+            "{} cells can't be reached from the boundary, e.g. {:?}",
+            unreached.len(),
+            first
+        ));
+    }
+}
+
+/// The solution never has loops since the maze doesn't. So if no cell on
+/// the solution is a dead end, every part of the solution is a simple path
+/// between two openings in the boundary.
+fn check_solution(grid: &Grid, messages: &mut Vec<String>) {
+    let size = grid.get_size();
+    for index in 0..size * size {
+        let coords = GridCoords::from_index(index, size);
+        let cell = grid.get_cell(coords);
+        let degree = DIRECTIONS
+            .into_iter()
+            .filter(|direction| cell.is_solution_connection(*direction))
+            .count();
+        if degree == 1 {
+            messages.push(format!("the solution dead-ends at {:?}", coords));
+        }
+    }
+}
+
+/// Every (cell, direction) pair that leads out of the grid
+fn boundary_connections(size: usize) -> Vec<(GridCoords, Direction)> {
+    let mut result = Vec::new();
+    for i in 0..size {
+        result.push((GridCoords { x: i, y: 0 }, Direction::Down));
+        result.push((GridCoords { x: i, y: size - 1 }, Direction::Up));
+        result.push((GridCoords { x: 0, y: i }, Direction::Left));
+        result.push((GridCoords { x: size - 1, y: i }, Direction::Right));
+    }
+
+    result
+}
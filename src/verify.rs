@@ -0,0 +1,136 @@
+//! Check a tileset that was already written to disk. Unlike the `validate`
+//! module, which checks tiles as they are generated, this reads the GLB
+//! files back in, so it also catches problems in how tiles were written,
+//! and tiles that disagree with their neighbors even though each one looks
+//! fine on its own.
+
+use std::path::Path;
+
+use crate::direction::Direction;
+use crate::error::{MazeError, Result};
+use crate::tile::Tile;
+use crate::subtree::fill_uri_template;
+use crate::tileset::{read_available_levels, read_content_uri};
+use crate::validate::{check_level, check_refinement, check_seam, check_tile, Violation};
+
+/// The results of verifying a tileset
+pub struct VerifyReport {
+    pub tiles_checked: usize,
+    pub violations: Vec<Violation>,
+}
+
+/// Read every tile of the tileset in output_directory and check it on its
+/// own, against its neighbors at the same level and against its parent.
+/// Each level is also checked as one maze, for loops through several tiles
+/// and parts cut off from the rest, but only if none of its tiles are
+/// missing. Missing or unreadable tiles are reported as violations rather
+/// than errors so the rest of the tileset still gets checked.
+pub fn verify_tileset(output_directory: &Path) -> Result<VerifyReport> {
+    let levels = read_available_levels(output_directory)?;
+    let content_uri = read_content_uri(output_directory)?;
+
+    let mut report = VerifyReport {
+        tiles_checked: 0,
+        violations: Vec::new(),
+    };
+    let mut grid_size = None;
+
+    // Only the current level and its parents are kept in memory, in
+    // row-major order.
+    let mut parents: Vec<Option<Tile>> = Vec::new();
+    for level in 0..levels {
+        let tiles_per_side = 1 << level;
+        let mut tiles = Vec::with_capacity(tiles_per_side * tiles_per_side);
+        for y in 0..tiles_per_side {
+            for x in 0..tiles_per_side {
+                let path = output_directory
+                    .join(fill_uri_template(&content_uri, (level, x, y)));
+                let tile = if path.exists() {
+                    read_tile(&path, level, x, y)
+                } else {
+                    Err(MazeError::InvalidData("tile is missing".to_string()))
+                };
+
+                match tile {
+                    Ok(tile) => {
+                        let size = tile.get_grid().get_size();
+                        let expected_size = *grid_size.get_or_insert(size);
+                        if size != expected_size {
+                            return Err(MazeError::InvalidData(format!(
+                                "{} is {}x{} but other tiles are {}x{}",
+                                path.display(),
+                                size,
+                                size,
+                                expected_size,
+                                expected_size
+                            )));
+                        }
+                        tiles.push(Some(tile));
+                    },
+                    Err(error) => {
+                        report.violations.push(Violation {
+                            level,
+                            x,
+                            y,
+                            message: error.to_string()
+                        });
+                        tiles.push(None);
+                    }
+                }
+            }
+        }
+
+        for (index, tile) in tiles.iter().enumerate() {
+            let tile = match tile {
+                Some(tile) => tile,
+                None => continue
+            };
+            report.tiles_checked += 1;
+            report.violations.extend(check_tile(tile));
+
+            if tile.x + 1 < tiles_per_side {
+                if let Some(right) = &tiles[index + 1] {
+                    report.violations.extend(check_seam(tile, right, Direction::Right));
+                }
+            }
+
+            if tile.y + 1 < tiles_per_side {
+                if let Some(up) = &tiles[index + tiles_per_side] {
+                    report.violations.extend(check_seam(tile, up, Direction::Up));
+                }
+            }
+
+            if level > 0 {
+                let parent_index = (tile.y >> 1) * (tiles_per_side >> 1) + (tile.x >> 1);
+                if let Some(parent) = &parents[parent_index] {
+                    report.violations.extend(check_refinement(parent, tile));
+                }
+            }
+        }
+
+        let complete: Option<Vec<&Tile>> = tiles.iter().map(Option::as_ref).collect();
+        if let Some(complete) = complete {
+            report.violations.extend(check_level(&complete));
+        }
+
+        parents = tiles;
+    }
+
+    Ok(report)
+}
+
+/// Read a tile back, and make sure its transform matches its filename
+fn read_tile(path: &Path, level: usize, x: usize, y: usize) -> Result<Tile> {
+    let tile = Tile::read(path)?;
+    if (tile.level, tile.x, tile.y) != (level, x, y) {
+        return Err(MazeError::InvalidData(format!(
+            "{} has the transform of tile {}.{}.{}",
+            path.display(),
+            tile.level,
+            tile.x,
+            tile.y
+        )));
+    }
+
+    Ok(tile)
+}
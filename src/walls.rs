@@ -0,0 +1,372 @@
+//! Real 3D geometry for a tile, for viewers that don't have the custom
+//! shader. The walls are extruded from the edges of each cell, and there's
+//! an optional floor and a ribbon along the solution. Everything is in the
+//! same coordinates as the quad in `geometry`: the tile goes from -1 to 1
+//! in x and z, with y up.
+
+use image::Rgba;
+
+use crate::direction::Direction;
+use crate::error::{MazeError, Result};
+use crate::grid::{Grid, BIT_ORDER};
+use crate::grid_coords::GridCoords;
+
+/// How far the solution ribbon floats above the floor, in cells. Just
+/// enough to keep it from z-fighting with the floor
+const SOLUTION_LIFT: f32 = 0.01;
+
+/// Parameters for the wall geometry
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallStyle {
+    /// Height of the walls, in cells
+    pub height: f32,
+    /// Thickness of the walls as a fraction of a cell. Like the atlases,
+    /// neighboring cells each get half of the wall between them.
+    pub thickness: f32,
+    /// Width of the solution ribbon as a fraction of a cell
+    pub solution_width: f32,
+    pub floor: bool,
+    pub solution: bool,
+    pub wall_color: Rgba<u8>,
+    pub floor_color: Rgba<u8>,
+    pub solution_color: Rgba<u8>,
+}
+
+impl Default for WallStyle {
+    fn default() -> Self {
+        Self {
+            height: 0.5,
+            thickness: 0.2,
+            solution_width: 0.3,
+            floor: true,
+            solution: true,
+            wall_color: Rgba([96, 96, 112, 255]),
+            floor_color: Rgba([224, 224, 208, 255]),
+            solution_color: Rgba([224, 48, 32, 255]),
+        }
+    }
+}
+
+impl WallStyle {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.height > 0.0 && self.height.is_finite()) {
+            return Err(MazeError::InvalidInput(format!(
+                "walls must have a positive height, not {}",
+                self.height
+            )));
+        }
+
+        let fits = |fraction: f32| fraction > 0.0 && fraction < 1.0;
+        if !fits(self.thickness) || !fits(self.solution_width) {
+            return Err(MazeError::InvalidInput(format!(
+                "walls {} cells thick and a solution {} cells wide don't fit in a cell",
+                self.thickness,
+                self.solution_width
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// An indexed triangle mesh with flat shading
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn get_triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Add a flat quad facing towards normal. The corners go around the
+    /// quad in either direction, the winding is fixed up to match the
+    /// normal.
+    fn add_quad(&mut self, mut corners: [[f32; 3]; 4], normal: [f32; 3]) {
+        let [a, b, c, _] = corners;
+        let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let cross = [
+            ab[1] * ac[2] - ab[2] * ac[1],
+            ab[2] * ac[0] - ab[0] * ac[2],
+            ab[0] * ac[1] - ab[1] * ac[0],
+        ];
+        let dot: f32 = cross.iter().zip(normal).map(|(a, b)| a * b).sum();
+        if dot < 0.0 {
+            corners.reverse();
+        }
+
+        let first = self.positions.len() as u32;
+        self.positions.extend(corners);
+        self.normals.extend([normal; 4]);
+        self.indices.extend([0, 1, 2, 2, 3, 0].map(|index| first + index));
+    }
+
+    /// Add the top and sides of an axis-aligned box standing on the floor.
+    /// The bottom is never visible so it's left out, and so are the sides
+    /// whose normals are in hidden.
+    fn add_box(&mut self, min: [f32; 2], max: [f32; 2], height: f32, hidden: &[[f32; 3]]) {
+        let [x0, z0] = min;
+        let [x1, z1] = max;
+        let faces = [
+            (
+                [[x0, height, z0], [x1, height, z0], [x1, height, z1], [x0, height, z1]],
+                [0.0, 1.0, 0.0]
+            ),
+            (
+                [[x0, 0.0, z0], [x0, height, z0], [x0, height, z1], [x0, 0.0, z1]],
+                [-1.0, 0.0, 0.0]
+            ),
+            (
+                [[x1, 0.0, z0], [x1, height, z0], [x1, height, z1], [x1, 0.0, z1]],
+                [1.0, 0.0, 0.0]
+            ),
+            (
+                [[x0, 0.0, z0], [x1, 0.0, z0], [x1, height, z0], [x0, height, z0]],
+                [0.0, 0.0, -1.0]
+            ),
+            (
+                [[x0, 0.0, z1], [x1, 0.0, z1], [x1, height, z1], [x0, height, z1]],
+                [0.0, 0.0, 1.0]
+            ),
+        ];
+        for (corners, normal) in faces {
+            if !hidden.contains(&normal) {
+                self.add_quad(corners, normal);
+            }
+        }
+    }
+
+    /// Add a flat rectangle facing up
+    fn add_rectangle(&mut self, min: [f32; 2], max: [f32; 2], y: f32) {
+        let [x0, z0] = min;
+        let [x1, z1] = max;
+        self.add_quad(
+            [[x0, y, z0], [x1, y, z0], [x1, y, z1], [x0, y, z1]],
+            [0.0, 1.0, 0.0]
+        );
+    }
+}
+
+/// One part of the tile, drawn with a single material
+pub struct MazeMesh {
+    pub name: &'static str,
+    pub color: Rgba<u8>,
+    pub mesh: Mesh,
+}
+
+/// Build the meshes for a grid: the walls, then the floor and solution
+/// ribbon if the style has them. Empty meshes are left out.
+pub fn make_meshes(grid: &Grid, style: &WallStyle) -> Vec<MazeMesh> {
+    let cell_size = 2.0 / grid.get_size() as f32;
+    let mut meshes = vec![MazeMesh {
+        name: "Walls",
+        color: style.wall_color,
+        mesh: make_walls(grid, style, cell_size),
+    }];
+
+    if style.floor {
+        let mut floor = Mesh::default();
+        floor.add_rectangle([-1.0, -1.0], [1.0, 1.0], 0.0);
+        meshes.push(MazeMesh {
+            name: "Floor",
+            color: style.floor_color,
+            mesh: floor,
+        });
+    }
+
+    if style.solution {
+        meshes.push(MazeMesh {
+            name: "Solution",
+            color: style.solution_color,
+            mesh: make_solution(grid, style, cell_size),
+        });
+    }
+
+    meshes.retain(|maze_mesh| !maze_mesh.mesh.indices.is_empty());
+    meshes
+}
+
+/// The corner of a cell with the smallest x and z. Grid y increases in
+/// the -z direction, the same as the tile transforms.
+fn get_cell_min(coords: GridCoords, cell_size: f32) -> [f32; 2] {
+    [
+        -1.0 + coords.x as f32 * cell_size,
+        1.0 - (coords.y + 1) as f32 * cell_size,
+    ]
+}
+
+/// Which way a direction points in x and z
+fn get_offset(direction: Direction) -> [f32; 2] {
+    match direction {
+        Direction::Right => [1.0, 0.0],
+        Direction::Up => [0.0, -1.0],
+        Direction::Left => [-1.0, 0.0],
+        Direction::Down => [0.0, 1.0],
+    }
+}
+
+/// Walls are merged into runs along each line between cells, so a long
+/// straight wall is a single box. Where walls meet, only one of them fills
+/// in the corner and the other stops at its side, which keeps the boxes
+/// from overlapping at corners and T-junctions. The ends that stop against
+/// another wall are hidden, so they're left out. Walls are cut off at the
+/// edges of the tile so the neighboring tile can add the other half.
+fn make_walls(grid: &Grid, style: &WallStyle, cell_size: f32) -> Mesh {
+    let size = grid.get_size();
+    let half_thickness = 0.5 * style.thickness * cell_size;
+    let height = style.height * cell_size;
+    let to_x = |line: usize| -1.0 + line as f32 * cell_size;
+    let to_z = |line: usize| 1.0 - line as f32 * cell_size;
+    let clamp = |point: [f32; 2]| point.map(|value| value.clamp(-1.0, 1.0));
+
+    let mut mesh = Mesh::default();
+
+    // Horizontal walls run along x. They give way to any vertical wall
+    // that touches their ends, since that wall fills in the corner.
+    for y in 0..=size {
+        let is_covered = |x: usize| {
+            (y > 0 && has_vertical_wall(grid, x, y - 1)) ||
+            (y < size && has_vertical_wall(grid, x, y))
+        };
+        for (start, end) in find_runs(size, |x| has_horizontal_wall(grid, x, y)) {
+            let mut hidden = Vec::new();
+            let mut min_x = to_x(start) - half_thickness;
+            if is_covered(start) {
+                min_x += 2.0 * half_thickness;
+                hidden.push([-1.0, 0.0, 0.0]);
+            }
+            let mut max_x = to_x(end) + half_thickness;
+            if is_covered(end) {
+                max_x -= 2.0 * half_thickness;
+                hidden.push([1.0, 0.0, 0.0]);
+            }
+
+            let z = to_z(y);
+            mesh.add_box(
+                clamp([min_x, z - half_thickness]),
+                clamp([max_x, z + half_thickness]),
+                height,
+                &hidden
+            );
+        }
+    }
+
+    // Vertical walls run along z, and fill in the corners at their ends
+    // unless a horizontal wall goes straight through
+    for x in 0..=size {
+        let is_covered = |y: usize| {
+            x > 0 && x < size &&
+            has_horizontal_wall(grid, x - 1, y) &&
+            has_horizontal_wall(grid, x, y)
+        };
+        for (start, end) in find_runs(size, |y| has_vertical_wall(grid, x, y)) {
+            // Grid y goes towards -z, so the run starts at its largest z
+            let mut hidden = Vec::new();
+            let mut max_z = to_z(start) + half_thickness;
+            if is_covered(start) {
+                max_z -= 2.0 * half_thickness;
+                hidden.push([0.0, 0.0, 1.0]);
+            }
+            let mut min_z = to_z(end) - half_thickness;
+            if is_covered(end) {
+                min_z += 2.0 * half_thickness;
+                hidden.push([0.0, 0.0, -1.0]);
+            }
+
+            let wall_x = to_x(x);
+            mesh.add_box(
+                clamp([wall_x - half_thickness, min_z]),
+                clamp([wall_x + half_thickness, max_z]),
+                height,
+                &hidden
+            );
+        }
+    }
+
+    mesh
+}
+
+/// Whether there's a wall on the line x = line (counting lines from the left
+/// edge of the tile) next to the cell in row y
+fn has_vertical_wall(grid: &Grid, line: usize, y: usize) -> bool {
+    if line == 0 {
+        !grid.get_cell(GridCoords { x: 0, y }).is_connected(Direction::Left)
+    } else {
+        !grid.get_cell(GridCoords { x: line - 1, y }).is_connected(Direction::Right)
+    }
+}
+
+/// Whether there's a wall on the line y = line (counting lines from the
+/// bottom of the grid) next to the cell in column x
+fn has_horizontal_wall(grid: &Grid, x: usize, line: usize) -> bool {
+    if line == 0 {
+        !grid.get_cell(GridCoords { x, y: 0 }).is_connected(Direction::Down)
+    } else {
+        !grid.get_cell(GridCoords { x, y: line - 1 }).is_connected(Direction::Up)
+    }
+}
+
+/// Group the walls along a line into runs of neighboring cells. Each run
+/// is the range of lines it goes between, so a run over cells 2 and 3 is
+/// (2, 4).
+fn find_runs(size: usize, has_wall: impl Fn(usize) -> bool) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for i in 0..=size {
+        match (start, i < size && has_wall(i)) {
+            (None, true) => start = Some(i),
+            (Some(run_start), false) => {
+                runs.push((run_start, i));
+                start = None;
+            },
+            _ => {}
+        }
+    }
+
+    runs
+}
+
+/// A strip from the center of each cell on the solution to each side the
+/// solution goes through
+fn make_solution(grid: &Grid, style: &WallStyle, cell_size: f32) -> Mesh {
+    let size = grid.get_size();
+    let half_width = 0.5 * style.solution_width * cell_size;
+    let y = SOLUTION_LIFT * cell_size;
+
+    let mut mesh = Mesh::default();
+    for cell_y in 0..size {
+        for cell_x in 0..size {
+            let coords = GridCoords { x: cell_x, y: cell_y };
+            let cell = grid.get_cell(coords);
+            let [min_x, min_z] = get_cell_min(coords, cell_size);
+            let center = [min_x + 0.5 * cell_size, min_z + 0.5 * cell_size];
+
+            for direction in BIT_ORDER {
+                if !cell.is_solution_connection(direction) {
+                    continue;
+                }
+
+                // Start half a width behind the center so the strips
+                // overlap into a solid corner
+                let [dx, dz] = get_offset(direction);
+                let start = [center[0] - dx * half_width, center[1] - dz * half_width];
+                let end = [center[0] + dx * 0.5 * cell_size, center[1] + dz * 0.5 * cell_size];
+                let min = [
+                    start[0].min(end[0]) - dz.abs() * half_width,
+                    start[1].min(end[1]) - dx.abs() * half_width,
+                ];
+                let max = [
+                    start[0].max(end[0]) + dz.abs() * half_width,
+                    start[1].max(end[1]) + dx.abs() * half_width,
+                ];
+                mesh.add_rectangle(min, max, y);
+            }
+        }
+    }
+
+    mesh
+}
@@ -0,0 +1,305 @@
+//! A minimal lossless WebP (VP8L) encoder, since the image crate can decode
+//! WebP but not encode it. Feature ID textures only have a handful of
+//! distinct values, so plain Huffman coding of each channel already does
+//! well. There are no transforms, color cache or backward references.
+
+use std::cmp::Reverse;
+
+use image::RgbaImage;
+
+use crate::error::{MazeError, Result};
+
+const VP8L_SIGNATURE: u8 = 0x2f;
+const MAX_SIZE: u32 = 1 << 14;
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+
+/// Green shares its alphabet with backward reference lengths and the color
+/// cache, which aren't used here
+const GREEN_ALPHABET_SIZE: usize = 256 + 24;
+const ALPHABET_SIZE: usize = 256;
+const DISTANCE_ALPHABET_SIZE: usize = 40;
+
+/// Order the code length code lengths are written in
+const CODE_LENGTH_CODE_ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15
+];
+/// Code length code for a run of 3 to 10 zeros
+const ZEROS_SHORT: u8 = 17;
+/// Code length code for a run of 11 to 138 zeros
+const ZEROS_LONG: u8 = 18;
+
+/// Encode an image as a lossless WebP file
+pub fn encode_lossless(image: &RgbaImage) -> Result<Vec<u8>> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+        return Err(MazeError::InvalidInput(format!(
+            "WebP images must be from 1 to {} pixels wide, not {}x{}",
+            MAX_SIZE,
+            width,
+            height
+        )));
+    }
+
+    let mut histograms = [
+        vec![0u32; GREEN_ALPHABET_SIZE],
+        vec![0u32; ALPHABET_SIZE],
+        vec![0u32; ALPHABET_SIZE],
+        vec![0u32; ALPHABET_SIZE],
+    ];
+    for pixel in image.pixels() {
+        let [red, green, blue, alpha] = pixel.0;
+        histograms[0][green as usize] += 1;
+        histograms[1][red as usize] += 1;
+        histograms[2][blue as usize] += 1;
+        histograms[3][alpha as usize] += 1;
+    }
+    let alpha_is_used = histograms[3][255] != width * height;
+
+    let mut writer = BitWriter::new();
+    writer.write(width - 1, 14);
+    writer.write(height - 1, 14);
+    writer.write(alpha_is_used as u32, 1);
+    // Version
+    writer.write(0, 3);
+    // No transforms, color cache or meta prefix codes
+    writer.write(0, 1);
+    writer.write(0, 1);
+    writer.write(0, 1);
+
+    // The codes are green, red, blue, alpha, then distance
+    let codes: Vec<PrefixCode> = histograms.iter()
+        .map(|histogram| PrefixCode::write(&mut writer, histogram))
+        .collect();
+    PrefixCode::write(&mut writer, &[0; DISTANCE_ALPHABET_SIZE]);
+
+    for pixel in image.pixels() {
+        let [red, green, blue, alpha] = pixel.0;
+        codes[0].write_symbol(&mut writer, green as usize);
+        codes[1].write_symbol(&mut writer, red as usize);
+        codes[2].write_symbol(&mut writer, blue as usize);
+        codes[3].write_symbol(&mut writer, alpha as usize);
+    }
+
+    let mut chunk = vec![VP8L_SIGNATURE];
+    chunk.extend(writer.finish());
+
+    let padding = chunk.len() % 2;
+    let mut webp = Vec::with_capacity(20 + chunk.len() + padding);
+    webp.extend_from_slice(b"RIFF");
+    webp.extend_from_slice(&((12 + chunk.len() + padding) as u32).to_le_bytes());
+    webp.extend_from_slice(b"WEBP");
+    webp.extend_from_slice(b"VP8L");
+    webp.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+    webp.extend_from_slice(&chunk);
+    webp.resize(webp.len() + padding, 0);
+
+    Ok(webp)
+}
+
+/// A canonical Huffman code
+struct PrefixCode {
+    lengths: Vec<u8>,
+    codes: Vec<u16>,
+}
+
+impl PrefixCode {
+    /// Choose a code for the symbols in histogram and write it to the
+    /// stream
+    fn write(writer: &mut BitWriter, histogram: &[u32]) -> Self {
+        let used: Vec<usize> = (0..histogram.len())
+            .filter(|&symbol| histogram[symbol] > 0)
+            .collect();
+
+        // One or two 8-bit symbols can use the short form
+        if used.len() <= 2 && used.iter().all(|&symbol| symbol < 256) {
+            let mut lengths = vec![0; histogram.len()];
+            // Simple code
+            writer.write(1, 1);
+            writer.write(used.len().max(1) as u32 - 1, 1);
+            // The first symbol is always 8 bits for simplicity. The smaller
+            // symbol must come first, it gets the code 0.
+            writer.write(1, 1);
+            writer.write(used.first().copied().unwrap_or(0) as u32, 8);
+            if let [_, second] = used[..] {
+                writer.write(second as u32, 8);
+                for &symbol in &used {
+                    lengths[symbol] = 1;
+                }
+            }
+
+            return Self::from_lengths(lengths);
+        }
+
+        let lengths = get_code_lengths(histogram, MAX_CODE_LENGTH);
+        Self::write_lengths(writer, &lengths);
+        Self::from_lengths(lengths)
+    }
+
+    /// Write the code lengths themselves, compressed with another prefix
+    /// code
+    fn write_lengths(writer: &mut BitWriter, lengths: &[u8]) {
+        // Runs of zeros are shortened, everything else is written as is
+        let mut tokens: Vec<(u8, u32, u8)> = Vec::new();
+        let mut i = 0;
+        while i < lengths.len() {
+            let zeros = lengths[i..].iter().take_while(|&&length| length == 0).count();
+            if zeros >= 11 {
+                let run = zeros.min(138);
+                tokens.push((ZEROS_LONG, run as u32 - 11, 7));
+                i += run;
+            } else if zeros >= 3 {
+                tokens.push((ZEROS_SHORT, zeros as u32 - 3, 3));
+                i += zeros;
+            } else {
+                tokens.push((lengths[i], 0, 0));
+                i += 1;
+            }
+        }
+
+        let mut histogram = [0u32; 19];
+        for &(token, _, _) in &tokens {
+            histogram[token as usize] += 1;
+        }
+        // A code with a single symbol would have zero bits, which not every
+        // decoder handles. Give it an unused partner instead.
+        if histogram.iter().filter(|&&count| count > 0).count() < 2 {
+            let unused = histogram.iter().position(|&count| count == 0).unwrap_or(0);
+            histogram[unused] = 1;
+        }
+        let code_length_code = Self::from_lengths(
+            get_code_lengths(&histogram, MAX_CODE_LENGTH_CODE_LENGTH)
+        );
+
+        // Normal code
+        writer.write(0, 1);
+        let count = CODE_LENGTH_CODE_ORDER.iter()
+            .rposition(|&symbol| code_length_code.lengths[symbol] > 0)
+            .map_or(4, |last| (last + 1).max(4));
+        writer.write(count as u32 - 4, 4);
+        for &symbol in &CODE_LENGTH_CODE_ORDER[..count] {
+            writer.write(code_length_code.lengths[symbol] as u32, 3);
+        }
+
+        // Every symbol's length is written, rather than stopping early
+        writer.write(0, 1);
+        for (token, extra, extra_bits) in tokens {
+            code_length_code.write_symbol(writer, token as usize);
+            writer.write(extra, extra_bits);
+        }
+    }
+
+    /// Assign canonical codes: shorter codes first, then in symbol order
+    fn from_lengths(lengths: Vec<u8>) -> Self {
+        let max_length = lengths.iter().copied().max().unwrap_or(0) as usize;
+        let mut length_counts = vec![0u16; max_length + 1];
+        for &length in lengths.iter().filter(|&&length| length > 0) {
+            length_counts[length as usize] += 1;
+        }
+
+        let mut next_code = vec![0u16; max_length + 1];
+        let mut code = 0;
+        for length in 1..=max_length {
+            code = (code + length_counts[length - 1]) << 1;
+            next_code[length] = code;
+        }
+
+        let codes = lengths.iter()
+            .map(|&length| {
+                if length == 0 {
+                    return 0;
+                }
+                let code = next_code[length as usize];
+                next_code[length as usize] += 1;
+                code
+            })
+            .collect();
+
+        Self { lengths, codes }
+    }
+
+    fn write_symbol(&self, writer: &mut BitWriter, symbol: usize) {
+        let length = self.lengths[symbol];
+        // Codes are read a bit at a time starting with the most
+        // significant bit, but the stream is packed least significant bit
+        // first
+        let code = self.codes[symbol].reverse_bits() >> (16 - length.max(1));
+        writer.write(code as u32, length);
+    }
+}
+
+/// Find Huffman code lengths for a histogram, at most max_length bits each.
+/// If the code is too deep, rare symbols are made more common until it
+/// fits, like libwebp does.
+fn get_code_lengths(histogram: &[u32], max_length: u8) -> Vec<u8> {
+    let mut min_count = 1;
+    loop {
+        let counts: Vec<u64> = histogram.iter()
+            .map(|&count| if count > 0 { count.max(min_count) as u64 } else { 0 })
+            .collect();
+        let lengths = get_huffman_lengths(&counts);
+        if lengths.iter().all(|&length| length <= max_length) {
+            return lengths;
+        }
+        min_count *= 2;
+    }
+}
+
+/// Plain Huffman code lengths, merging the two least common nodes until
+/// there's only one left
+fn get_huffman_lengths(counts: &[u64]) -> Vec<u8> {
+    // Each node is its count and the symbols under it
+    let mut nodes: Vec<(u64, Vec<usize>)> = counts.iter()
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .map(|(symbol, &count)| (count, vec![symbol]))
+        .collect();
+
+    let mut lengths = vec![0u8; counts.len()];
+    while nodes.len() > 1 {
+        nodes.sort_by_key(|node| Reverse(node.0));
+        let (count_a, symbols_a) = nodes.pop().expect("there are at least 2 nodes");
+        let (count_b, mut symbols_b) = nodes.pop().expect("there are at least 2 nodes");
+        symbols_b.extend(symbols_a);
+        for &symbol in &symbols_b {
+            lengths[symbol] += 1;
+        }
+        nodes.push((count_a + count_b, symbols_b));
+    }
+
+    lengths
+}
+
+/// Packs bits least significant bit first
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    bit_count: u8,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn write(&mut self, value: u32, bits: u8) {
+        self.buffer |= (value as u64 & ((1 << bits) - 1)) << self.bit_count;
+        self.bit_count += bits;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
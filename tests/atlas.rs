@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use image::{Rgba, RgbImage};
+
+use nested_mazes::atlas::{AtlasLayout, AtlasSource, AtlasStyle, CornerStyle};
+use nested_mazes::config::MazeConfig;
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::render::{Atlas, AtlasRenderer};
+use nested_mazes::subtree::SubtreeLayout;
+use nested_mazes::tile::Tile;
+use nested_mazes::tile_format::TileFormat;
+use nested_mazes::tileset::make_tileset_json;
+
+fn is_set(pixel: &Rgba<u8>) -> bool {
+    pixel[0] > 127
+}
+
+#[test]
+fn default_style_matches_the_test_walls() {
+    let style = AtlasStyle {
+        padding: 0,
+        ..AtlasStyle::default()
+    };
+    let walls = style.make_walls_atlas().unwrap();
+    let expected = image::open("assets/walls-test.png").unwrap().to_rgba8();
+    assert_eq!(walls.dimensions(), expected.dimensions());
+
+    // The hand-made atlas leaves tile 0 empty since no cell is ever
+    // completely walled in, and has one stray pixel in tile 10
+    let pixels = walls.enumerate_pixels()
+        .filter(|&(x, y, _)| x >= 16 && (x, y) != (16 * 10 + 15, 2));
+    for (x, y, pixel) in pixels {
+        assert_eq!(
+            is_set(pixel),
+            is_set(expected.get_pixel(x, y)),
+            "pixel ({}, {})",
+            x,
+            y
+        );
+    }
+}
+
+#[test]
+fn padding_repeats_the_edge_of_each_tile() {
+    let style = AtlasStyle {
+        tile_size: 20,
+        wall_thickness: 3,
+        padding: 2,
+        corners: CornerStyle::Round,
+        ..AtlasStyle::default()
+    };
+    let layout = style.get_layout();
+    assert_eq!(layout.tile_stride(), 24);
+    assert_eq!(layout.dimensions(), (16 * 24, 24));
+
+    let walls = style.make_walls_atlas().unwrap();
+    assert_eq!(walls.dimensions(), layout.dimensions());
+    for tile in 0..16 {
+        let start = tile * 24;
+        for i in 0..24 {
+            let inside = i.clamp(2, 21);
+            assert_eq!(walls.get_pixel(start, i), walls.get_pixel(start + 2, inside));
+            assert_eq!(walls.get_pixel(start + 23, i), walls.get_pixel(start + 21, inside));
+            assert_eq!(walls.get_pixel(start + i, 0), walls.get_pixel(start + inside, 2));
+        }
+    }
+}
+
+#[test]
+fn connections_reach_each_solution_side() {
+    let style = AtlasStyle {
+        padding: 0,
+        ..AtlasStyle::default()
+    };
+    let connections = style.make_connections_atlas().unwrap();
+    for bits in 0..16u32 {
+        let start = 16 * bits;
+        assert_eq!(is_set(connections.get_pixel(start + 8, 8)), bits != 0);
+        // up, down, left, right
+        assert_eq!(is_set(connections.get_pixel(start + 8, 0)), bits & 1 != 0);
+        assert_eq!(is_set(connections.get_pixel(start + 8, 15)), bits & 2 != 0);
+        assert_eq!(is_set(connections.get_pixel(start, 8)), bits & 4 != 0);
+        assert_eq!(is_set(connections.get_pixel(start + 15, 8)), bits & 8 != 0);
+        // Outside the path is transparent
+        assert_eq!(connections.get_pixel(start, 0)[3], 0);
+    }
+}
+
+#[test]
+fn padding_does_not_change_renders() {
+    let exits = MazeConfig::default_exits(8);
+    let config = MazeConfig::new(4, 8, exits, Algorithm::Eller.into()).unwrap();
+    let tile = Tile::generate(&config, 1, 1, 0).unwrap();
+
+    let render = |padding| {
+        let style = AtlasStyle {
+            tile_size: 12,
+            padding,
+            ..AtlasStyle::default()
+        };
+        let to_atlas = |image: image::RgbaImage| {
+            let image = image::DynamicImage::ImageRgba8(image).to_rgb8();
+            Atlas::new(image, padding).unwrap()
+        };
+        let renderer = AtlasRenderer::new(
+            to_atlas(style.make_walls_atlas().unwrap()),
+            to_atlas(style.make_connections_atlas().unwrap()),
+            12
+        ).unwrap();
+        renderer.render_grid(tile.get_grid()).unwrap()
+    };
+
+    let unpadded: RgbImage = render(0);
+    assert_eq!(unpadded, render(3));
+}
+
+#[test]
+fn atlas_layout_is_recorded_in_the_tileset() {
+    let atlases = AtlasSource::default().load().unwrap();
+    assert_eq!(atlases.layout, AtlasLayout { tile_size: 16, padding: 0 });
+
+    let generated = AtlasSource::Generated(AtlasStyle::default()).load().unwrap();
+    let exits = MazeConfig::default_exits(8);
+    let config = MazeConfig::new(1, 8, exits, Algorithm::Wilson.into()).unwrap();
+    let tileset_json = make_tileset_json(
+        &config,
+        3,
+        &generated.layout,
+        &SubtreeLayout::default(),
+        &TileFormat::default()
+    );
+    let properties = &tileset_json["metadata"]["properties"];
+    assert_eq!(properties["atlas_tile_count"], 16);
+    assert_eq!(properties["atlas_tile_size"], 16);
+    assert_eq!(properties["atlas_padding"], 1);
+}
+
+#[test]
+fn rejects_mismatched_atlases() {
+    let mismatched = AtlasSource::Files {
+        walls: PathBuf::from("assets/walls-test.png"),
+        connections: PathBuf::from("figures/how-it-works.png"),
+    };
+    assert!(mismatched.load().is_err());
+
+    let too_thick = AtlasStyle {
+        wall_thickness: 9,
+        ..AtlasStyle::default()
+    };
+    assert!(too_thick.make_walls_atlas().is_err());
+    assert!(AtlasLayout::from_dimensions(250, 16, 0).is_err());
+    assert!(AtlasLayout::from_dimensions(256, 16, 8).is_err());
+    assert_eq!(
+        AtlasLayout::from_dimensions(288, 18, 1).unwrap(),
+        AtlasLayout { tile_size: 16, padding: 1 }
+    );
+}
@@ -0,0 +1,98 @@
+//! Helpers shared by the integration tests. Not every test file uses all
+//! of them.
+#![allow(dead_code)]
+
+use std::env::temp_dir;
+use std::fs::{create_dir_all, remove_dir_all};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use nested_mazes::config::MazeConfig;
+use nested_mazes::maze_generator::{Algorithm, AlgorithmSchedule};
+use nested_mazes::subtree::SubtreeLayout;
+use nested_mazes::tile_format::TileFormat;
+use nested_mazes::tileset::{MazeTileset, TilesetAssets};
+
+/// A config with the default exits for its grid size
+pub fn make_config(
+    seed: u64,
+    grid_size: usize,
+    algorithms: impl Into<AlgorithmSchedule>
+) -> MazeConfig {
+    let exits = MazeConfig::default_exits(grid_size);
+    MazeConfig::new(seed, grid_size, exits, algorithms.into()).unwrap()
+}
+
+pub fn parse_schedule(algorithms: &str) -> AlgorithmSchedule {
+    algorithms.parse().unwrap()
+}
+
+/// An empty directory under the system temp directory that's removed when
+/// this is dropped, so a failing test doesn't leave it behind for the next
+/// run. Names should be unique across all the tests since they run in
+/// parallel.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = temp_dir().join(format!("nested-mazes-{}", name));
+        if path.exists() {
+            remove_dir_all(&path).unwrap();
+        }
+        create_dir_all(&path).unwrap();
+        Self { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // Don't panic while a failed test is already unwinding
+        let _ = remove_dir_all(&self.path);
+    }
+}
+
+/// What to generate with `generate`
+pub struct TilesetOptions {
+    pub levels: usize,
+    pub config: MazeConfig,
+    pub subtrees: SubtreeLayout,
+    pub format: TileFormat,
+}
+
+impl Default for TilesetOptions {
+    fn default() -> Self {
+        Self {
+            levels: 3,
+            config: make_config(11, 8, Algorithm::Kruskal),
+            subtrees: SubtreeLayout::default(),
+            format: TileFormat::default(),
+        }
+    }
+}
+
+/// Generate a tileset into a temporary directory
+pub fn generate(name: &str, options: TilesetOptions) -> TempDir {
+    let directory = TempDir::new(name);
+    let tileset = MazeTileset::new(
+        &directory,
+        options.levels,
+        options.config,
+        TilesetAssets::default(),
+        options.subtrees,
+        options.format,
+        2
+    ).unwrap();
+    tileset.generate().unwrap();
+
+    directory
+}
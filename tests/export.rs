@@ -0,0 +1,66 @@
+mod common;
+
+use nested_mazes::direction::Direction;
+use nested_mazes::export::{export_level, render_walls};
+use nested_mazes::grid::BIT_ORDER;
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::tile::Tile;
+
+use common::make_config;
+
+#[test]
+fn stitches_every_tile_in_place() {
+    let config = make_config(11, 8, Algorithm::Wilson);
+    let image = export_level(&config, 2).unwrap();
+    assert_eq!(image.dimensions(), (32, 32));
+
+    for y in 0..4 {
+        for x in 0..4 {
+            let tile = Tile::generate(&config, 2, x, y).unwrap();
+            let expected = tile.get_grid().to_image_bytes();
+            for (cell_y, row) in expected.chunks(3 * 8).enumerate() {
+                for (cell_x, pixel) in row.chunks(3).enumerate() {
+                    let image_x = (8 * x + cell_x) as u32;
+                    let image_y = (8 * y + cell_y) as u32;
+                    assert_eq!(&image.get_pixel(image_x, image_y).0[..], pixel);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn walls_have_one_opening_per_exit() {
+    let config = make_config(11, 8, Algorithm::Wilson);
+    let image = export_level(&config, 1).unwrap();
+    let walls = render_walls(&image, true);
+    assert_eq!(walls.dimensions(), (33, 33));
+
+    // The border is solid apart from the 2 default exits, at x = 1 on the
+    // bottom and x = 2 on the top for the root tile. Each root cell is 2x2
+    // cells at level 1.
+    let is_open = |x, y| walls.get_pixel(x, y).0 != [0, 0, 0];
+    let top: Vec<u32> = (0..33).filter(|x| is_open(*x, 0)).collect();
+    let bottom: Vec<u32> = (0..33).filter(|x| is_open(*x, 32)).collect();
+    assert_eq!(top.len(), 1);
+    assert_eq!(bottom.len(), 1);
+    assert!([9, 11].contains(&top[0]), "{:?}", top);
+    assert!([5, 7].contains(&bottom[0]), "{:?}", bottom);
+    assert!((0..33).all(|y| !is_open(0, y) && !is_open(32, y)));
+}
+
+#[test]
+fn rejects_huge_levels() {
+    assert!(export_level(&make_config(11, 8, Algorithm::Wilson), 11).is_err());
+    assert!(export_level(&make_config(11, 8, Algorithm::Wilson), 200).is_err());
+}
+
+#[test]
+fn direction_bits_follow_the_bit_order() {
+    for (position, direction) in BIT_ORDER.into_iter().enumerate() {
+        assert_eq!(direction.bit(), 1 << position, "{:?}", direction);
+    }
+    // The textures and exports depend on this, so it can't quietly change
+    assert_eq!(Direction::Up.bit(), 1);
+    assert_eq!(Direction::Right.bit(), 8);
+}
@@ -0,0 +1,116 @@
+use serde_json::Value;
+
+use nested_mazes::config::MazeConfig;
+use nested_mazes::direction::Direction;
+use nested_mazes::glb::Glb;
+use nested_mazes::grid::{BIT_ORDER, CONNECTIONS_CHANNEL, SOLUTION_CHANNEL};
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::tile::Tile;
+use nested_mazes::tile_format::TileFormat;
+
+fn make_glb() -> Glb {
+    let exits = MazeConfig::default_exits(8);
+    let config = MazeConfig::new(12, 8, exits, Algorithm::Wilson.into()).unwrap();
+    let tile = Tile::generate(&config, 2, 1, 3).unwrap();
+    Glb::parse(&tile.make_glb(&TileFormat::default()).unwrap()).unwrap()
+}
+
+fn get_values<'a>(glb: &'a Glb, table: &Value, property: &str) -> &'a [u8] {
+    let view = table["properties"][property]["values"].as_u64().unwrap() as usize;
+    let offset = glb.json["bufferViews"][view]["byteOffset"].as_u64().unwrap();
+    assert_eq!(offset % 8, 0, "{} is not aligned", property);
+    glb.get_buffer_view(view).unwrap()
+}
+
+#[test]
+fn declares_a_feature_id_per_channel() {
+    let glb = make_glb();
+    let extensions_used = glb.json["extensionsUsed"].as_array().unwrap();
+    assert!(extensions_used.contains(&"EXT_mesh_features".into()));
+    assert!(extensions_used.contains(&"EXT_structural_metadata".into()));
+
+    let feature_ids = &glb.json["meshes"][0]["primitives"][0]["extensions"]
+        ["EXT_mesh_features"]["featureIds"];
+    let channels: Vec<&Value> = feature_ids.as_array()
+        .unwrap()
+        .iter()
+        .map(|feature_id| {
+            assert_eq!(feature_id["featureCount"], 16);
+            assert_eq!(feature_id["propertyTable"], 0);
+            &feature_id["texture"]["channels"]
+        })
+        .collect();
+    assert_eq!(
+        channels,
+        [&serde_json::json!([CONNECTIONS_CHANNEL]), &serde_json::json!([SOLUTION_CHANNEL])]
+    );
+}
+
+#[test]
+fn property_table_describes_every_connection() {
+    let glb = make_glb();
+    let metadata = &glb.json["extensions"]["EXT_structural_metadata"];
+    let table = &metadata["propertyTables"][0];
+    assert_eq!(table["count"], 16);
+    let class = &metadata["schema"]["classes"][table["class"].as_str().unwrap()];
+
+    // Every property in the table is declared in the schema and vice versa
+    let mut declared: Vec<_> = class["properties"].as_object().unwrap().keys().collect();
+    let mut properties: Vec<_> = table["properties"].as_object().unwrap().keys().collect();
+    declared.sort();
+    properties.sort();
+    assert_eq!(declared, properties);
+
+    let bits = get_values(&glb, table, "bits");
+    let opening_counts = get_values(&glb, table, "opening_count");
+    let shapes = get_values(&glb, table, "shape");
+    let up = get_values(&glb, table, "up");
+    let left = get_values(&glb, table, "left");
+    let shape_names: Vec<&str> = metadata["schema"]["enums"]["shape"]["values"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| value["name"].as_str().unwrap())
+        .collect();
+
+    let get_bit = |direction: Direction| {
+        1 << BIT_ORDER.iter().position(|other| *other == direction).unwrap()
+    };
+    for feature in 0..16u8 {
+        assert_eq!(bits[feature as usize], feature);
+        assert_eq!(opening_counts[feature as usize] as u32, feature.count_ones());
+
+        let is_set = |values: &[u8]| values[feature as usize / 8] & (1 << (feature % 8)) != 0;
+        assert_eq!(is_set(up), feature & get_bit(Direction::Up) != 0);
+        assert_eq!(is_set(left), feature & get_bit(Direction::Left) != 0);
+    }
+
+    let shape_of = |directions: &[Direction]| {
+        let feature: u8 = directions.iter().map(|direction| get_bit(*direction)).sum();
+        shape_names[shapes[feature as usize] as usize]
+    };
+    assert_eq!(shape_of(&[]), "CLOSED");
+    assert_eq!(shape_of(&[Direction::Down]), "DEAD_END");
+    assert_eq!(shape_of(&[Direction::Left, Direction::Right]), "STRAIGHT");
+    assert_eq!(shape_of(&[Direction::Up, Direction::Right]), "TURN");
+    assert_eq!(
+        shape_of(&[Direction::Up, Direction::Down, Direction::Left]),
+        "T_JUNCTION"
+    );
+    assert_eq!(shape_of(&BIT_ORDER), "CROSSROADS");
+}
+
+#[test]
+fn binary_chunk_holds_image_and_table() {
+    let glb = make_glb();
+    let buffer_length = glb.json["buffers"][0]["byteLength"].as_u64().unwrap() as usize;
+    assert!(buffer_length <= glb.binary.len());
+    assert!(glb.binary.len() - buffer_length < 4);
+
+    for view in glb.json["bufferViews"].as_array().unwrap() {
+        if view["buffer"] == 0 {
+            let end = view["byteOffset"].as_u64().unwrap() + view["byteLength"].as_u64().unwrap();
+            assert!(end as usize <= buffer_length);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use std::env;
+use std::fs::{read_to_string, write};
+use std::path::PathBuf;
+
+use nested_mazes::config::MazeConfig;
+use nested_mazes::grid::Grid;
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::tile::Tile;
+use nested_mazes::tile_format::TileFormat;
+
+use common::{make_config, parse_schedule};
+
+/// Draw the connections and then the solution of a grid
+fn draw(grid: &Grid) -> String {
+    let size = grid.get_size();
+    let solution_bytes: Vec<u8> = grid
+        .to_image_bytes()
+        .chunks(3)
+        .flat_map(|pixel| [pixel[1], 0, 0])
+        .collect();
+    let solution = Grid::from_image_bytes(size, &solution_bytes);
+
+    format!("{:?}\n{:?}", grid, solution)
+}
+
+/// Compare against a file in tests/golden. Set UPDATE_GOLDEN=1 to
+/// rewrite the files after an intentional change to the generator.
+fn assert_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = read_to_string(&path).unwrap();
+    assert_eq!(
+        actual,
+        expected,
+        "{} changed, set UPDATE_GOLDEN=1 if this is intentional",
+        path.display()
+    );
+}
+
+/// Generate a tile, round trip it through a GLB and check both against the
+/// golden file
+fn check_tile(name: &str, config: &MazeConfig, level: usize, x: usize, y: usize) {
+    let tile = Tile::generate(config, level, x, y).unwrap();
+    let drawing = draw(tile.get_grid());
+    assert_golden(name, &drawing);
+
+    let glb = tile.make_glb(&TileFormat::default()).unwrap();
+    let loaded = Tile::from_glb(&glb).unwrap();
+    assert_eq!((loaded.level, loaded.x, loaded.y), (level, x, y));
+    assert_eq!(draw(loaded.get_grid()), drawing);
+}
+
+#[test]
+fn root_tile() {
+    check_tile("root.txt", &make_config(1234, 16, Algorithm::Backtracker), 0, 0, 0);
+}
+
+#[test]
+fn nested_tile() {
+    check_tile("nested.txt", &make_config(1234, 16, Algorithm::Backtracker), 3, 2, 5);
+}
+
+#[test]
+fn mixed_algorithms_small_grid() {
+    let config = make_config(42, 8, parse_schedule("prim,2=wilson,4=growing-tree:0.75"));
+    check_tile("mixed.txt", &config, 5, 17, 30);
+}
+
+#[test]
+fn round_trips_deep_transforms() {
+    let config = make_config(7, 4, Algorithm::Kruskal);
+    for level in [1, 10, 30, 45] {
+        let last = (1 << level) - 1;
+        for (x, y) in [(0, 0), (last, 0), (last / 3, last)] {
+            let tile = Tile::generate(&config, level, x, y).unwrap();
+            let loaded = Tile::from_glb(&tile.make_glb(&TileFormat::default()).unwrap()).unwrap();
+            assert_eq!((loaded.level, loaded.x, loaded.y), (level, x, y));
+            assert_eq!(loaded.get_grid().to_image_bytes(), tile.get_grid().to_image_bytes());
+        }
+    }
+}
+
+#[test]
+fn rejects_invalid_glbs() {
+    assert!(Tile::from_glb(b"not a glb").is_err());
+
+    let config = make_config(7, 4, Algorithm::Kruskal);
+    let glb = Tile::generate(&config, 1, 1, 0).unwrap().make_glb(&TileFormat::default()).unwrap();
+    assert!(Tile::from_glb(&glb[..glb.len() / 2]).is_err());
+}
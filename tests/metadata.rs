@@ -0,0 +1,76 @@
+use nested_mazes::atlas::AtlasLayout;
+use nested_mazes::config::MazeConfig;
+use nested_mazes::maze_generator::AlgorithmSchedule;
+use nested_mazes::subtree::SubtreeLayout;
+use nested_mazes::tile_format::TileFormat;
+use nested_mazes::tileset::make_tileset_json;
+
+fn make_tileset() -> serde_json::Value {
+    let algorithms: AlgorithmSchedule = "prim,2=growing-tree:0.25".parse().unwrap();
+    let exits = MazeConfig::default_exits(32);
+    let config = MazeConfig::new(u64::MAX, 32, exits, algorithms).unwrap();
+    let layout = AtlasLayout { tile_size: 24, padding: 2 };
+    make_tileset_json(
+        &config,
+        5,
+        &layout,
+        &SubtreeLayout::default(),
+        &TileFormat::default()
+    )
+}
+
+#[test]
+fn every_property_is_declared() {
+    let tileset = make_tileset();
+    let schema = &tileset["schema"];
+    let declared = schema["classes"]["tileset"]["properties"].as_object().unwrap();
+    let properties = tileset["metadata"]["properties"].as_object().unwrap();
+
+    let mut declared_names: Vec<_> = declared.keys().collect();
+    let mut names: Vec<_> = properties.keys().collect();
+    declared_names.sort();
+    names.sort();
+    assert_eq!(declared_names, names);
+
+    for (name, value) in properties {
+        let property = &declared[name];
+        let matches_type = match property["type"].as_str().unwrap() {
+            "STRING" => value.is_string(),
+            "SCALAR" => value.is_u64(),
+            "ENUM" => {
+                let enum_type = property["enumType"].as_str().unwrap();
+                let enum_names: Vec<_> = schema["enums"][enum_type]["values"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|value| &value["name"])
+                    .collect();
+                let values = value.as_array().unwrap();
+                values.len() as u64 == property["count"].as_u64().unwrap() &&
+                    values.iter().all(|value| enum_names.contains(&value))
+            },
+            other => panic!("unexpected type {}", other)
+        };
+        assert!(matches_type, "{} = {}", name, value);
+    }
+}
+
+#[test]
+fn describes_the_maze() {
+    let tileset = make_tileset();
+    let properties = &tileset["metadata"]["properties"];
+
+    assert_eq!(properties["seed"], u64::MAX.to_string());
+    assert_eq!(properties["grid_size"], 32);
+    assert_eq!(properties["level_count"], 5);
+    assert_eq!(properties["algorithm"], "prim,2=growing-tree:0.25");
+    assert_eq!(properties["atlas_tile_size"], 24);
+    assert_eq!(properties["atlas_padding"], 2);
+    assert_eq!(properties["connections_channel"], 0);
+    assert_eq!(properties["solution_channel"], 1);
+    assert_eq!(
+        properties["bit_directions"],
+        serde_json::json!(["UP", "DOWN", "LEFT", "RIGHT"])
+    );
+    assert_eq!(properties["generator_version"], env!("CARGO_PKG_VERSION"));
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use image::RgbImage;
+
+use nested_mazes::config::MazeConfig;
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::render::{Atlas, AtlasRenderer};
+use nested_mazes::tile::Tile;
+
+const FLOOR: [u8; 3] = [0, 26, 0];
+const WALL: [u8; 3] = [255, 51, 0];
+const PATH: [u8; 3] = [255, 255, 0];
+
+fn read_atlas(name: &str) -> Atlas {
+    Atlas::read(&Path::new("assets").join(name), 0).unwrap()
+}
+
+fn read_mask(name: &str) -> RgbImage {
+    image::open(Path::new("assets").join(name)).unwrap().to_rgb8()
+}
+
+#[test]
+fn matches_the_atlas_at_full_resolution() {
+    let exits = MazeConfig::default_exits(8);
+    let config = MazeConfig::new(9, 8, exits, Algorithm::Prim.into()).unwrap();
+    let tile = Tile::generate(&config, 2, 1, 3).unwrap();
+    let grid = tile.get_grid();
+
+    let renderer = AtlasRenderer::new(
+        read_atlas("walls-test.png"),
+        read_atlas("connections-test.png"),
+        16
+    ).unwrap();
+    let image = renderer.render_grid(grid).unwrap();
+    assert_eq!(image.dimensions(), (128, 128));
+
+    // The test atlases are 16x16 per tile and pure black and white, so each
+    // cell should be an exact copy of its atlas tiles
+    let walls = read_mask("walls-test.png");
+    let connections = read_mask("connections-test.png");
+    let cells = grid.to_image_bytes();
+    for (index, cell) in cells.chunks(3).enumerate() {
+        let (cell_x, cell_y) = ((index % 8) as u32, (index / 8) as u32);
+        for y in 0..16 {
+            for x in 0..16 {
+                let wall = walls.get_pixel(16 * cell[0] as u32 + x, y)[0] > 127;
+                let path = connections.get_pixel(16 * cell[1] as u32 + x, y)[0] > 127;
+                let expected = if path {
+                    PATH
+                } else if wall {
+                    WALL
+                } else {
+                    FLOOR
+                };
+
+                // north is up in the render
+                let pixel = image.get_pixel(16 * cell_x + x, 16 * (7 - cell_y) + y);
+                assert_eq!(pixel.0, expected, "cell ({}, {})", cell_x, cell_y);
+            }
+        }
+    }
+}
+
+#[test]
+fn scales_to_any_cell_size() {
+    let renderer = AtlasRenderer::new(
+        read_atlas("walls-test.png"),
+        read_atlas("connections-test.png"),
+        64
+    ).unwrap();
+
+    // A single dead end, open to the north, with the solution going through it
+    let cells = RgbImage::from_raw(1, 1, vec![1, 1, 0]).unwrap();
+    let image = renderer.render(&cells).unwrap();
+    assert_eq!(image.dimensions(), (64, 64));
+    assert_eq!(image.get_pixel(32, 0).0, PATH);
+    assert_eq!(image.get_pixel(0, 63).0, WALL);
+    assert_eq!(image.get_pixel(63, 32).0, WALL);
+    assert_eq!(image.get_pixel(32, 56).0, FLOOR);
+}
+
+#[test]
+fn rejects_bad_atlases_and_sizes() {
+    assert!(Atlas::new(RgbImage::new(100, 16), 0).is_err());
+    assert!(AtlasRenderer::new(
+        read_atlas("walls-test.png"),
+        read_atlas("connections-test.png"),
+        0
+    ).is_err());
+
+    let renderer = AtlasRenderer::new(
+        read_atlas("walls-test.png"),
+        read_atlas("connections-test.png"),
+        64
+    ).unwrap();
+    assert!(renderer.render(&RgbImage::new(512, 1)).is_err());
+}
@@ -0,0 +1,226 @@
+mod common;
+
+use std::fs::read;
+use std::path::Path;
+
+use serde_json::Value;
+
+use nested_mazes::config::MazeConfig;
+use nested_mazes::subtree::{
+    get_algorithm_value,
+    get_subtree_index,
+    SubtreeFormat,
+    SubtreeLayout,
+    TileStats,
+    MAX_SUBTREE_LEVELS
+};
+use nested_mazes::tile::Tile;
+use nested_mazes::tile_format::TileFormat;
+use nested_mazes::tileset::find_missing_files;
+
+use common::{make_config, parse_schedule, TempDir, TilesetOptions};
+
+fn make_subtree_config() -> MazeConfig {
+    make_config(5, 8, parse_schedule("kruskal,1=prim"))
+}
+
+/// The stats the generator would have written for a tile in the default
+/// format
+fn make_stats(config: &MazeConfig, tile: &Tile) -> TileStats {
+    let triangle_count = tile.make_files(&TileFormat::default()).unwrap().triangle_count;
+    TileStats::new(config, tile, triangle_count)
+}
+
+fn generate(name: &str, levels: usize, subtrees: SubtreeLayout) -> TempDir {
+    common::generate(&format!("subtree-{}", name), TilesetOptions {
+        levels,
+        config: make_subtree_config(),
+        subtrees,
+        ..TilesetOptions::default()
+    })
+}
+
+/// Split a subtree file into its JSON and binary chunks
+fn parse_subtree(bytes: &[u8]) -> (Value, Vec<u8>) {
+    assert_eq!(&bytes[0..4], b"subt");
+    assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 1);
+    let json_length = u64::from_le_bytes(bytes[8..16].try_into().unwrap()) as usize;
+    let binary_length = u64::from_le_bytes(bytes[16..24].try_into().unwrap()) as usize;
+    assert_eq!(json_length % 8, 0);
+    assert_eq!(binary_length % 8, 0);
+    assert_eq!(bytes.len(), 24 + json_length + binary_length);
+
+    let json = serde_json::from_slice(&bytes[24..24 + json_length]).unwrap();
+    (json, bytes[24 + json_length..].to_vec())
+}
+
+fn read_subtree(directory: &Path, name: &str) -> (Value, Vec<u8>) {
+    parse_subtree(&read(directory.join(name)).unwrap())
+}
+
+fn get_view<'a>(json: &Value, binary: &'a [u8], view_index: &Value) -> &'a [u8] {
+    let view = &json["bufferViews"][view_index.as_u64().unwrap() as usize];
+    let offset = view["byteOffset"].as_u64().unwrap() as usize;
+    let length = view["byteLength"].as_u64().unwrap() as usize;
+    assert_eq!(offset % 8, 0);
+
+    &binary[offset..offset + length]
+}
+
+fn get_values<'a>(json: &Value, binary: &'a [u8], property: &str) -> &'a [u8] {
+    let properties = &json["propertyTables"][0]["properties"];
+    get_view(json, binary, &properties[property]["values"])
+}
+
+#[test]
+fn subtree_index_is_level_then_morton_order() {
+    assert_eq!(get_subtree_index(0, 0, 0), 0);
+    assert_eq!(get_subtree_index(1, 0, 0), 1);
+    assert_eq!(get_subtree_index(1, 1, 0), 2);
+    assert_eq!(get_subtree_index(1, 0, 1), 3);
+    assert_eq!(get_subtree_index(1, 1, 1), 4);
+    assert_eq!(get_subtree_index(2, 0, 0), 5);
+    assert_eq!(get_subtree_index(2, 2, 1), 5 + 0b0110);
+    assert_eq!(get_subtree_index(2, 3, 3), 20);
+    assert_eq!(get_subtree_index(31, 0, 0), ((1 << 62) - 1) / 3);
+}
+
+#[test]
+#[should_panic(expected = "too deep")]
+fn subtree_index_doesnt_overflow() {
+    get_subtree_index(32, 0, 0);
+}
+
+#[test]
+fn finds_the_subtree_of_each_tile() {
+    let layout = SubtreeLayout::new(2, SubtreeFormat::Binary).unwrap();
+
+    assert_eq!(layout.get_root((1, 1, 0)), (0, 0, 0));
+    assert_eq!(layout.get_row((1, 1, 0)), 2);
+    assert_eq!(layout.get_root((3, 5, 2)), (2, 2, 1));
+    assert_eq!(layout.get_row((3, 5, 2)), 2);
+    assert_eq!(layout.get_root((4, 9, 3)), (4, 9, 3));
+    assert_eq!(layout.get_row((4, 9, 3)), 0);
+    assert_eq!(layout.get_roots(5).len(), 1 + 16 + 256);
+    assert_eq!(layout.get_file_name((2, 3, 1)), "2.3.1.subtree");
+    assert!(SubtreeLayout::new(0, SubtreeFormat::Json).is_err());
+    assert!(SubtreeLayout::new(MAX_SUBTREE_LEVELS, SubtreeFormat::Binary).is_ok());
+    assert!(SubtreeLayout::new(MAX_SUBTREE_LEVELS + 1, SubtreeFormat::Binary).is_err());
+    assert!(SubtreeLayout::new(20, SubtreeFormat::Json).is_err());
+}
+
+#[test]
+fn availability_only_subtree() {
+    let layout = SubtreeLayout::new(2, SubtreeFormat::Binary).unwrap();
+    let files = layout.make_files((0, 0, 0), 4, None);
+    assert_eq!(files.len(), 1);
+    let (json, binary) = parse_subtree(&files[0].1);
+
+    assert_eq!(json["tileAvailability"]["constant"], 1);
+    assert_eq!(json["contentAvailability"][0]["constant"], 1);
+    assert_eq!(json["childSubtreeAvailability"]["constant"], 1);
+    assert!(json.get("tileMetadata").is_none());
+    assert!(binary.is_empty());
+
+    // The subtrees at the bottom have no children
+    let (json, _) = parse_subtree(&layout.make_files((2, 1, 3), 4, None)[0].1);
+    assert_eq!(json["childSubtreeAvailability"]["constant"], 0);
+}
+
+#[test]
+fn generated_subtrees_have_tile_metadata() {
+    let layout = SubtreeLayout::new(2, SubtreeFormat::Binary).unwrap();
+    let directory = generate("binary", 3, layout);
+    let config = make_subtree_config();
+
+    let tileset: Value = serde_json::from_slice(
+        &read(directory.join("tileset.json")).unwrap()
+    ).unwrap();
+    let implicit_tiling = &tileset["root"]["implicitTiling"];
+    assert_eq!(implicit_tiling["subtreeLevels"], 2);
+    assert_eq!(implicit_tiling["subtrees"]["uri"], "{level}.{x}.{y}.subtree");
+    assert!(find_missing_files(&directory).unwrap().is_empty());
+
+    let (root_json, root_binary) = read_subtree(&directory, "0.0.0.subtree");
+    let (leaf_json, leaf_binary) = read_subtree(&directory, "2.3.2.subtree");
+    let tiles: Vec<(usize, TileStats)> = [(0, 0, 0), (1, 1, 0), (1, 0, 1)]
+        .into_iter()
+        .map(|(level, x, y)| {
+            let path = directory.join(format!("tiles/{}.{}.{}.glb", level, x, y));
+            let tile = Tile::read(&path).unwrap();
+            (layout.get_row((level, x, y)), make_stats(&config, &tile))
+        })
+        .collect();
+    let leaf = Tile::read(&directory.join("tiles/2.3.2.glb")).unwrap();
+    let leaf_stats = make_stats(&config, &leaf);
+
+    assert_eq!(root_json["childSubtreeAvailability"]["constant"], 1);
+    assert_eq!(root_json["tileMetadata"], 0);
+    let table = &root_json["propertyTables"][0];
+    assert_eq!(table["class"], "tile");
+    assert_eq!(table["count"], 1 + 4);
+
+    let exit_counts = get_values(&root_json, &root_binary, "exit_count");
+    let solution_lengths = get_values(&root_json, &root_binary, "solution_length");
+    let dead_end_counts = get_values(&root_json, &root_binary, "dead_end_count");
+    let algorithms = get_values(&root_json, &root_binary, "algorithm");
+    let on_solution = get_values(&root_json, &root_binary, "on_solution");
+    let triangle_counts = get_values(&root_json, &root_binary, "triangle_count");
+    assert_eq!(exit_counts.len(), 2 * 5);
+    assert_eq!(on_solution.len(), 1);
+
+    for (row, stats) in tiles {
+        let u16_at = |values: &[u8]| u16::from_le_bytes(
+            values[2 * row..2 * row + 2].try_into().unwrap()
+        );
+        let u32_at = |values: &[u8]| u32::from_le_bytes(
+            values[4 * row..4 * row + 4].try_into().unwrap()
+        );
+
+        assert_eq!(u16_at(exit_counts), stats.exit_count);
+        assert_eq!(u32_at(solution_lengths), stats.solution_length);
+        assert_eq!(u32_at(dead_end_counts), stats.dead_end_count);
+        assert_eq!(algorithms[row], get_algorithm_value(stats.algorithm));
+        assert_eq!(on_solution[row / 8] & (1 << (row % 8)) != 0, stats.on_solution);
+        assert_eq!(u32_at(triangle_counts), stats.triangle_count);
+        assert_eq!(stats.triangle_count, 2);
+    }
+
+    // The root always has the solution and both exits, and the algorithm
+    // changes at level 1
+    let root = u32::from_le_bytes(solution_lengths[0..4].try_into().unwrap());
+    assert!(root > 0);
+    assert!(u16::from_le_bytes(exit_counts[0..2].try_into().unwrap()) >= 2);
+    assert_eq!(on_solution[0] & 1, 1);
+    assert_eq!(algorithms[0], 2);
+    assert!(algorithms[1..].iter().all(|&algorithm| algorithm == 1));
+
+    // The tileset ends halfway through the bottom subtrees, so only their
+    // root is available
+    assert_eq!(leaf_json["childSubtreeAvailability"]["constant"], 0);
+    let availability = &leaf_json["tileAvailability"];
+    assert_eq!(availability["availableCount"], 1);
+    assert_eq!(get_view(&leaf_json, &leaf_binary, &availability["bitstream"]), [1]);
+    assert_eq!(leaf_json["contentAvailability"][0], *availability);
+    assert_eq!(leaf_json["propertyTables"][0]["count"], 1);
+    let leaf_exits = get_values(&leaf_json, &leaf_binary, "exit_count");
+    assert_eq!(u16::from_le_bytes(leaf_exits.try_into().unwrap()), leaf_stats.exit_count);
+}
+
+#[test]
+fn json_subtrees_use_an_external_buffer() {
+    let layout = SubtreeLayout::new(1, SubtreeFormat::Json).unwrap();
+    let directory = generate("json", 2, layout);
+    let missing = find_missing_files(&directory).unwrap();
+    let subtree: Value = serde_json::from_slice(
+        &read(directory.join("1.1.0.subtree.json")).unwrap()
+    ).unwrap();
+    let buffer = read(directory.join("1.1.0.subtree.bin")).unwrap();
+
+    assert!(missing.is_empty(), "{:?}", missing);
+    assert_eq!(subtree["buffers"][0]["uri"], "1.1.0.subtree.bin");
+    assert_eq!(subtree["buffers"][0]["byteLength"], buffer.len());
+    assert_eq!(subtree["tileAvailability"]["constant"], 1);
+    assert_eq!(subtree["childSubtreeAvailability"]["constant"], 0);
+    assert_eq!(subtree["propertyTables"][0]["count"], 1);
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use image::RgbImage;
+
+use nested_mazes::export::export_region;
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::svg::{render_svg, SvgStyle};
+
+use common::make_config;
+
+/// Pull the d attribute out of the path with the given stroke color
+fn find_path<'a>(svg: &'a str, color: &str) -> Option<&'a str> {
+    let line = svg.lines().find(|line| {
+        line.contains("<path") && line.contains(&format!("stroke=\"{}\"", color))
+    })?;
+    let start = line.find("d=\"")? + 3;
+    let end = start + line[start..].find('"')?;
+    Some(&line[start..end])
+}
+
+#[test]
+fn draws_merged_walls_and_exits() {
+    // A 2x1 corridor with an opening on the left
+    let left = 8 | 4;
+    let right = 4;
+    let cells = RgbImage::from_raw(2, 1, vec![left, 0, 0, right, 0, 0]).unwrap();
+    let svg = render_svg(&cells, &SvgStyle::default());
+
+    let walls = find_path(&svg, "black").unwrap();
+    // top and bottom walls are one line each, plus the right end
+    assert_eq!(walls, "M0 10L20 10 M0 0L20 0 M20 10L20 0");
+    assert!(find_path(&svg, "red").is_none());
+}
+
+#[test]
+fn draws_solution_and_styles() {
+    let config = make_config(5, 8, Algorithm::Backtracker);
+    let cells = export_region(&config, 2, 1..3, 0..1).unwrap();
+    assert_eq!(cells.dimensions(), (16, 8));
+
+    let style = SvgStyle {
+        cell_size: 4.0,
+        wall_color: "#123456".to_string(),
+        wall_width: 0.5,
+        solution_color: Some("blue".to_string()),
+        solution_width: 1.5,
+        background_color: Some("a\"b".to_string()),
+    };
+    let svg = render_svg(&cells, &style);
+
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.contains("width=\"67\" height=\"35\""));
+    assert!(svg.contains("fill=\"a&quot;b\""));
+    assert!(svg.contains("stroke-width=\"0.5\""));
+    assert!(find_path(&svg, "#123456").is_some());
+
+    let has_solution = cells.pixels().any(|pixel| pixel[1] != 0);
+    assert_eq!(find_path(&svg, "blue").is_some(), has_solution);
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use std::fs::write;
+use std::path::{Path, PathBuf};
+
+use image::{GenericImage, GenericImageView, RgbaImage};
+
+use nested_mazes::atlas::{AtlasLayout, AtlasSource};
+use nested_mazes::direction::Direction;
+use nested_mazes::theme::Theme;
+
+use common::TempDir;
+
+const SHUFFLED_ORDER: [&str; 4] = ["right", "up", "left", "down"];
+
+/// Rearrange one of the single-row test atlases into a 4x4 grid using
+/// SHUFFLED_ORDER for the bits
+fn shuffle_atlas(source: &str, destination: &Path) {
+    let atlas = image::open(source).unwrap().to_rgba8();
+    let mut shuffled = RgbaImage::new(64, 64);
+    for theme_index in 0..16u32 {
+        // Convert back to up, down, left, right
+        let bits: u32 = SHUFFLED_ORDER.iter()
+            .enumerate()
+            .filter(|(theme_bit, _)| theme_index & (1 << theme_bit) != 0)
+            .map(|(_, name)| match *name {
+                "up" => 1,
+                "down" => 2,
+                "left" => 4,
+                _ => 8,
+            })
+            .sum();
+        let tile = atlas.view(16 * bits, 0, 16, 16);
+        shuffled.copy_from(&*tile, 16 * (theme_index % 4), 16 * (theme_index / 4))
+            .unwrap();
+    }
+    shuffled.save(destination).unwrap();
+}
+
+fn write_manifest(directory: &Path, extra: &str) {
+    let manifest = format!(
+        r#"{{"name": "test", "walls": "walls.png", "connections": "connections.png"{}}}"#,
+        extra
+    );
+    write(directory.join("theme.json"), manifest).unwrap();
+}
+
+#[test]
+fn themes_are_rearranged_into_the_standard_layout() {
+    let directory = TempDir::new("theme-shuffled");
+    shuffle_atlas("assets/walls-test.png", &directory.join("walls.png"));
+    shuffle_atlas("assets/connections-test.png", &directory.join("connections.png"));
+    write_manifest(
+        &directory,
+        r#", "columns": 4, "bit_order": ["right", "up", "left", "down"]"#
+    );
+
+    let theme = Theme::read(&directory).unwrap();
+    assert_eq!(theme.name, "test");
+    assert_eq!(theme.columns, 4);
+    assert_eq!(
+        theme.bit_order,
+        [Direction::Right, Direction::Up, Direction::Left, Direction::Down]
+    );
+
+    let atlases = AtlasSource::Theme(directory.join("theme.json")).load().unwrap();
+
+    assert_eq!(atlases.layout, AtlasLayout { tile_size: 16, padding: 0 });
+    let decode = |png: &[u8]| image::load_from_memory(png).unwrap().to_rgba8();
+    let expected_walls = image::open("assets/walls-test.png").unwrap().to_rgba8();
+    let expected_connections = image::open("assets/connections-test.png")
+        .unwrap()
+        .to_rgba8();
+    assert!(decode(&atlases.walls_png) == expected_walls);
+    assert!(decode(&atlases.connections_png) == expected_connections);
+}
+
+#[test]
+fn bundled_theme_loads() {
+    let atlases = AtlasSource::Theme(PathBuf::from("assets/themes/pixel-art"))
+        .load()
+        .unwrap();
+    assert_eq!(atlases.layout, AtlasLayout { tile_size: 8, padding: 0 });
+}
+
+#[test]
+fn rejects_bad_themes() {
+    let directory = TempDir::new("theme-bad");
+    shuffle_atlas("assets/walls-test.png", &directory.join("walls.png"));
+    shuffle_atlas("assets/walls-test.png", &directory.join("connections.png"));
+
+    let load = |extra: &str| {
+        write_manifest(&directory, extra);
+        Theme::read(&directory).and_then(|theme| theme.load())
+    };
+
+    // The images are a 4x4 grid, not a single row
+    assert!(load("").is_err());
+    assert!(load(r#", "columns": 4"#).is_ok());
+    assert!(load(r#", "columns": 3"#).is_err());
+    assert!(load(r#", "columns": 4, "padding": 8"#).is_err());
+    assert!(load(r#", "columns": 4, "bit_order": ["up", "up", "left", "down"]"#).is_err());
+    assert!(load(r#", "columns": 4, "bit_order": ["up", "down", "left"]"#).is_err());
+    write(directory.join("theme.json"), r#"{"name": "test"}"#).unwrap();
+    assert!(Theme::read(&directory).is_err());
+}
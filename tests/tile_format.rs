@@ -0,0 +1,318 @@
+mod common;
+
+use std::fs::{read, remove_file};
+
+use serde_json::Value;
+
+use nested_mazes::atlas::AtlasLayout;
+use nested_mazes::geometry::make_buffer;
+use nested_mazes::glb::Glb;
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::subtree::SubtreeLayout;
+use nested_mazes::tile::Tile;
+use nested_mazes::tile_format::{
+    ChannelLayout,
+    ContentLayout,
+    ImageContainer,
+    TileFormat,
+    TileGeometry
+};
+use nested_mazes::tileset::{find_missing_files, make_tileset_json};
+use nested_mazes::verify::verify_tileset;
+use nested_mazes::walls::WallStyle;
+
+use common::{make_config, TempDir, TilesetOptions};
+
+const LAYOUTS: [ChannelLayout; 2] = [
+    ChannelLayout::Rgb8,
+    ChannelLayout::R8Packed
+];
+const CONTAINERS: [ImageContainer; 2] = [
+    ImageContainer::Png,
+    ImageContainer::WebP
+];
+
+/// Every combination of layout and container that `TileFormat::validate`
+/// accepts
+fn valid_formats() -> Vec<TileFormat> {
+    LAYOUTS.into_iter()
+        .flat_map(|layout| CONTAINERS.map(|container| TileFormat {
+            layout,
+            container,
+            ..TileFormat::default()
+        }))
+        .filter(|format| format.validate().is_ok())
+        .collect()
+}
+
+fn make_tile() -> Tile {
+    Tile::generate(&make_config(7, 16, Algorithm::Wilson), 3, 5, 2).unwrap()
+}
+
+fn generate(name: &str, format: TileFormat) -> TempDir {
+    common::generate(
+        &format!("tile-format-{}", name),
+        TilesetOptions { format, ..TilesetOptions::default() }
+    )
+}
+
+fn get_feature_ids(glb: &Glb) -> &Vec<Value> {
+    glb.json["meshes"][0]["primitives"][0]["extensions"]["EXT_mesh_features"]["featureIds"]
+        .as_array()
+        .unwrap()
+}
+
+#[test]
+fn every_format_round_trips() {
+    let tile = make_tile();
+    let expected = tile.get_grid().to_image_bytes();
+
+    for format in valid_formats() {
+        let glb = tile.make_glb(&format).unwrap();
+        let decoded = Tile::from_glb(&glb).unwrap();
+
+        assert_eq!((decoded.level, decoded.x, decoded.y), (3, 5, 2), "{:?}", format);
+        assert_eq!(decoded.get_grid().to_image_bytes(), expected, "{:?}", format);
+    }
+}
+
+#[test]
+fn feature_ids_are_never_in_alpha() {
+    let tile = make_tile();
+
+    // Viewers may premultiply alpha, so every pixel has to be opaque
+    for format in valid_formats() {
+        let glb = Glb::parse(&tile.make_glb(&format).unwrap()).unwrap();
+        let (bytes, _) = glb.get_texture_image(0).unwrap();
+        let image = image::load_from_memory(bytes).unwrap().to_rgba8();
+        assert!(image.pixels().all(|pixel| pixel[3] == 255), "{:?}", format);
+    }
+}
+
+#[test]
+fn every_layout_encodes_differently() {
+    // The root tile always has part of the solution. Without it, the packed
+    // layout is the same as red alone.
+    let tile = Tile::generate(&make_config(7, 16, Algorithm::Wilson), 0, 0, 0).unwrap();
+    assert!(tile.get_grid().to_image_bytes().chunks(3).any(|pixel| pixel[1] != 0));
+
+    // A layout that writes the same bytes as another one isn't worth having
+    for container in CONTAINERS {
+        let images: Vec<Vec<u8>> = LAYOUTS.into_iter()
+            .map(|layout| {
+                let format = TileFormat { layout, container, ..TileFormat::default() };
+                format.encode_image(tile.get_grid()).unwrap()
+            })
+            .collect();
+        for (index, image) in images.iter().enumerate() {
+            assert!(!images[..index].contains(image), "{:?} {:?}", container, LAYOUTS[index]);
+        }
+    }
+}
+
+#[test]
+fn webp_needs_an_extension() {
+    let tile = make_tile();
+
+    let format = TileFormat { container: ImageContainer::WebP, ..TileFormat::default() };
+    let glb = Glb::parse(&tile.make_glb(&format).unwrap()).unwrap();
+    let extension = "EXT_texture_webp";
+    assert_eq!(glb.json["images"][0]["mimeType"], "image/webp");
+    assert!(glb.json["extensionsUsed"].as_array().unwrap().contains(&extension.into()));
+    assert_eq!(glb.json["extensionsRequired"], serde_json::json!([extension]));
+    assert_eq!(glb.json["textures"][0]["extensions"][extension]["source"], 0);
+    assert!(glb.json["textures"][0].get("source").is_none());
+
+    let glb = Glb::parse(&tile.make_glb(&TileFormat::default()).unwrap()).unwrap();
+    assert_eq!(glb.json["images"][0]["mimeType"], "image/png");
+    assert!(glb.json.get("extensionsRequired").is_none());
+}
+
+#[test]
+fn webp_is_readable_by_the_image_crate() {
+    let tile = make_tile();
+    let format = TileFormat {
+        layout: ChannelLayout::R8Packed,
+        container: ImageContainer::WebP,
+        ..TileFormat::default()
+    };
+    let glb = Glb::parse(&tile.make_glb(&format).unwrap()).unwrap();
+    let (bytes, _) = glb.get_texture_image(0).unwrap();
+
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(&bytes[8..16], b"WEBPVP8L");
+    let image = image::load_from_memory(bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (16, 16));
+}
+
+#[test]
+fn basisu_is_only_declared_for_basis_images() {
+    let tile = make_tile();
+
+    for format in valid_formats() {
+        let glb = Glb::parse(&tile.make_glb(&format).unwrap()).unwrap();
+        let textures = glb.json["textures"].as_array().unwrap();
+        for (index, texture) in textures.iter().enumerate() {
+            if texture["extensions"].get("KHR_texture_basisu").is_none() {
+                continue;
+            }
+
+            // Basis Universal data is always a KTX2 file with an
+            // undefined vkFormat, the real format is in the payload
+            let (bytes, mime_type) = glb.get_texture_image(index).unwrap();
+            assert_eq!(mime_type, "image/ktx2", "{:?}", format);
+            assert_eq!(&bytes[0..12], b"\xabKTX 20\xbb\r\n\x1a\n", "{:?}", format);
+            let vk_format = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
+            assert_eq!(vk_format, 0, "{:?}", format);
+        }
+    }
+}
+
+#[test]
+fn packed_layout_has_a_single_feature_id() {
+    let tile = make_tile();
+    let format = TileFormat {
+        layout: ChannelLayout::R8Packed,
+        ..TileFormat::default()
+    };
+    let glb = Glb::parse(&tile.make_glb(&format).unwrap()).unwrap();
+
+    let feature_ids = get_feature_ids(&glb);
+    assert_eq!(feature_ids.len(), 1);
+    assert_eq!(feature_ids[0]["featureCount"], 256);
+    assert_eq!(feature_ids[0]["texture"]["channels"], serde_json::json!([0]));
+    let table = feature_ids[0]["propertyTable"].as_u64().unwrap() as usize;
+    let metadata = &glb.json["extensions"]["EXT_structural_metadata"];
+    assert_eq!(metadata["propertyTables"][table]["count"], 256);
+
+    // Each value is the connection bits with the solution bits above them
+    let get_values = |property: &str| {
+        let view = metadata["propertyTables"][table]["properties"][property]["values"]
+            .as_u64()
+            .unwrap();
+        glb.get_buffer_view(view as usize).unwrap()
+    };
+    let connection_bits = get_values("connection_bits");
+    let solution_bits = get_values("solution_bits");
+    for value in 0..256 {
+        assert_eq!(connection_bits[value] as usize, value & 0x0f);
+        assert_eq!(solution_bits[value] as usize, value >> 4);
+    }
+}
+
+#[test]
+fn metadata_says_where_the_bits_are() {
+    let config = make_config(3, 8, Algorithm::Kruskal);
+    let get_metadata = |layout, container| {
+        let format = TileFormat { layout, container, ..TileFormat::default() };
+        let tileset = make_tileset_json(
+            &config,
+            3,
+            &AtlasLayout { tile_size: 24, padding: 2 },
+            &SubtreeLayout::default(),
+            &format
+        );
+        let properties = &tileset["metadata"]["properties"];
+        (
+            properties["connections_channel"].as_u64().unwrap(),
+            properties["solution_channel"].as_u64().unwrap(),
+            properties["solution_shift"].as_u64().unwrap()
+        )
+    };
+
+    assert_eq!(get_metadata(ChannelLayout::Rgb8, ImageContainer::Png), (0, 1, 0));
+    assert_eq!(get_metadata(ChannelLayout::Rgb8, ImageContainer::WebP), (0, 1, 0));
+    assert_eq!(get_metadata(ChannelLayout::R8Packed, ImageContainer::WebP), (0, 0, 4));
+}
+
+#[test]
+fn embedded_glb_is_self_contained() {
+    let tile = make_tile();
+    let format = TileFormat { content: ContentLayout::Embedded, ..TileFormat::default() };
+    let glb = Glb::parse(&tile.make_glb(&format).unwrap()).unwrap();
+
+    let buffers = glb.json["buffers"].as_array().unwrap();
+    assert_eq!(buffers.len(), 1);
+    assert!(buffers[0].get("uri").is_none());
+    for view in glb.json["bufferViews"].as_array().unwrap() {
+        assert_eq!(view["buffer"], 0);
+    }
+
+    // The quad comes first, then the image
+    let geometry = make_buffer();
+    assert_eq!(&glb.binary[..geometry.len()], &geometry[..]);
+    assert_eq!(glb.json["bufferViews"][4]["byteOffset"], geometry.len());
+    let decoded = Tile::from_glb(&tile.make_glb(&format).unwrap()).unwrap();
+    assert_eq!(decoded.get_grid().to_image_bytes(), tile.get_grid().to_image_bytes());
+}
+
+#[test]
+fn embedded_tileset_has_no_shared_files() {
+    let format = TileFormat { content: ContentLayout::Embedded, ..TileFormat::default() };
+    let directory = generate("embedded", format);
+    let missing = find_missing_files(&directory).unwrap();
+    let has_geometry = directory.join("tiles/geometry.bin").exists();
+    let report = verify_tileset(&directory).unwrap();
+
+    assert!(missing.is_empty(), "{:?}", missing);
+    assert!(!has_geometry);
+    assert!(report.violations.is_empty(), "{:?}", report.violations);
+}
+
+#[test]
+fn gltf_tileset_has_separate_images() {
+    let format = TileFormat {
+        container: ImageContainer::WebP,
+        content: ContentLayout::Gltf,
+        ..TileFormat::default()
+    };
+    let directory = generate("gltf", format);
+    let tileset: Value = serde_json::from_slice(
+        &read(directory.join("tileset.json")).unwrap()
+    ).unwrap();
+    let gltf: Value = serde_json::from_slice(
+        &read(directory.join("tiles/1.1.0.gltf")).unwrap()
+    ).unwrap();
+    let properties = read(directory.join("tiles/properties.bin")).unwrap();
+    let missing = find_missing_files(&directory).unwrap();
+    let report = verify_tileset(&directory).unwrap();
+
+    // Images are checked like any other file
+    remove_file(directory.join("tiles/2.3.1.webp")).unwrap();
+    let missing_image = find_missing_files(&directory).unwrap();
+
+    assert_eq!(tileset["root"]["content"]["uri"], "tiles/{level}.{x}.{y}.gltf");
+    assert_eq!(gltf["images"][0]["uri"], "1.1.0.webp");
+    assert_eq!(gltf["images"][0]["mimeType"], "image/webp");
+    assert_eq!(gltf["buffers"][0]["uri"], "properties.bin");
+    assert_eq!(gltf["buffers"][0]["byteLength"], properties.len());
+    assert_eq!(gltf["buffers"][1]["uri"], "geometry.bin");
+    assert!(missing.is_empty(), "{:?}", missing);
+    assert_eq!(report.tiles_checked, 1 + 4 + 16);
+    assert!(report.violations.is_empty(), "{:?}", report.violations);
+    assert_eq!(missing_image, [directory.join("tiles/2.3.1.webp")]);
+}
+
+#[test]
+fn single_files_match_the_whole_content() {
+    let tile = make_tile();
+    let contents = [ContentLayout::SharedGeometry, ContentLayout::Embedded, ContentLayout::Gltf];
+    let geometries = [TileGeometry::Quad, TileGeometry::Walls(WallStyle::default())];
+    for content in contents {
+        for geometry in geometries {
+            let format = TileFormat { content, geometry, ..TileFormat::default() };
+            let files = tile.make_files(&format).unwrap().files;
+            for (name, bytes) in &files {
+                let file = tile.make_file(&format, name).unwrap();
+                assert_eq!(file.as_ref(), Some(bytes), "{:?} {}", format, name);
+            }
+
+            // Other tiles' files, and files this format doesn't have
+            for name in ["0.0.0.glb", "3.5.2.png", "3.5.2.bin", "3.5.2.gltf.bin"] {
+                let expected = files.iter().any(|(file_name, _)| file_name == name);
+                let file = tile.make_file(&format, name).unwrap();
+                assert_eq!(file.is_some(), expected, "{:?} {}", format, name);
+            }
+        }
+    }
+}
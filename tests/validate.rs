@@ -0,0 +1,216 @@
+mod common;
+
+use nested_mazes::config::{MazeConfig, RootExit};
+use nested_mazes::direction::Direction;
+use nested_mazes::grid::{Grid, CONNECTIONS_CHANNEL};
+use nested_mazes::grid_coords::GridCoords;
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::tile::Tile;
+use nested_mazes::validate::{
+    check_grid,
+    check_level,
+    check_refinement,
+    check_seam,
+    validate_maze
+};
+
+use common::{make_config, parse_schedule};
+
+fn assert_valid(config: &MazeConfig, levels: usize) {
+    let violations = validate_maze(config, levels).unwrap();
+    let messages: Vec<String> = violations
+        .iter()
+        .take(10)
+        .map(|violation| violation.to_string())
+        .collect();
+    assert!(
+        violations.is_empty(),
+        "{} violations with {}, e.g.\n{}",
+        violations.len(),
+        config.algorithms,
+        messages.join("\n")
+    );
+}
+
+#[test]
+fn every_algorithm_is_valid() {
+    for algorithm in Algorithm::ALL {
+        for seed in [1, 2, 3] {
+            assert_valid(&make_config(seed, 8, algorithm), 4);
+        }
+    }
+}
+
+#[test]
+fn default_grid_size_is_valid() {
+    assert_valid(&make_config(1234, 16, Algorithm::Backtracker), 4);
+}
+
+#[test]
+fn small_and_large_grids_are_valid() {
+    assert_valid(&make_config(5, 2, Algorithm::Wilson), 7);
+    assert_valid(&make_config(5, 64, Algorithm::Kruskal), 2);
+}
+
+#[test]
+fn mixed_schedule_is_valid() {
+    let algorithms = parse_schedule("backtracker,1=eller,2=growing-tree:0.9,3=sidewinder");
+    assert_valid(&make_config(99, 8, algorithms), 5);
+}
+
+#[test]
+fn exits_on_every_side_are_valid() {
+    let exits = vec![
+        RootExit { direction: Direction::Left, index: 0 },
+        RootExit { direction: Direction::Right, index: 7 },
+    ];
+    let config = MazeConfig::new(7, 8, exits, Algorithm::Prim.into()).unwrap();
+    assert_valid(&config, 4);
+}
+
+#[test]
+fn odd_exit_counts_are_rejected() {
+    let mut exits = vec![
+        RootExit { direction: Direction::Down, index: 3 },
+        RootExit { direction: Direction::Up, index: 5 },
+        RootExit { direction: Direction::Left, index: 2 },
+    ];
+    // One exit would be left without a partner, so its path would dead-end
+    assert!(MazeConfig::new(1, 8, exits.clone(), Algorithm::Prim.into()).is_err());
+
+    exits.push(RootExit { direction: Direction::Right, index: 6 });
+    let config = MazeConfig::new(1, 8, exits, Algorithm::Prim.into()).unwrap();
+    assert_valid(&config, 3);
+}
+
+#[test]
+fn deep_tiles_are_valid() {
+    let config = make_config(42, 8, Algorithm::HuntAndKill);
+    let level = config.max_levels() - 1;
+    let x = (1 << (level - 1)) + 5;
+    let y = (1 << (level - 2)) + 3;
+
+    let parent = Tile::generate(&config, level - 1, x >> 1, y >> 1).unwrap();
+    let tile = Tile::generate(&config, level, x, y).unwrap();
+    let right = Tile::generate(&config, level, x + 1, y).unwrap();
+    let up = Tile::generate(&config, level, x, y + 1).unwrap();
+
+    assert!(check_grid(tile.get_grid()).is_empty());
+    assert!(check_refinement(&parent, &tile).is_empty());
+    assert!(check_seam(&tile, &right, Direction::Right).is_empty());
+    assert!(check_seam(&tile, &up, Direction::Up).is_empty());
+    assert!(check_seam(&right, &tile, Direction::Left).is_empty());
+}
+
+#[test]
+fn detects_loops() {
+    let mut grid = Grid::new(2);
+    grid.mark_boundaries();
+    grid.mark_exit(Direction::Down, 0);
+    grid.connect(GridCoords { x: 0, y: 0 }, GridCoords { x: 1, y: 0 });
+    grid.connect(GridCoords { x: 1, y: 0 }, GridCoords { x: 1, y: 1 });
+    grid.connect(GridCoords { x: 1, y: 1 }, GridCoords { x: 0, y: 1 });
+    grid.connect(GridCoords { x: 0, y: 1 }, GridCoords { x: 0, y: 0 });
+
+    let messages = check_grid(&grid);
+    assert!(messages.iter().any(|message| message.contains("loop")));
+}
+
+#[test]
+fn detects_unreachable_cells() {
+    let mut grid = Grid::new(2);
+    grid.mark_boundaries();
+    grid.mark_exit(Direction::Down, 0);
+    grid.connect(GridCoords { x: 0, y: 0 }, GridCoords { x: 0, y: 1 });
+
+    let messages = check_grid(&grid);
+    assert!(messages.iter().any(|message| message.contains("2 cells can't be reached")));
+}
+
+#[test]
+fn detects_solution_dead_ends() {
+    let mut grid = Grid::new(2);
+    grid.mark_boundaries();
+    grid.mark_exit(Direction::Down, 0);
+    grid.connect(GridCoords { x: 0, y: 0 }, GridCoords { x: 1, y: 0 });
+    grid.connect(GridCoords { x: 1, y: 0 }, GridCoords { x: 1, y: 1 });
+    grid.connect(GridCoords { x: 1, y: 1 }, GridCoords { x: 0, y: 1 });
+    grid.connect_solution(GridCoords { x: 0, y: 0 }, GridCoords { x: 1, y: 0 });
+
+    let messages = check_grid(&grid);
+    assert_eq!(messages.len(), 1, "{:?}", messages);
+    assert!(messages[0].contains("dead-ends"));
+}
+
+#[test]
+fn detects_mismatched_neighbors() {
+    let config = make_config(3, 8, Algorithm::Backtracker);
+    let tile = Tile::generate(&config, 2, 1, 1).unwrap();
+    let far_tile = Tile::generate(&config, 2, 3, 1).unwrap();
+    let other_parent = Tile::generate(&config, 1, 1, 0).unwrap();
+
+    assert_eq!(check_seam(&tile, &far_tile, Direction::Right).len(), 1);
+    assert_eq!(check_refinement(&other_parent, &tile).len(), 1);
+}
+
+/// Level 1 of a maze, with the connection across the seam between the two
+/// bottom tiles at the given row opened or closed. Both sides are changed,
+/// so the seam itself still matches.
+fn make_level_with_seam(config: &MazeConfig, row: usize, open: bool) -> Vec<Tile> {
+    let mut tiles: Vec<Tile> = [(0, 0), (1, 0), (0, 1), (1, 1)]
+        .into_iter()
+        .map(|(x, y)| Tile::generate(config, 1, x, y).unwrap())
+        .collect();
+
+    let size = config.grid_size;
+    for (index, x, direction) in [(0, size - 1, Direction::Right), (1, 0, Direction::Left)] {
+        let tile = &tiles[index];
+        let mut bytes = tile.get_grid().to_image_bytes();
+        let connections = &mut bytes[3 * (row * size + x) + CONNECTIONS_CHANNEL];
+        if open {
+            *connections |= direction.bit();
+        } else {
+            *connections &= !direction.bit();
+        }
+        let grid = Grid::from_image_bytes(size, &bytes);
+        tiles[index] = Tile::from_grid(tile.level, tile.x, tile.y, grid);
+    }
+
+    tiles
+}
+
+#[test]
+fn detects_loops_and_gaps_across_tiles() {
+    let config = make_config(3, 8, Algorithm::Kruskal);
+    let untouched: Vec<Tile> = [(0, 0), (1, 0)]
+        .into_iter()
+        .map(|(x, y)| Tile::generate(&config, 1, x, y).unwrap())
+        .collect();
+    let is_open = |row| untouched[0].get_grid()
+        .get_cell(GridCoords { x: 7, y: row })
+        .is_connected(Direction::Right);
+    let wall = (0..8).find(|&row| !is_open(row)).unwrap();
+    let hallway = (0..8).find(|&row| is_open(row)).unwrap();
+
+    // Setting a row to what it already was changes nothing
+    let tiles = make_level_with_seam(&config, hallway, true);
+    assert!(check_level(&tiles.iter().collect::<Vec<_>>()).is_empty());
+
+    for (row, open, text) in [(wall, true, "loop"), (hallway, false, "aren't connected")] {
+        let tiles = make_level_with_seam(&config, row, open);
+        let level: Vec<&Tile> = tiles.iter().collect();
+
+        // The seam still matches, so only the level as a whole is wrong
+        assert!(check_seam(&tiles[0], &tiles[1], Direction::Right).is_empty());
+        let violations = check_level(&level);
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        assert!(violations[0].message.contains(text), "{:?}", violations);
+    }
+}
+
+#[test]
+fn rejects_invalid_level_counts() {
+    let config = make_config(3, 8, Algorithm::Backtracker);
+    assert!(validate_maze(&config, 0).is_err());
+    assert!(validate_maze(&config, config.max_levels() + 1).is_err());
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use std::fs::{copy, read_to_string, remove_file, write};
+use std::path::{Path, PathBuf};
+
+use nested_mazes::error::MazeError;
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::tileset::{find_missing_files, get_max_checked_levels};
+use nested_mazes::verify::verify_tileset;
+
+use common::{make_config, TempDir, TilesetOptions};
+
+fn generate(name: &str, seed: u64) -> TempDir {
+    common::generate(&format!("verify-{}", name), TilesetOptions {
+        levels: 4,
+        config: make_config(seed, 8, Algorithm::Kruskal),
+        ..TilesetOptions::default()
+    })
+}
+
+fn tile_path(directory: &Path, level: usize, x: usize, y: usize) -> PathBuf {
+    directory.join(format!("tiles/{}.{}.{}.glb", level, x, y))
+}
+
+#[test]
+fn generated_tileset_is_valid() {
+    let directory = generate("valid", 1);
+    let report = verify_tileset(&directory).unwrap();
+
+    assert_eq!(report.tiles_checked, 1 + 4 + 16 + 64);
+    assert!(report.violations.is_empty(), "{:?}", report.violations);
+}
+
+#[test]
+fn detects_tiles_from_another_maze() {
+    let directory = generate("swapped", 2);
+    let other = generate("other", 3);
+    copy(tile_path(&other, 2, 1, 2), tile_path(&directory, 2, 1, 2)).unwrap();
+    remove_file(tile_path(&directory, 3, 0, 0)).unwrap();
+
+    let report = verify_tileset(&directory).unwrap();
+
+    assert_eq!(report.tiles_checked, 1 + 4 + 16 + 64 - 1);
+
+    let has_violation = |level, x, y, text: &str| {
+        report.violations.iter().any(|violation| {
+            (violation.level, violation.x, violation.y) == (level, x, y) &&
+                violation.message.contains(text)
+        })
+    };
+    assert!(has_violation(3, 0, 0, "missing"));
+    // The swapped tile disagrees with its parent and its children
+    assert!(has_violation(2, 1, 2, "parent cell"));
+    assert!(has_violation(3, 2, 4, "parent cell"));
+    // and with at least one of its neighbors
+    let seam_mismatches = report.violations
+        .iter()
+        .filter(|violation| violation.message.contains("neighboring tile"))
+        .count();
+    assert!(seam_mismatches > 0);
+}
+
+#[test]
+fn rejects_absurd_level_counts() {
+    let directory = generate("levels", 4);
+    let tileset_path = directory.join("tileset.json");
+    let tileset_json = read_to_string(&tileset_path).unwrap();
+    let set_levels = |levels| write(&tileset_path, tileset_json.replace(
+        "\"availableLevels\": 4",
+        &format!("\"availableLevels\": {}", levels)
+    )).unwrap();
+
+    // Whole levels have to fit in memory, so deeper tilesets need smaller
+    // grids
+    assert_eq!(get_max_checked_levels(16), 8);
+    assert_eq!(get_max_checked_levels(256), 4);
+    assert_eq!(get_max_checked_levels(8), 9);
+
+    // The 8x8 tileset can claim up to 9 levels, it's just missing some
+    set_levels("9");
+    assert!(!find_missing_files(&directory).unwrap().is_empty());
+
+    for levels in ["0", "10", "17", "64", "1000000"] {
+        set_levels(levels);
+
+        let missing = find_missing_files(&directory);
+        let report = verify_tileset(&directory);
+
+        assert!(matches!(missing, Err(MazeError::InvalidInput(_))), "{}", levels);
+        assert!(matches!(report, Err(MazeError::InvalidInput(_))), "{}", levels);
+    }
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use nested_mazes::glb::Glb;
+use nested_mazes::grid::Grid;
+use nested_mazes::grid_coords::GridCoords;
+use nested_mazes::maze_generator::Algorithm;
+use nested_mazes::tile::Tile;
+use nested_mazes::tile_format::{ContentLayout, TileFormat, TileGeometry};
+use nested_mazes::tileset::find_missing_files;
+use nested_mazes::verify::verify_tileset;
+use nested_mazes::walls::{make_meshes, WallStyle};
+
+use common::{generate, make_config, TilesetOptions};
+
+fn make_tile() -> Tile {
+    Tile::generate(&make_config(5, 8, Algorithm::Backtracker), 0, 0, 0).unwrap()
+}
+
+fn walls_format(style: WallStyle) -> TileFormat {
+    TileFormat {
+        geometry: TileGeometry::Walls(style),
+        ..TileFormat::default()
+    }
+}
+
+/// 2x2 cells with a single connection across the bottom row
+fn make_small_grid() -> Grid {
+    let mut grid = Grid::new(2);
+    grid.connect(GridCoords { x: 0, y: 0 }, GridCoords { x: 1, y: 0 });
+    grid
+}
+
+#[test]
+fn walls_are_merged_into_runs() {
+    let style = WallStyle { floor: false, solution: false, ..WallStyle::default() };
+    let meshes = make_meshes(&make_small_grid(), &style);
+
+    // 3 horizontal runs and 3 vertical runs rather than a box for each of
+    // the 11 sides. Each box is a top and 4 sides, except that the ends
+    // that stop against another wall are left out: both ends of the
+    // horizontal runs and of the short run in the middle.
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].name, "Walls");
+    assert_eq!(meshes[0].mesh.get_triangle_count(), (6 * 5 - 8) * 2);
+}
+
+#[test]
+fn corners_dont_overlap() {
+    let style = WallStyle { thickness: 0.2, floor: false, solution: false, ..WallStyle::default() };
+    let meshes = make_meshes(&make_small_grid(), &style);
+    let mesh = &meshes[0].mesh;
+
+    // Every quad has its own 4 vertices, so the tops are easy to pick out
+    let top_area: f32 = mesh.positions
+        .chunks(4)
+        .zip(mesh.normals.chunks(4))
+        .filter(|(_, normals)| normals[0] == [0.0, 1.0, 0.0])
+        .map(|(corners, _)| {
+            let xs = corners.iter().map(|corner| corner[0]);
+            let zs = corners.iter().map(|corner| corner[2]);
+            let width = xs.clone().fold(f32::MIN, f32::max) - xs.fold(f32::MAX, f32::min);
+            let depth = zs.clone().fold(f32::MIN, f32::max) - zs.fold(f32::MAX, f32::min);
+            width * depth
+        })
+        .sum();
+
+    // Cells are 1 unit wide, so the walls are 0.2 thick. The outer walls are
+    // cut in half at the edge of the tile, then there's the wall across the
+    // middle and half of the wall down the middle, which stops at both ends.
+    let outline = 2.0 * 2.0 - 1.8 * 1.8;
+    let middle = 1.8 * 0.2 + 0.8 * 0.2;
+    assert!((top_area - (outline + middle)).abs() < 1e-5, "{}", top_area);
+}
+
+#[test]
+fn walls_stay_inside_the_tile() {
+    let tile = make_tile();
+    let style = WallStyle { height: 2.0, ..WallStyle::default() };
+    let meshes = make_meshes(tile.get_grid(), &style);
+
+    let names: Vec<&str> = meshes.iter().map(|maze_mesh| maze_mesh.name).collect();
+    assert_eq!(names, ["Walls", "Floor", "Solution"]);
+    for maze_mesh in &meshes {
+        let mesh = &maze_mesh.mesh;
+        assert_eq!(mesh.positions.len(), mesh.normals.len());
+        assert!(mesh.indices.iter().all(|&index| (index as usize) < mesh.positions.len()));
+        for [x, y, z] in &mesh.positions {
+            assert!((-1.0..=1.0).contains(x) && (-1.0..=1.0).contains(z));
+            // 2 cells tall, and a cell is 1/4 of the tile
+            assert!((0.0..=0.5).contains(y));
+        }
+    }
+}
+
+#[test]
+fn bad_styles_are_rejected() {
+    for style in [
+        WallStyle { height: 0.0, ..WallStyle::default() },
+        WallStyle { height: f32::NAN, ..WallStyle::default() },
+        WallStyle { thickness: 1.0, ..WallStyle::default() },
+        WallStyle { solution_width: 0.0, ..WallStyle::default() },
+    ] {
+        assert!(walls_format(style).validate().is_err(), "{:?}", style);
+    }
+    assert!(walls_format(WallStyle::default()).validate().is_ok());
+}
+
+#[test]
+fn wall_glb_round_trips() {
+    let tile = make_tile();
+    let format = walls_format(WallStyle::default());
+    let glb_bytes = tile.make_glb(&format).unwrap();
+    let glb = Glb::parse(&glb_bytes).unwrap();
+
+    // The walls aren't shared, so everything is in the BIN chunk
+    assert_eq!(glb.json["buffers"].as_array().unwrap().len(), 1);
+    let primitives = glb.json["meshes"][0]["primitives"].as_array().unwrap();
+    assert_eq!(primitives.len(), 3);
+    assert_eq!(glb.json["materials"].as_array().unwrap().len(), 3);
+    for (index, primitive) in primitives.iter().enumerate() {
+        assert_eq!(primitive["material"], index);
+        assert!(primitive["attributes"].get("TEXCOORD_0").is_some());
+        assert!(primitive["extensions"]["EXT_mesh_features"]["featureIds"].is_array());
+    }
+
+    // The count in the tile metadata matches the index buffers
+    let index_count: u64 = primitives.iter()
+        .map(|primitive| {
+            let accessor = primitive["indices"].as_u64().unwrap() as usize;
+            glb.json["accessors"][accessor]["count"].as_u64().unwrap()
+        })
+        .sum();
+    let content = tile.make_files(&format).unwrap();
+    assert_eq!(content.triangle_count, index_count as usize / 3);
+    assert_eq!(content.files[0].1, glb_bytes);
+    assert_eq!(tile.make_files(&TileFormat::default()).unwrap().triangle_count, 2);
+
+    let decoded = Tile::from_glb(&glb_bytes).unwrap();
+    assert_eq!((decoded.level, decoded.x, decoded.y), (0, 0, 0));
+    assert_eq!(decoded.get_grid().to_image_bytes(), tile.get_grid().to_image_bytes());
+
+    let bare = walls_format(WallStyle { floor: false, solution: false, ..WallStyle::default() });
+    let glb = Glb::parse(&tile.make_glb(&bare).unwrap()).unwrap();
+    assert_eq!(glb.json["meshes"][0]["primitives"].as_array().unwrap().len(), 1);
+    assert_eq!(glb.json["materials"][0]["name"], "Walls");
+}
+
+#[test]
+fn wall_tilesets_are_complete() {
+    for (name, content) in [("glb", ContentLayout::SharedGeometry), ("gltf", ContentLayout::Gltf)] {
+        let directory = generate(&format!("walls-{}", name), TilesetOptions {
+            levels: 2,
+            config: make_config(13, 8, Algorithm::Kruskal),
+            format: TileFormat { content, ..walls_format(WallStyle::default()) },
+            ..TilesetOptions::default()
+        });
+
+        let missing = find_missing_files(&directory).unwrap();
+        let has_geometry = directory.join("tiles/geometry.bin").exists();
+        let has_buffer = directory.join("tiles/1.0.1.bin").exists();
+        let report = verify_tileset(&directory).unwrap();
+
+        assert!(missing.is_empty(), "{}: {:?}", name, missing);
+        assert!(!has_geometry, "{}", name);
+        assert_eq!(has_buffer, content == ContentLayout::Gltf, "{}", name);
+        assert!(report.violations.is_empty(), "{}: {:?}", name, report.violations);
+    }
+}